@@ -38,7 +38,7 @@ fn main() {
             }
             
             // Test con strategia json_photo_taken
-            let proposta_json = calcola_proposta_con_strategia(&foto, "json_photo_taken");
+            let proposta_json = calcola_proposta_con_strategia(foto, "json_photo_taken");
             if let Some(ref dt) = proposta_json {
                 println!("  Test json_photo_taken: {} (ora={}:{}:{})", 
                          dt.format("%Y-%m-%d %H:%M:%S"), 
@@ -48,7 +48,7 @@ fn main() {
             }
             
             // Test con strategia nome_file_preferito
-            let proposta_nome = calcola_proposta_con_strategia(&foto, "nome_file_preferito");
+            let proposta_nome = calcola_proposta_con_strategia(foto, "nome_file_preferito");
             if let Some(ref dt) = proposta_nome {
                 println!("  Test nome_file_preferito: {} (ora={}:{}:{})", 
                          dt.format("%Y-%m-%d %H:%M:%S"), 
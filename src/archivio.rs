@@ -0,0 +1,405 @@
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use crate::{CampiDataExif, FotoData};
+use crate::metadati::{self, MetadatiGoogle, PosizioneGps};
+
+// Per l'EXIF di un JPEG basta l'inizio del file (APP1 è all'inizio e non supera 64 KB)
+const MAX_INTESTAZIONE_JPEG: u64 = 512 * 1024;
+// I RAW (TIFF) possono avere gli IFD ovunque: si leggono interi fino a questo limite
+const MAX_RAW: u64 = 256 * 1024 * 1024;
+const MAX_JSON: u64 = 1024 * 1024;
+
+// Un file dentro un archivio: parte dell'export e percorso interno
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct VoceArchivio {
+    pub archivio: PathBuf,
+    pub nome: String, // es. "Takeout/Google Foto/Photos from 2015/IMG_1.jpg"
+}
+
+// Da dove leggere una foto non estratta: la foto e i file che la accompagnano
+// (JSON e JPG della coppia RAW+JPG), anche se si trovano in parti diverse
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrigineArchivio {
+    pub foto: VoceArchivio,
+    pub compagni: Vec<VoceArchivio>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EsitoEstrazione {
+    pub estratte: Vec<(VoceArchivio, PathBuf)>,
+    pub errori: Vec<String>,
+}
+
+pub fn is_archivio(path: &Path) -> bool {
+    let nome = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    nome.ends_with(".zip") || nome.ends_with(".tgz") || nome.ends_with(".tar.gz") || nome.ends_with(".tar")
+}
+
+// Conta i byte letti dall'archivio compresso, per la barra di avanzamento
+struct LettoreConteggiato<R> {
+    interno: R,
+    letti: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for LettoreConteggiato<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.interno.read(buf)?;
+        self.letti.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for LettoreConteggiato<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.interno.seek(pos)
+    }
+}
+
+// Scorre tutti i file di un archivio, nell'ordine in cui sono memorizzati.
+// Gli zip si potrebbero leggere in ordine sparso, i tgz no: una sola passata va bene per entrambi
+fn per_ogni_voce(archivio: &Path, letti: Arc<AtomicU64>, mut f: impl FnMut(&str, &mut dyn Read) -> Result<(), String>) -> Result<(), String> {
+    let file = fs::File::open(archivio)
+        .map_err(|e| format!("Errore apertura {}: {}", archivio.display(), e))?;
+    let lettore = LettoreConteggiato { interno: std::io::BufReader::new(file), letti };
+    let nome_archivio = archivio.to_string_lossy().to_lowercase();
+
+    if nome_archivio.ends_with(".zip") {
+        let mut zip = zip::ZipArchive::new(lettore)
+            .map_err(|e| format!("Archivio zip non valido {}: {}", archivio.display(), e))?;
+        for i in 0..zip.len() {
+            let mut voce = zip.by_index(i)
+                .map_err(|e| format!("Errore lettura {}: {}", archivio.display(), e))?;
+            if voce.is_dir() {
+                continue;
+            }
+            let nome = voce.name().to_string();
+            f(&nome, &mut voce)?;
+        }
+    } else {
+        let flusso: Box<dyn Read> = if nome_archivio.ends_with(".tar") {
+            Box::new(lettore)
+        } else {
+            Box::new(flate2::read::GzDecoder::new(lettore))
+        };
+        let mut tar = tar::Archive::new(flusso);
+        let voci = tar.entries()
+            .map_err(|e| format!("Archivio tar non valido {}: {}", archivio.display(), e))?;
+        for voce in voci {
+            let mut voce = voce.map_err(|e| format!("Errore lettura {}: {}", archivio.display(), e))?;
+            if !voce.header().entry_type().is_file() {
+                continue;
+            }
+            let nome = voce.path()
+                .map_err(|e| format!("Nome non valido in {}: {}", archivio.display(), e))?
+                .to_string_lossy()
+                .to_string();
+            f(&nome, &mut voce)?;
+        }
+    }
+    Ok(())
+}
+
+fn estensione(nome: &str) -> String {
+    Path::new(nome).extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase()
+}
+
+fn leggi_al_massimo(lettore: &mut dyn Read, limite: u64) -> Result<Vec<u8>, String> {
+    let mut dati = Vec::new();
+    lettore.take(limite).read_to_end(&mut dati).map_err(|e| e.to_string())?;
+    Ok(dati)
+}
+
+struct FotoLetta {
+    voce: VoceArchivio,
+    exif: CampiDataExif,
+    fotocamera: Option<String>,
+}
+
+struct JsonLetto {
+    voce: VoceArchivio,
+    date: (Option<chrono::DateTime<chrono::Utc>>, Option<chrono::DateTime<chrono::Utc>>),
+    metadati: Option<MetadatiGoogle>,
+    posizione: Option<PosizioneGps>,
+}
+
+// Foto, JSON e voci illeggibili di una parte
+type ParteLetta = (Vec<FotoLetta>, Vec<JsonLetto>, Vec<String>);
+
+// Una passata su una parte: EXIF delle foto e contenuto dei JSON, senza estrarre nulla.
+// Una voce illeggibile si salta e si segnala; Err solo se non si legge la parte
+fn leggi_parte(archivio: &Path, letti: Arc<AtomicU64>, foto_lette: &AtomicUsize) -> Result<ParteLetta, String> {
+    let mut foto = Vec::new();
+    let mut json = Vec::new();
+    let mut errori = Vec::new();
+
+    per_ogni_voce(archivio, letti, |nome, lettore| {
+        let voce = VoceArchivio { archivio: archivio.to_path_buf(), nome: nome.to_string() };
+        let ext = estensione(nome);
+        match ext.as_str() {
+            "jpg" | "jpeg" | "orf" | "nef" => {
+                let limite = if ext == "jpg" || ext == "jpeg" { MAX_INTESTAZIONE_JPEG } else { MAX_RAW };
+                let dati = match leggi_al_massimo(lettore, limite) {
+                    Ok(dati) => dati,
+                    Err(e) => {
+                        errori.push(format!("Errore lettura {} in {}: {}", nome, archivio.display(), e));
+                        return Ok(());
+                    }
+                };
+                let (exif, fotocamera) = crate::leggi_exif_da_bytes(&dati);
+                foto.push(FotoLetta { voce, exif, fotocamera });
+                foto_lette.fetch_add(1, Ordering::Relaxed);
+            }
+            "json" => {
+                let dati = match leggi_al_massimo(lettore, MAX_JSON) {
+                    Ok(dati) => dati,
+                    Err(e) => {
+                        errori.push(format!("Errore lettura {} in {}: {}", nome, archivio.display(), e));
+                        return Ok(());
+                    }
+                };
+                let valore = serde_json::from_str(&String::from_utf8_lossy(&dati)).unwrap_or_default();
+                json.push(JsonLetto {
+                    voce,
+                    date: crate::leggi_data_json_da_valore(&valore),
+                    metadati: metadati::leggi_metadati_json_da_valore(&valore),
+                    posizione: metadati::leggi_posizione_json_da_valore(&valore),
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    })?;
+
+    Ok((foto, json, errori))
+}
+
+// Percorso "virtuale" di una voce: le parti dell'export sono unite come se fossero
+// state estratte tutte nella cartella del primo archivio
+fn percorso_virtuale(radice: &Path, nome: &str) -> PathBuf {
+    radice.join(nome)
+}
+
+// Legge le foto da tutte le parti dell'export, abbinando JSON e JPG anche tra parti diverse.
+// `progresso` riceve prima il totale e poi l'avanzamento, in MB compressi letti
+pub fn leggi_foto_da_archivi(archivi: &[PathBuf], progresso: Option<std::sync::mpsc::Sender<usize>>) -> Result<Vec<FotoData>, String> {
+    let radice = archivi.first()
+        .and_then(|a| a.parent())
+        .ok_or("Nessun archivio selezionato")?
+        .to_path_buf();
+
+    let totale_mb: u64 = archivi.iter()
+        .map(|a| fs::metadata(a).map(|m| m.len()).unwrap_or(0))
+        .sum::<u64>() / (1024 * 1024);
+    if let Some(ref sender) = progresso {
+        let _ = sender.send(totale_mb as usize);
+    }
+
+    let letti = Arc::new(AtomicU64::new(0));
+    let foto_lette = AtomicUsize::new(0);
+
+    // Le parti sono indipendenti: si leggono in parallelo, il progresso arriva da questo thread
+    let risultati = std::thread::scope(|s| {
+        let lettura = s.spawn(|| {
+            archivi.par_iter()
+                .map(|a| leggi_parte(a, letti.clone(), &foto_lette))
+                .collect::<Vec<_>>()
+        });
+        while !lettura.is_finished() {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            if let Some(ref sender) = progresso {
+                let _ = sender.send((letti.load(Ordering::Relaxed) / (1024 * 1024)) as usize);
+            }
+        }
+        lettura.join().unwrap_or_default()
+    });
+    eprintln!("[DEBUG] Archivi letti: {} foto", foto_lette.load(Ordering::Relaxed));
+
+    let mut indice_foto: HashMap<PathBuf, FotoLetta> = HashMap::new();
+    let mut indice_json: HashMap<PathBuf, JsonLetto> = HashMap::new();
+    for risultato in risultati {
+        let (foto, json, errori) = risultato?;
+        for errore in errori {
+            eprintln!("Voce saltata: {}", errore);
+        }
+        for f in foto {
+            indice_foto.entry(percorso_virtuale(&radice, &f.voce.nome)).or_insert(f);
+        }
+        for j in json {
+            indice_json.entry(percorso_virtuale(&radice, &j.voce.nome)).or_insert(j);
+        }
+    }
+
+    let mut foto_list: Vec<FotoData> = indice_foto.iter()
+        .map(|(path, letta)| {
+            let jpg = crate::trova_file_jpg_associato_con(path, |p| indice_foto.contains_key(p))
+                .and_then(|p| indice_foto.get(&p));
+            let (exif, fotocamera) = match jpg {
+                Some(j) => (j.exif, j.fotocamera.clone()),
+                None => (letta.exif, letta.fotocamera.clone()),
+            };
+
+            let mut foto = crate::nuova_foto(path.clone(), exif, fotocamera);
+            let mut compagni = Vec::new();
+            if let Some(json) = crate::trova_file_json_con(path, |p| indice_json.contains_key(p))
+                .and_then(|p| indice_json.get(&p)) {
+                (foto.data_json, foto.data_json_creation) = json.date;
+                foto.metadati_json = json.metadati.clone();
+                foto.posizione_json = json.posizione;
+                compagni.push(json.voce.clone());
+            }
+            if let Some(j) = jpg {
+                compagni.push(j.voce.clone());
+                foto.jpg_associato_usato = foto.exif_datetime_original.is_some();
+            }
+            foto.origine_archivio = Some(OrigineArchivio { foto: letta.voce.clone(), compagni });
+
+            crate::completa_foto(&mut foto);
+            foto
+        })
+        .collect();
+
+    foto_list.sort_by(|a, b| a.nome_file.cmp(&b.nome_file));
+    Ok(foto_list)
+}
+
+// Percorso di destinazione sicuro: niente percorsi assoluti o ".." dentro l'archivio
+pub fn destinazione_voce(dir_output: &Path, nome: &str) -> Result<PathBuf, String> {
+    let relativo = Path::new(nome);
+    if relativo.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return Err(format!("Percorso non valido nell'archivio: {}", nome));
+    }
+    Ok(dir_output.join(relativo))
+}
+
+// Voci da estrarre per un insieme di foto (foto + JSON + JPG della coppia), senza ripetizioni
+pub fn voci_da_estrarre(foto_list: &[&FotoData]) -> Vec<VoceArchivio> {
+    let mut voci: Vec<VoceArchivio> = Vec::new();
+    for origine in foto_list.iter().filter_map(|f| f.origine_archivio.as_ref()) {
+        for voce in std::iter::once(&origine.foto).chain(origine.compagni.iter()) {
+            if !voci.contains(voce) {
+                voci.push(voce.clone());
+            }
+        }
+    }
+    voci
+}
+
+fn copia_in(lettore: &mut dyn Read, destinazione: &Path) -> Result<(), String> {
+    if destinazione.exists() {
+        return Err(format!("{} esiste già", destinazione.display()));
+    }
+    if let Some(dir) = destinazione.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Errore creazione cartella {}: {}", dir.display(), e))?;
+    }
+    let mut file = fs::File::create(destinazione)
+        .map_err(|e| format!("Errore creazione {}: {}", destinazione.display(), e))?;
+    std::io::copy(lettore, &mut file)
+        .map(|_| ())
+        .map_err(|e| format!("Errore estrazione {}: {}", destinazione.display(), e))
+}
+
+// Estrae le voci in dir_output mantenendo la struttura dell'export.
+// `progresso` conta le voci estratte
+pub fn estrai_voci(voci: &[VoceArchivio], dir_output: &Path, progresso: Option<&AtomicUsize>) -> EsitoEstrazione {
+    let mut per_archivio: HashMap<&Path, HashMap<&str, &VoceArchivio>> = HashMap::new();
+    for voce in voci {
+        per_archivio.entry(voce.archivio.as_path()).or_default().insert(voce.nome.as_str(), voce);
+    }
+
+    let mut esito = EsitoEstrazione::default();
+    for (archivio, richieste) in per_archivio {
+        let nome_archivio = archivio.to_string_lossy().to_lowercase();
+        let mut trovate = 0;
+
+        let risultato = if nome_archivio.ends_with(".zip") {
+            // Negli zip si accede direttamente alle voci richieste
+            fs::File::open(archivio)
+                .map_err(|e| format!("Errore apertura {}: {}", archivio.display(), e))
+                .and_then(|f| zip::ZipArchive::new(std::io::BufReader::new(f))
+                    .map_err(|e| format!("Archivio zip non valido {}: {}", archivio.display(), e)))
+                .map(|mut zip| {
+                    for (nome, voce) in &richieste {
+                        let estratta = destinazione_voce(dir_output, nome).and_then(|dest| {
+                            let mut lettore = zip.by_name(nome)
+                                .map_err(|e| format!("{} non trovato in {}: {}", nome, archivio.display(), e))?;
+                            copia_in(&mut lettore, &dest).map(|_| dest)
+                        });
+                        match estratta {
+                            Ok(dest) => esito.estratte.push(((*voce).clone(), dest)),
+                            Err(e) => esito.errori.push(e),
+                        }
+                        trovate += 1;
+                        if let Some(contatore) = progresso {
+                            contatore.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                })
+        } else {
+            // tgz: una passata sull'intero archivio
+            per_ogni_voce(archivio, Arc::new(AtomicU64::new(0)), |nome, lettore| {
+                if let Some(voce) = richieste.get(nome) {
+                    match destinazione_voce(dir_output, nome).and_then(|dest| copia_in(lettore, &dest).map(|_| dest)) {
+                        Ok(dest) => esito.estratte.push(((*voce).clone(), dest)),
+                        Err(e) => esito.errori.push(e),
+                    }
+                    trovate += 1;
+                    if let Some(contatore) = progresso {
+                        contatore.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                Ok(())
+            })
+        };
+
+        if let Err(e) = risultato {
+            esito.errori.push(e);
+        }
+        if trovate < richieste.len() {
+            esito.errori.push(format!("{} file non trovati in {}", richieste.len() - trovate, archivio.display()));
+        }
+    }
+    esito
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn leggi_parte_salta_la_voce_illeggibile() {
+        let dir = std::env::temp_dir().join(format!("corrigi-exif-archivio-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let archivio = dir.join("takeout-001.zip");
+
+        let mut zip = zip::ZipWriter::new(fs::File::create(&archivio).unwrap());
+        let opzioni = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("Takeout/IMG_1.jpg.json", opzioni).unwrap();
+        zip.write_all(br#"{"photoTakenTime": {"timestamp": "1000"}}"#).unwrap();
+        zip.start_file("Takeout/IMG_2.jpg.json", opzioni).unwrap();
+        zip.write_all(br#"{"photoTakenTime": {"timestamp": "2000"}}"#).unwrap();
+        zip.finish().unwrap();
+
+        // Un byte cambiato nei dati della prima voce: il CRC non torna più
+        let mut dati = fs::read(&archivio).unwrap();
+        let inizio = dati.windows(4).position(|w| w == b"1000").unwrap();
+        dati[inizio] = b'9';
+        fs::write(&archivio, dati).unwrap();
+
+        let (foto, json, errori) = leggi_parte(&archivio, Arc::new(AtomicU64::new(0)), &AtomicUsize::new(0)).unwrap();
+        assert!(foto.is_empty());
+        assert_eq!(errori.len(), 1);
+        assert!(errori[0].contains("IMG_1.jpg.json"));
+        assert_eq!(json.len(), 1);
+        assert_eq!(json[0].date.0, chrono::DateTime::from_timestamp(2000, 0));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
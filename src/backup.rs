@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// Copie dei file prima di scriverli: exiftool scrive con -overwrite_original e -all=
+// cancella anche i tag che non tocchiamo, quindi l'annullamento dal journal riporta
+// le date ma non il resto. Ogni esecuzione ha la sua cartella, con lo stesso nome del
+// journal (o, senza journal, con la data dell'esecuzione), e dentro l'albero dei path
+// assoluti degli originali. Sui filesystem che lo supportano (Btrfs, XFS, APFS) la copia
+// è un reflink e non occupa spazio finché l'originale non cambia
+
+const CARTELLA_SENZA_JOURNAL: &str = "senza-journal";
+
+// Cartella predefinita: $XDG_DATA_HOME/corrigi-exif/backup
+pub fn dir_predefinita() -> Option<PathBuf> {
+    crate::percorsi::dir_dati().map(|d| d.join("backup"))
+}
+
+pub fn dir_radice(dir: Option<&Path>) -> Option<PathBuf> {
+    dir.map(Path::to_path_buf).or_else(dir_predefinita)
+}
+
+// Cartella delle copie di un'esecuzione: una ripresa nello stesso journal usa la stessa.
+// Senza journal il nome ha la data: una cartella condivisa da tutte le esecuzioni terrebbe
+// le copie della prima (copia non sovrascrive) e pulisci non la farebbe mai scadere.
+// Va quindi calcolata una volta per esecuzione
+pub fn dir_esecuzione(dir: Option<&Path>, journal: Option<&Path>) -> Option<PathBuf> {
+    let nome = journal
+        .and_then(|j| j.file_stem())
+        .map(|s| s.to_os_string())
+        .unwrap_or_else(|| format!("{}-{}", chrono::Local::now().format("%Y%m%d-%H%M%S%.3f"), CARTELLA_SENZA_JOURNAL).into());
+    dir_radice(dir).map(|d| d.join(nome))
+}
+
+// Path della copia: l'albero del path assoluto sotto la cartella dell'esecuzione
+pub fn percorso_copia(dir_esecuzione: &Path, originale: &Path) -> PathBuf {
+    let assoluto = std::path::absolute(originale).unwrap_or_else(|_| originale.to_path_buf());
+    let mut copia = dir_esecuzione.to_path_buf();
+    for componente in assoluto.components() {
+        match componente {
+            // C: su Windows diventa una cartella "C"
+            Component::Prefix(prefisso) => copia.push(prefisso.as_os_str().to_string_lossy().replace([':', '\\', '?'], "")),
+            Component::Normal(nome) => copia.push(nome),
+            _ => {}
+        }
+    }
+    copia
+}
+
+// Copia (o reflink) con la stessa data di modifica dell'originale. None se la copia
+// c'era già: in una ripresa o in un nuovo tentativo vale quella fatta prima della prima scrittura
+pub fn copia(originale: &Path, dir_esecuzione: &Path) -> Result<Option<PathBuf>, String> {
+    let copia = percorso_copia(dir_esecuzione, originale);
+    if copia.exists() {
+        return Ok(None);
+    }
+    let metadata = fs::metadata(originale).map_err(|e| format!("Errore lettura {}: {}", originale.display(), e))?;
+    // Su una copia a metà (disco pieno) non si deve contare: si scrive in un temporaneo
+    let mut temporaneo = copia.as_os_str().to_os_string();
+    temporaneo.push(".tmp");
+    let temporaneo = PathBuf::from(temporaneo);
+    let mut tentativo = 0;
+    loop {
+        if let Some(dir) = copia.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Errore creazione {}: {}", dir.display(), e))?;
+        }
+        match reflink_copy::reflink_or_copy(originale, &temporaneo).and_then(|_| fs::rename(&temporaneo, &copia)) {
+            Ok(()) => break,
+            // Un'altra scrittura ha appena tolto la cartella rimasta vuota (elimina_copia): si ricrea
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && originale.exists() && tentativo < 3 => tentativo += 1,
+            Err(e) => {
+                let _ = fs::remove_file(&temporaneo);
+                return Err(format!("Errore backup {} -> {}: {}", originale.display(), copia.display(), e));
+            }
+        }
+    }
+    let mtime = filetime::FileTime::from_last_modification_time(&metadata);
+    if let Err(e) = filetime::set_file_mtime(&copia, mtime) {
+        eprintln!("[DEBUG] Data di modifica non copiata su {}: {}", copia.display(), e);
+    }
+    Ok(Some(copia))
+}
+
+// Rimette l'originale com'era, data di modifica compresa. La copia resta
+pub fn ripristina(copia: &Path, originale: &Path) -> Result<(), String> {
+    let metadata = fs::metadata(copia).map_err(|e| format!("Errore lettura {}: {}", copia.display(), e))?;
+    let mut temporaneo = originale.as_os_str().to_os_string();
+    temporaneo.push(".ripristino");
+    let temporaneo = PathBuf::from(temporaneo);
+    reflink_copy::reflink_or_copy(copia, &temporaneo)
+        .and_then(|_| fs::rename(&temporaneo, originale))
+        .map_err(|e| {
+            let _ = fs::remove_file(&temporaneo);
+            format!("Errore ripristino {} da {}: {}", originale.display(), copia.display(), e)
+        })?;
+    let mtime = filetime::FileTime::from_last_modification_time(&metadata);
+    filetime::set_file_times(originale, mtime, mtime)
+        .map_err(|e| format!("Errore ripristino data file {}: {}", originale.display(), e))
+}
+
+// Cancella una copia non più necessaria e le cartelle rimaste vuote fino a quella dell'esecuzione
+pub fn elimina_copia(copia: &Path, dir_esecuzione: &Path) -> Result<(), String> {
+    fs::remove_file(copia).map_err(|e| format!("Errore cancellazione {}: {}", copia.display(), e))?;
+    let mut dir = copia.parent();
+    while let Some(d) = dir.filter(|d| d.starts_with(dir_esecuzione)) {
+        // remove_dir fallisce se la cartella non è vuota: lì ci si ferma
+        if fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct EsecuzioneBackup {
+    pub dir: PathBuf,
+    pub file: usize,
+    pub byte: u64, // Dimensione dei file: con i reflink lo spazio occupato davvero è meno
+    pub modificata: SystemTime,
+}
+
+impl EsecuzioneBackup {
+    pub fn eta_giorni(&self) -> u64 {
+        SystemTime::now().duration_since(self.modificata).unwrap_or_default().as_secs() / 86400
+    }
+}
+
+// Esecuzioni con copie, dalla più recente
+pub fn elenca(dir: Option<&Path>) -> Vec<EsecuzioneBackup> {
+    let Some(radice) = dir_radice(dir) else {
+        return Vec::new();
+    };
+    let mut esecuzioni: Vec<EsecuzioneBackup> = fs::read_dir(&radice)
+        .map(|entries| entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|dir| {
+            let mut file = 0;
+            let mut byte = 0;
+            // L'ultima copia fatta dice quando l'esecuzione è finita
+            let mut modificata = fs::metadata(&dir).and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+            for entry in walkdir::WalkDir::new(&dir).into_iter().flatten().filter(|e| e.file_type().is_file()) {
+                if let Ok(metadata) = entry.metadata() {
+                    file += 1;
+                    byte += metadata.len();
+                    if let Ok(creato) = metadata.created() {
+                        modificata = modificata.max(creato);
+                    }
+                }
+            }
+            EsecuzioneBackup { dir, file, byte, modificata }
+        })
+        .collect();
+    esecuzioni.sort_by_key(|e| std::cmp::Reverse(e.modificata));
+    esecuzioni
+}
+
+// Cancella le esecuzioni più vecchie di `giorni`. Restituisce (cancellate, byte liberati)
+pub fn pulisci(dir: Option<&Path>, giorni: u64) -> Result<(usize, u64), String> {
+    let limite = SystemTime::now() - Duration::from_secs(giorni * 86400);
+    let mut cancellate = 0;
+    let mut byte = 0;
+    for esecuzione in elenca(dir).into_iter().filter(|e| e.modificata < limite) {
+        fs::remove_dir_all(&esecuzione.dir)
+            .map_err(|e| format!("Errore cancellazione {}: {}", esecuzione.dir.display(), e))?;
+        eprintln!("[DEBUG] Backup cancellato: {} ({} file)", esecuzione.dir.display(), esecuzione.file);
+        cancellate += 1;
+        byte += esecuzione.byte;
+    }
+    Ok((cancellate, byte))
+}
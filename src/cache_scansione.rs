@@ -0,0 +1,313 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::{FotoData, SorgentiFoto};
+
+// Cache su disco di quanto la scansione legge dai file, per riaprire una libreria
+// grande senza rileggere ogni foto. Una voce vale finché la foto e i file letti con
+// lei (JSON, sidecar, JPG della coppia) hanno la stessa firma e non ne sono comparsi
+// di nuovi. Le scritture e gli annullamenti tolgono comunque le voci dei file che
+// toccano (rimuovi): exiftool -P lascia la data di modifica, e una data riscritta con
+// un'altra ha la stessa lunghezza
+
+// Cambia se cambia SorgentiFoto o il modo di leggerla: le cache vecchie si ignorano
+const VERSIONE_CACHE: u32 = 2;
+pub const NOME_FILE_CACHE: &str = "scansione.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Firma {
+    dimensione: u64,
+    secondi: i64,
+    nanosecondi: u32,
+    // Inode e ctime cambiano anche quando la data di modifica viene rimessa com'era
+    // (exiftool -P riscrive il file e lo rinomina). Zero dove non ci sono
+    #[serde(default)]
+    inode: u64,
+    #[serde(default)]
+    ctime_secondi: i64,
+    #[serde(default)]
+    ctime_nanosecondi: i64,
+}
+
+impl Firma {
+    pub(crate) fn di(path: &Path) -> Option<Firma> {
+        let metadata = fs::metadata(path).ok()?;
+        let mtime = filetime::FileTime::from_last_modification_time(&metadata);
+        #[cfg(unix)]
+        let (inode, ctime_secondi, ctime_nanosecondi) = {
+            use std::os::unix::fs::MetadataExt;
+            (metadata.ino(), metadata.ctime(), metadata.ctime_nsec())
+        };
+        #[cfg(not(unix))]
+        let (inode, ctime_secondi, ctime_nanosecondi) = (0, 0, 0);
+        Some(Firma {
+            dimensione: metadata.len(),
+            secondi: mtime.unix_seconds(),
+            nanosecondi: mtime.nanoseconds(),
+            inode,
+            ctime_secondi,
+            ctime_nanosecondi,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoceCache {
+    firma: Firma,
+    compagni: Vec<(PathBuf, Firma)>,
+    sorgenti: SorgentiFoto,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct FileCache {
+    versione: u32,
+    voci: HashMap<PathBuf, VoceCache>,
+}
+
+#[derive(Serialize)]
+struct FileCacheDaScrivere<'a> {
+    versione: u32,
+    voci: &'a HashMap<PathBuf, VoceCache>,
+}
+
+#[derive(Debug, Default)]
+pub struct CacheScansione {
+    file: PathBuf,
+    voci: HashMap<PathBuf, VoceCache>,
+    modificata: bool,
+    rileggi_tutto: bool, // Scansione completa: nessuna voce si usa, tutte si riscrivono
+}
+
+// Le voci sono per path assoluto, così una scansione con un path relativo da
+// un'altra cartella ritrova le stesse foto
+fn chiave(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// Cartella predefinita: $XDG_CACHE_HOME/corrigi-exif
+pub fn dir_predefinita() -> Option<PathBuf> {
+    crate::percorsi::dir_cache()
+}
+
+impl CacheScansione {
+    // Cache nella cartella indicata; vuota se il file manca, è illeggibile o di un'altra versione
+    pub fn carica(dir: &Path) -> Self {
+        let file = dir.join(NOME_FILE_CACHE);
+        let voci = match fs::read(&file) {
+            Ok(contenuto) => match serde_json::from_slice::<FileCache>(&contenuto) {
+                Ok(cache) if cache.versione == VERSIONE_CACHE => cache.voci,
+                Ok(_) => {
+                    eprintln!("[DEBUG] Cache di scansione {} di un'altra versione, ignorata", file.display());
+                    HashMap::new()
+                }
+                Err(e) => {
+                    eprintln!("[DEBUG] Cache di scansione {} illeggibile, ignorata: {}", file.display(), e);
+                    HashMap::new()
+                }
+            },
+            Err(_) => HashMap::new(),
+        };
+        eprintln!("[DEBUG] Cache di scansione {}: {} voci", file.display(), voci.len());
+        CacheScansione { file, voci, modificata: false, rileggi_tutto: false }
+    }
+
+    pub fn len(&self) -> usize {
+        self.voci.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.voci.is_empty()
+    }
+
+    // Cache nella cartella scelta o in quella predefinita
+    pub fn carica_da(dir: Option<&Path>) -> Option<Self> {
+        match dir {
+            Some(dir) => Some(Self::carica(dir)),
+            None => dir_predefinita().map(|d| Self::carica(&d)),
+        }
+    }
+
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    // Scansione completa: le foto lette si rileggono dal disco e le loro voci si riscrivono
+    pub fn forza_rilettura(&mut self) {
+        self.rileggi_tutto = true;
+    }
+
+    // Sorgenti salvate, se la foto e i suoi compagni non sono cambiati
+    pub fn cerca(&self, path: &Path) -> Option<SorgentiFoto> {
+        if self.rileggi_tutto {
+            return None;
+        }
+        let voce = self.voci.get(&chiave(path))?;
+        if Firma::di(path)? != voce.firma {
+            return None;
+        }
+        // Stessi compagni di prima: un JSON o un sidecar nuovo invalida la voce
+        let jpg_associato = crate::trova_file_jpg_associato(path);
+        let json = crate::trova_file_json(path);
+        let sidecar_xmp = crate::sidecar::trova_sidecar_xmp(path);
+        let attuali: Vec<PathBuf> = [&jpg_associato, &json, &sidecar_xmp].into_iter().flatten().map(|p| chiave(p)).collect();
+        let salvati: Vec<PathBuf> = voce.compagni.iter().map(|(p, _)| p.clone()).collect();
+        if attuali != salvati {
+            return None;
+        }
+        for (compagno, firma) in &voce.compagni {
+            if Firma::di(compagno)? != *firma {
+                return None;
+            }
+        }
+        // Path dei compagni come li vede questa scansione (relativi o assoluti come la foto)
+        Some(SorgentiFoto { jpg_associato, json, sidecar_xmp, ..voce.sorgenti.clone() })
+    }
+
+    // Legge la foto dalla cache o dal disco; la voce nuova va poi passata a `inserisci`
+    pub fn leggi(cache: Option<&CacheScansione>, path: PathBuf) -> (FotoData, Option<VoceCache>) {
+        if let Some(sorgenti) = cache.and_then(|c| c.cerca(&path)) {
+            return (crate::foto_da_sorgenti(path, sorgenti), None);
+        }
+        // Firma presa prima della lettura: se il file cambia nel frattempo la voce risulta vecchia
+        let firma = Firma::di(&path);
+        let sorgenti = crate::leggi_sorgenti(&path);
+        let voce = firma.and_then(|firma| {
+            let compagni = sorgenti.compagni()
+                .map(|p| Firma::di(p).map(|f| (chiave(p), f)))
+                .collect::<Option<Vec<_>>>()?;
+            Some(VoceCache { firma, compagni, sorgenti: sorgenti.clone() })
+        });
+        (crate::foto_da_sorgenti(path, sorgenti), voce)
+    }
+
+    pub fn inserisci(&mut self, path: &Path, voce: VoceCache) {
+        self.voci.insert(chiave(path), voce);
+        self.modificata = true;
+    }
+
+    // Toglie la voce di un file appena scritto, ripristinato o spostato e quelle che lo
+    // hanno tra i compagni (il RAW di un JPG scritto, la foto di un sidecar)
+    pub fn rimuovi(&mut self, path: &Path) {
+        self.rimuovi_tutti([path]);
+    }
+
+    // Come rimuovi, con un solo passaggio sulle voci per tutti i file di un'esecuzione
+    pub fn rimuovi_tutti<'a>(&mut self, path: impl IntoIterator<Item = &'a Path>) {
+        let path: HashSet<PathBuf> = path.into_iter().map(chiave).collect();
+        let prima = self.voci.len();
+        self.voci.retain(|foto, voce| {
+            !path.contains(foto) && !voce.compagni.iter().any(|(compagno, _)| path.contains(compagno))
+        });
+        self.modificata |= self.voci.len() != prima;
+    }
+
+    // Toglie le foto sotto `directory` che la scansione non ha più trovato
+    pub fn mantieni_solo(&mut self, directory: &Path, presenti: &[&Path]) {
+        let directory = chiave(directory);
+        let presenti: HashSet<PathBuf> = presenti.iter().map(|p| chiave(p)).collect();
+        let prima = self.voci.len();
+        self.voci.retain(|path, _| !path.starts_with(&directory) || presenti.contains(path));
+        self.modificata |= self.voci.len() != prima;
+    }
+
+    // Scrive su un file temporaneo e lo rinomina: una scansione interrotta non lascia una cache a metà
+    pub fn salva(&mut self) -> Result<(), String> {
+        if !self.modificata {
+            return Ok(());
+        }
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Errore creazione {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_vec(&FileCacheDaScrivere { versione: VERSIONE_CACHE, voci: &self.voci })
+            .map_err(|e| e.to_string())?;
+        let temporaneo = self.file.with_extension("json.tmp");
+        fs::write(&temporaneo, json)
+            .and_then(|_| fs::rename(&temporaneo, &self.file))
+            .map_err(|e| format!("Errore scrittura cache {}: {}", self.file.display(), e))?;
+        self.modificata = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cartella temporanea con una foto finta: per la cache conta solo la firma
+    fn cartella(nome: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("corrigi-exif-cache-{}-{}", nome, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn cache_con(dir: &Path, foto: &Path) -> CacheScansione {
+        let mut cache = CacheScansione { file: dir.join(NOME_FILE_CACHE), ..Default::default() };
+        let (_, voce) = CacheScansione::leggi(None, foto.to_path_buf());
+        cache.inserisci(foto, voce.unwrap());
+        cache
+    }
+
+    #[test]
+    fn cerca_stessa_dimensione_con_data_nuova() {
+        let dir = cartella("data");
+        let foto = dir.join("IMG_0001.jpg");
+        fs::write(&foto, "2019:07:14 09:05:03").unwrap();
+        let mtime = filetime::FileTime::from_last_modification_time(&fs::metadata(&foto).unwrap());
+        let cache = cache_con(&dir, &foto);
+        assert!(cache.cerca(&foto).is_some());
+
+        // Come exiftool -P: file nuovo rinominato sopra l'originale, stessa dimensione e data di modifica
+        let temporaneo = dir.join("IMG_0001.jpg_exiftool_tmp");
+        fs::write(&temporaneo, "2020:01:01 00:00:00").unwrap();
+        filetime::set_file_mtime(&temporaneo, mtime).unwrap();
+        fs::rename(&temporaneo, &foto).unwrap();
+        assert!(cache.cerca(&foto).is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn cerca_compagno_nuovo() {
+        let dir = cartella("compagno");
+        let foto = dir.join("IMG_0002.jpg");
+        fs::write(&foto, "foto").unwrap();
+        let cache = cache_con(&dir, &foto);
+        assert!(cache.cerca(&foto).is_some());
+
+        fs::write(dir.join("IMG_0002.jpg.xmp"), "<x:xmpmeta/>").unwrap();
+        assert!(cache.cerca(&foto).is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn cerca_con_rileggi_tutto_e_rimuovi() {
+        let dir = cartella("rileggi");
+        let foto = dir.join("IMG_0003.jpg");
+        fs::write(&foto, "foto").unwrap();
+        let mut cache = cache_con(&dir, &foto);
+        assert!(cache.cerca(&foto).is_some());
+        cache.forza_rilettura();
+        assert!(cache.cerca(&foto).is_none());
+
+        let mut cache = cache_con(&dir, &foto);
+        cache.rimuovi(&foto);
+        assert!(cache.cerca(&foto).is_none());
+        assert!(cache.is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rimuovi_toglie_le_voci_che_hanno_il_file_tra_i_compagni() {
+        let dir = cartella("coppia");
+        let raw = dir.join("P1010001.ORF");
+        let jpg = dir.join("P1010001.JPG");
+        fs::write(&raw, "raw").unwrap();
+        fs::write(&jpg, "jpg").unwrap();
+        let mut cache = cache_con(&dir, &raw);
+        assert!(cache.cerca(&raw).is_some());
+        cache.rimuovi(&jpg);
+        assert!(cache.cerca(&raw).is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,666 @@
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+use crate::FotoData;
+use crate::esportazione::FormatoReport;
+use crate::filtri::{FiltroFoto, FiltroIncongruenza, Strategia};
+use crate::scrittura::{FusoDataFile, OpzioniScrittura, RichiestaScrittura, ScritturaSospesa};
+use crate::sidecar::{ModalitaOutput, NomeSidecar};
+
+// Riga di comando: le stesse operazioni della GUI, per script e cron
+
+// Codici di uscita
+pub const USCITA_OK: i32 = 0;
+pub const USCITA_ERRORI_FILE: i32 = 1; // Operazione completata, ma alcuni file sono falliti
+pub const USCITA_USO: i32 = 2;         // Argomenti non validi (come clap)
+pub const USCITA_FATALE: i32 = 3;      // Niente è stato fatto: directory, exiftool o journal mancanti
+
+#[derive(Debug, Parser)]
+#[command(name = "corrigi-exif", version, about = "Fix EXIF dates of Google Takeout photos",
+          long_about = "Fix EXIF dates of Google Takeout photos. Without arguments the GUI starts.")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub comando: Comando,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Comando {
+    #[command(about = "Read the photos and print a summary")]
+    Scan(ArgomentiReport),
+    #[command(about = "List the photos matching the filters with current and proposed dates")]
+    Report(ArgomentiReport),
+    #[command(about = "Write the proposed dates to the photos matching the filters")]
+    Apply(ArgomentiApply),
+    #[command(about = "Resume an apply that was stopped, closed or interrupted before the end")]
+    Resume(ArgomentiResume),
+    #[command(about = "Revert the last operation recorded in the journal")]
+    Undo(ArgomentiUndo),
+    #[command(about = "List the backups made before writing, or delete the old ones")]
+    Backups(ArgomentiBackups),
+    #[command(about = "Rename the photos matching the filters with a date template")]
+    Rename(ArgomentiRename),
+    #[command(about = "Copy, move or hardlink the photos matching the filters into dated folders")]
+    Organize(ArgomentiOrganize),
+    #[command(about = "Detect camera clock offsets (EXIF vs JSON) per camera and folder")]
+    Clock(ArgomentiScan),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Categoria {
+    #[value(name = "1900")]
+    Foto1900,
+    #[value(name = "whatsapp")]
+    Whatsapp,
+    #[value(name = "raw")]
+    Raw,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgomentiScan {
+    #[arg(required = true, help = "Directories to scan (recursively)")]
+    pub directory: Vec<PathBuf>,
+
+    #[arg(long = "strategy-datetime-original", value_enum, default_value = "json_preferito",
+          help = "Strategy for DateTimeOriginal")]
+    pub strategia_datetime_original: Strategia,
+
+    #[arg(long = "strategy-create-date", value_enum, default_value = "json_preferito",
+          help = "Strategy for CreateDate")]
+    pub strategia_create_date: Strategia,
+
+    #[arg(short = 'j', long = "threads", help = "Worker threads (default: automatic)")]
+    pub thread: Option<usize>,
+
+    #[arg(long = "cache-dir", help = "Folder of the scan cache (default: $XDG_CACHE_HOME/corrigi-exif)")]
+    pub dir_cache: Option<PathBuf>,
+
+    #[arg(long = "no-cache", conflicts_with_all = ["dir_cache", "rileggi"], help = "Neither read nor update the scan cache")]
+    pub senza_cache: bool,
+
+    #[arg(long = "rescan", help = "Re-read every photo, ignoring and refreshing the scan cache")]
+    pub rileggi: bool,
+
+    #[arg(long = "min-severity-days", default_value_t = 0.0,
+          help = "Only photos whose incongruity is at least this many days")]
+    pub soglia_gravita_giorni: f32,
+
+    #[arg(long = "all", help = "Include photos without incongruities")]
+    pub tutte: bool,
+
+    #[arg(long = "only-missing-exif", help = "Only photos without DateTimeOriginal and CreateDate")]
+    pub solo_exif_mancante: bool,
+
+    #[arg(long = "incongruity", value_enum, default_value = "all", help = "Type of incongruity")]
+    pub incongruenza: FiltroIncongruenza,
+
+    #[arg(long = "category", value_enum, help = "Only photos of these categories (repeatable)")]
+    pub categorie: Vec<Categoria>,
+
+    #[arg(long = "overrides", help = "CSV or JSON file of manual dates, applied before filtering (repeatable)")]
+    pub override_date: Vec<PathBuf>,
+
+    #[arg(long = "shift", allow_hyphen_values = true,
+          help = "Shift the camera time of every photo (or of --shift-camera), e.g. +1h, -1y, \"+2d 3h\", -01:30")]
+    pub scostamento: Option<String>,
+
+    #[arg(long = "shift-camera", requires = "scostamento", help = "Only shift photos from this camera (EXIF Make + Model)")]
+    pub fotocamera_scostamento: Option<String>,
+
+    #[arg(long = "fix-clock-offsets", help = "Correct the camera clock offsets detected by the `clock` command")]
+    pub correggi_orologi: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgomentiReport {
+    #[command(flatten)]
+    pub scan: ArgomentiScan,
+
+    #[arg(long = "format", value_enum, default_value = "text",
+          help = "Output format: text, JSON Lines or CSV (one record per photo)")]
+    pub formato: FormatoReport,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgomentiApply {
+    #[command(flatten)]
+    pub scan: ArgomentiScan,
+
+    #[arg(long = "metadata", help = "Also write description, people, GPS from the JSON")]
+    pub metadati: bool,
+
+    #[arg(long = "output", value_enum, default_value = "embedded", help = "Where to write the dates")]
+    pub modalita_output: ModalitaOutput,
+
+    #[arg(long = "sidecar-name", value_enum, default_value = "with-extension", help = "XMP sidecar naming")]
+    pub nome_sidecar: NomeSidecar,
+
+    #[arg(long = "set-mtime", help = "Set file modification time to the final DateTimeOriginal")]
+    pub imposta_data_file: bool,
+
+    #[arg(long = "mtime-timezone", value_enum, default_value = "local",
+          help = "Time zone of the EXIF time when setting the file modification time")]
+    pub fuso_data_file: FusoDataFile,
+
+    #[arg(long = "repair", help = "Rebuild unreadable EXIF from the readable tags instead of failing")]
+    pub riparazione: bool,
+
+    #[arg(long = "no-verify", help = "Don't read the written tags back to check them")]
+    pub senza_verifica: bool,
+
+    #[arg(long = "verify-pixels", help = "Also check that the image data of JPEGs is unchanged after writing")]
+    pub verifica_pixel: bool,
+
+    #[arg(long = "backup", help = "Copy each file to the backup folder before writing it")]
+    pub backup: bool,
+
+    #[arg(long = "backup-dir", help = "Backup folder (default: $XDG_DATA_HOME/corrigi-exif/backup)")]
+    pub dir_backup: Option<PathBuf>,
+
+    #[arg(long = "delete-verified-backups", requires = "backup", conflicts_with = "senza_verifica",
+          help = "Delete each backup once the write is verified")]
+    pub elimina_backup_verificati: bool,
+
+    #[arg(long = "errors", help = "Write the failed files to this file (CSV, or JSON Lines if it ends in .jsonl)")]
+    pub file_errori: Option<PathBuf>,
+
+    #[arg(long = "dry-run", help = "Show what would be written without changing anything")]
+    pub prova: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgomentiResume {
+    #[arg(short = 'j', long = "threads", help = "Worker threads (default: automatic)")]
+    pub thread: Option<usize>,
+
+    #[arg(long = "errors", help = "Write the failed files to this file (CSV, or JSON Lines if it ends in .jsonl)")]
+    pub file_errori: Option<PathBuf>,
+
+    #[arg(long = "cache-dir", help = "Folder of the scan cache to update (default: $XDG_CACHE_HOME/corrigi-exif)")]
+    pub dir_cache: Option<PathBuf>,
+
+    #[arg(long = "discard", help = "Forget the interrupted apply instead of resuming it")]
+    pub scarta: bool,
+
+    #[arg(long = "dry-run", help = "Show the files left to write without writing them")]
+    pub prova: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgomentiUndo {
+    #[arg(long = "list", help = "List the journals instead of undoing")]
+    pub elenca: bool,
+
+    #[arg(long = "journal", help = "Journal to undo (default: the most recent)")]
+    pub journal: Option<PathBuf>,
+
+    #[arg(long = "cache-dir", help = "Folder of the scan cache to update (default: $XDG_CACHE_HOME/corrigi-exif)")]
+    pub dir_cache: Option<PathBuf>,
+
+    #[arg(long = "dry-run", help = "Show the recorded operations without reverting them")]
+    pub prova: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgomentiBackups {
+    #[arg(long = "dir", help = "Backup folder (default: $XDG_DATA_HOME/corrigi-exif/backup)")]
+    pub dir: Option<PathBuf>,
+
+    #[arg(long = "clean", value_name = "DAYS", help = "Delete the backups older than this many days")]
+    pub pulisci_giorni: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgomentiRename {
+    #[command(flatten)]
+    pub scan: ArgomentiScan,
+
+    #[arg(long = "template", default_value = crate::rinomina::TEMPLATE_PREDEFINITO, help = "Naming template")]
+    pub template: String,
+
+    #[arg(long = "dry-run", help = "Show the planned renames without renaming")]
+    pub prova: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgomentiOrganize {
+    #[command(flatten)]
+    pub scan: ArgomentiScan,
+
+    #[arg(long = "target", required = true, help = "Folder to organize the photos into")]
+    pub destinazione: PathBuf,
+
+    #[arg(long = "template", default_value = crate::organizza::TEMPLATE_CARTELLE_PREDEFINITO,
+          help = "Folder template, one placeholder set per path component")]
+    pub template: String,
+
+    #[arg(long = "mode", value_enum, default_value = "copy", help = "Copy, move or hardlink the files")]
+    pub modalita: crate::organizza::ModalitaOrganizza,
+
+    #[arg(long = "skip-duplicates", help = "Organize only one copy of each group of identical files")]
+    pub salta_duplicati: bool,
+
+    #[arg(long = "dry-run", help = "Show the planned destinations without changing anything")]
+    pub prova: bool,
+}
+
+impl ArgomentiScan {
+    fn filtro(&self) -> FiltroFoto {
+        FiltroFoto {
+            soglia_gravita_giorni: self.soglia_gravita_giorni,
+            mostra_tutte_foto: self.tutte,
+            solo_exif_mancante: self.solo_exif_mancante,
+            filtro_incongruenza: self.incongruenza,
+            mostra_foto_1900: self.categorie.contains(&Categoria::Foto1900),
+            mostra_foto_whatsapp: self.categorie.contains(&Categoria::Whatsapp),
+            mostra_foto_raw: self.categorie.contains(&Categoria::Raw),
+            solo_duplicati: false,
+            periodo: None,
+        }
+    }
+
+    // Legge tutte le directory e calcola le proposte con le strategie richieste
+    fn leggi(&self) -> Result<Vec<FotoData>, String> {
+        if let Some(mancante) = self.directory.iter().find(|d| !d.is_dir()) {
+            return Err(format!("Directory non trovata: {}", mancante.display()));
+        }
+        let mut cache = match self.senza_cache {
+            true => None,
+            false => crate::cache_scansione::CacheScansione::carica_da(self.dir_cache.as_deref()),
+        };
+        if self.rileggi {
+            if let Some(ref mut cache) = cache {
+                cache.forza_rilettura();
+            }
+        }
+        let mut foto_list = Vec::new();
+        for dir in &self.directory {
+            foto_list.extend(crate::leggi_foto_da_directory_con_thread(dir, None, self.thread, cache.as_mut(), None));
+        }
+        salva_cache(cache);
+        crate::interpolazione::interpola_date(&mut foto_list);
+        for foto in &mut foto_list {
+            foto.applica_strategie(self.strategia_datetime_original.as_str(), self.strategia_create_date.as_str());
+        }
+        if let Some(ref testo) = self.scostamento {
+            let scostamento = crate::scostamento::interpreta_scostamento(testo)?;
+            let indici: Vec<usize> = foto_list.iter().enumerate()
+                .filter(|(_, f)| self.fotocamera_scostamento.is_none() || f.fotocamera == self.fotocamera_scostamento)
+                .map(|(i, _)| i)
+                .collect();
+            let (spostate, senza_data) = crate::scostamento::applica_scostamento(&mut foto_list, &indici, scostamento, "shift");
+            eprintln!("Scostamento {}: {} foto spostate, {} senza data EXIF", scostamento.descrizione(), spostate, senza_data);
+        }
+        if self.correggi_orologi {
+            for gruppo in crate::orologio::analizza_orologi(&foto_list).iter().filter(|g| g.da_correggere()) {
+                let (corrette, _) = crate::orologio::applica_correzione(&mut foto_list, gruppo);
+                eprintln!("Orologio {} in {}: {} foto corrette di {}", gruppo.fotocamera, gruppo.cartella.display(),
+                          corrette, gruppo.scostamento.descrizione());
+            }
+        }
+        // Le date manuali vincono anche sullo scostamento
+        for file in &self.override_date {
+            let righe = crate::date_manuali::leggi_file_override(file)?;
+            let esito = crate::date_manuali::applica_override(&mut foto_list, &righe)?;
+            for riga in &esito.non_trovate {
+                eprintln!("{}: nessuna foto corrisponde a {}", riga.origine, riga.modello);
+            }
+            eprintln!("{}: {} foto con data manuale, {} righe senza corrispondenza",
+                      file.display(), esito.applicate, esito.non_trovate.len());
+        }
+        Ok(foto_list)
+    }
+
+    fn leggi_filtrate(&self) -> Result<Vec<FotoData>, String> {
+        let filtro = self.filtro();
+        Ok(self.leggi()?.into_iter().filter(|f| filtro.accetta(f)).collect())
+    }
+}
+
+fn formatta_data(data: Option<chrono::DateTime<chrono::Utc>>) -> String {
+    data.map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn exiftool_disponibile() -> bool {
+    std::process::Command::new("exiftool").arg("-ver").output().is_ok()
+}
+
+// Esegue il comando e restituisce il codice di uscita
+pub fn esegui(cli: Cli) -> i32 {
+    let risultato = match cli.comando {
+        Comando::Scan(args) => scan(&args),
+        Comando::Report(args) => report(&args),
+        Comando::Apply(args) => apply(&args),
+        Comando::Resume(args) => resume(&args),
+        Comando::Undo(args) => undo(&args),
+        Comando::Backups(args) => backups(&args),
+        Comando::Rename(args) => rename(&args),
+        Comando::Organize(args) => organize(&args),
+        Comando::Clock(args) => clock(&args),
+    };
+    // I processi exiftool rimasti aperti per le scritture
+    crate::exiftool::pool().chiudi();
+    match risultato {
+        Ok(codice) => codice,
+        Err(e) => {
+            eprintln!("Errore: {}", e);
+            USCITA_FATALE
+        }
+    }
+}
+
+// Record per foto in JSON Lines o CSV su stdout; false per il formato testo
+fn stampa_record(foto_list: &[FotoData], formato: FormatoReport) -> Result<bool, String> {
+    let stdout = std::io::stdout().lock();
+    match formato {
+        FormatoReport::Testo => return Ok(false),
+        FormatoReport::JsonLines => crate::esportazione::scrivi_jsonl(foto_list, stdout)?,
+        FormatoReport::Csv => crate::esportazione::scrivi_csv(foto_list, stdout)?,
+    }
+    Ok(true)
+}
+
+fn scan(args: &ArgomentiReport) -> Result<i32, String> {
+    let start = std::time::Instant::now();
+    let foto_list = args.scan.leggi()?;
+    // scan esporta tutte le foto, report solo quelle che passano i filtri
+    if stampa_record(&foto_list, args.formato)? {
+        return Ok(USCITA_OK);
+    }
+    let filtro = args.scan.filtro();
+
+    let con_exif = foto_list.iter().filter(|f| f.exif_datetime_original.is_some()).count();
+    let con_incongruenze = foto_list.iter().filter(|f| !f.incongruenze.is_empty()).count();
+    let filtrate = foto_list.iter().filter(|f| filtro.accetta(f)).count();
+    let da_scrivere = foto_list.iter()
+        .filter(|f| filtro.accetta(f))
+        .filter(|f| crate::scrittura::richiede_scrittura(f, &OpzioniScrittura::default()))
+        .count();
+
+    println!("Photos:             {} (read in {:?})", foto_list.len(), start.elapsed());
+    println!("With DateTimeOriginal: {}", con_exif);
+    println!("With incongruities: {}", con_incongruenze);
+    println!("Matching filters:   {}", filtrate);
+    println!("To be written:      {}", da_scrivere);
+    Ok(USCITA_OK)
+}
+
+fn report(args: &ArgomentiReport) -> Result<i32, String> {
+    let foto_list = args.scan.leggi_filtrate()?;
+    if stampa_record(&foto_list, args.formato)? {
+        return Ok(USCITA_OK);
+    }
+    println!("path\texif_datetime_original\tproposed_datetime_original\texif_create_date\tproposed_create_date\tseverity_days\tincongruities");
+    for foto in &foto_list {
+        println!("{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                 foto.path.display(),
+                 formatta_data(foto.exif_datetime_original),
+                 formatta_data(foto.proposta_datetime_original),
+                 formatta_data(foto.exif_create_date),
+                 formatta_data(foto.proposta_create_date),
+                 foto.gravita_incongruenza,
+                 foto.incongruenze.join("; "));
+    }
+    Ok(USCITA_OK)
+}
+
+fn apply(args: &ArgomentiApply) -> Result<i32, String> {
+    let opzioni = OpzioniScrittura {
+        modalita_output: args.modalita_output,
+        nome_sidecar: args.nome_sidecar,
+        scrivi_metadati_json: args.metadati,
+        imposta_data_file: args.imposta_data_file,
+        fuso_data_file: args.fuso_data_file,
+        riparazione: args.riparazione,
+        verifica: !args.senza_verifica,
+        verifica_pixel: args.verifica_pixel,
+        backup: args.backup,
+        dir_backup: args.dir_backup.clone(),
+        elimina_backup_verificati: args.elimina_backup_verificati,
+    };
+    let foto_list = args.scan.leggi_filtrate()?;
+    let richieste: Vec<RichiestaScrittura> = foto_list.iter()
+        // Le foto ancora negli archivi Takeout non si possono scrivere
+        .filter(|f| f.origine_archivio.is_none())
+        .filter(|f| crate::scrittura::richiede_scrittura(f, &opzioni))
+        .map(|f| RichiestaScrittura::da_foto(f, &opzioni))
+        .collect();
+
+    if args.prova {
+        stampa_richieste(&richieste);
+        println!("{} photos would be written", richieste.len());
+        return Ok(USCITA_OK);
+    }
+    if richieste.is_empty() {
+        println!("Nothing to write");
+        return Ok(USCITA_OK);
+    }
+    if !exiftool_disponibile() {
+        return Err("exiftool non trovato. Assicurati che sia installato e nel PATH.".to_string());
+    }
+
+    let journal = crate::journal::Journal::nuovo("scrittura")?;
+    esegui_richieste(&richieste, opzioni, journal, args.scan.dir_cache.as_deref(), args.scan.thread, args.file_errori.as_deref())
+}
+
+// Una cache non salvata costa solo la prossima rilettura
+fn salva_cache(cache: Option<crate::cache_scansione::CacheScansione>) {
+    if let Some(Err(e)) = cache.map(|mut c| c.salva()) {
+        eprintln!("Avviso: {}", e);
+    }
+}
+
+fn stampa_richieste(richieste: &[RichiestaScrittura]) {
+    for richiesta in richieste {
+        let campi: Vec<String> = richiesta.campi.iter()
+            .map(|(campo, data)| format!("{}={}", campo, data.format("%Y-%m-%d %H:%M:%S")))
+            .collect();
+        println!("{}\t{}{}", richiesta.path.display(), campi.join(" "),
+                 if richiesta.usa_sidecar { " (xmp)" } else { "" });
+    }
+}
+
+// Scrive le richieste registrandole nel journal. Finché non finisce resta salvata una
+// scrittura sospesa, da riprendere con `resume` se il comando viene interrotto
+fn esegui_richieste(richieste: &[RichiestaScrittura], opzioni: OpzioniScrittura, journal: crate::journal::Journal,
+                    dir_cache: Option<&std::path::Path>, thread: Option<usize>, file_errori: Option<&std::path::Path>) -> Result<i32, String> {
+    let sospesa = ScritturaSospesa { journal: Some(journal.path().to_path_buf()), opzioni: opzioni.clone(), richieste: richieste.to_vec() };
+    if let Err(e) = sospesa.salva() {
+        eprintln!("Avviso: {}", e);
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(thread.unwrap_or_else(num_cpus::get))
+        .build()
+        .map_err(|e| e.to_string())?;
+    // Anche con --no-cache: le voci dei file scritti non devono restare in una cache usata dopo
+    let mut cache = crate::cache_scansione::CacheScansione::carica_da(dir_cache);
+    let esiti = pool.install(|| crate::scrittura::esegui_scritture(richieste, &opzioni, Some(&journal), cache.as_mut(), None, None));
+    salva_cache(cache);
+    ScritturaSospesa::aggiorna_dopo(Some(journal.path()), opzioni, richieste, &esiti);
+
+    let errori: Vec<&crate::errori_scrittura::ErroreScrittura> = esiti.iter()
+        .filter_map(|e| e.risultato.as_ref().err())
+        .collect();
+    if let Some(file) = file_errori {
+        let output = std::fs::File::create(file)
+            .map_err(|e| format!("Errore creazione {}: {}", file.display(), e))?;
+        if file.extension().is_some_and(|e| e == "jsonl") {
+            crate::errori_scrittura::scrivi_jsonl(errori.iter().copied(), output)?;
+        } else {
+            crate::errori_scrittura::scrivi_csv(errori.iter().copied(), output)?;
+        }
+    }
+
+    println!("Written: {}, errors: {}", esiti.len() - errori.len(), errori.len());
+    println!("Journal: {}", journal.path().display());
+    Ok(if errori.is_empty() { USCITA_OK } else { USCITA_ERRORI_FILE })
+}
+
+fn resume(args: &ArgomentiResume) -> Result<i32, String> {
+    let Some(sospesa) = ScritturaSospesa::carica() else {
+        println!("Nothing to resume");
+        return Ok(USCITA_OK);
+    };
+    if args.scarta {
+        ScritturaSospesa::elimina();
+        println!("Interrupted apply discarded ({} files not written)", sospesa.richieste.len());
+        return Ok(USCITA_OK);
+    }
+    let richieste = sospesa.da_eseguire();
+    println!("{} of {} files left to write", richieste.len(), sospesa.richieste.len());
+    if args.prova {
+        stampa_richieste(&richieste);
+        return Ok(USCITA_OK);
+    }
+    if richieste.is_empty() {
+        ScritturaSospesa::elimina();
+        return Ok(USCITA_OK);
+    }
+    if !exiftool_disponibile() {
+        return Err("exiftool non trovato. Assicurati che sia installato e nel PATH.".to_string());
+    }
+    // Stesso journal dell'esecuzione interrotta, se c'è ancora
+    let journal = match sospesa.journal.as_deref().filter(|j| j.exists()) {
+        Some(j) => crate::journal::Journal::riapri(j)?,
+        None => crate::journal::Journal::nuovo("scrittura")?,
+    };
+    esegui_richieste(&richieste, sospesa.opzioni, journal, args.dir_cache.as_deref(), args.thread, args.file_errori.as_deref())
+}
+
+fn undo(args: &ArgomentiUndo) -> Result<i32, String> {
+    if args.elenca {
+        for journal in crate::journal::elenca_journal() {
+            let voci = crate::journal::leggi_journal(&journal).map(|v| v.len()).unwrap_or(0);
+            println!("{}\t{} operations", journal.display(), voci);
+        }
+        return Ok(USCITA_OK);
+    }
+
+    let journal = match &args.journal {
+        Some(j) => j.clone(),
+        None => match crate::journal::elenca_journal().into_iter().next() {
+            Some(j) => j,
+            None => {
+                println!("Nothing to undo");
+                return Ok(USCITA_OK);
+            }
+        },
+    };
+
+    if args.prova {
+        for voce in crate::journal::leggi_journal(&journal)?.iter().rev() {
+            println!("{:?}", voce);
+        }
+        return Ok(USCITA_OK);
+    }
+
+    let mut cache = crate::cache_scansione::CacheScansione::carica_da(args.dir_cache.as_deref());
+    let (annullate, errori) = crate::journal::annulla_journal(&journal, cache.as_mut())?;
+    salva_cache(cache);
+    for errore in &errori {
+        eprintln!("{}", errore);
+    }
+    println!("Restored {} operations from {} ({} errors)", annullate, journal.display(), errori.len());
+    Ok(if errori.is_empty() { USCITA_OK } else { USCITA_ERRORI_FILE })
+}
+
+fn backups(args: &ArgomentiBackups) -> Result<i32, String> {
+    if let Some(giorni) = args.pulisci_giorni {
+        let (cancellate, byte) = crate::backup::pulisci(args.dir.as_deref(), giorni)?;
+        println!("Deleted {} backups older than {} days ({:.1} MB)", cancellate, giorni, byte as f64 / (1024.0 * 1024.0));
+        return Ok(USCITA_OK);
+    }
+    for esecuzione in crate::backup::elenca(args.dir.as_deref()) {
+        println!("{}\t{} files\t{:.1} MB\t{} days old", esecuzione.dir.display(), esecuzione.file,
+                 esecuzione.byte as f64 / (1024.0 * 1024.0), esecuzione.eta_giorni());
+    }
+    Ok(USCITA_OK)
+}
+
+fn rename(args: &ArgomentiRename) -> Result<i32, String> {
+    let foto_list = args.scan.leggi_filtrate()?;
+    let riferimenti: Vec<&FotoData> = foto_list.iter().collect();
+    let piano = crate::rinomina::pianifica_rinomina(&riferimenti, &args.template);
+
+    for (foto, motivo) in &piano.saltate {
+        eprintln!("Skipped {}: {}", foto.display(), motivo);
+    }
+    if args.prova {
+        for gruppo in &piano.gruppi {
+            for op in &gruppo.operazioni {
+                println!("{} -> {}", op.da.display(), op.a.display());
+            }
+        }
+        println!("{} photos would be renamed", piano.gruppi.len());
+        return Ok(USCITA_OK);
+    }
+
+    let esito = crate::rinomina::esegui_rinomina(&piano)?;
+    for errore in &esito.errori {
+        eprintln!("{}", errore);
+    }
+    println!("Renamed: {} files, errors: {}", esito.eseguite.len(), esito.errori.len());
+    if let Some(journal) = &esito.journal {
+        println!("Journal: {}", journal.display());
+    }
+    Ok(if esito.errori.is_empty() { USCITA_OK } else { USCITA_ERRORI_FILE })
+}
+
+fn organize(args: &ArgomentiOrganize) -> Result<i32, String> {
+    let mut foto_list = args.scan.leggi_filtrate()?;
+    if args.salta_duplicati {
+        let gruppi = crate::duplicati::trova_gruppi(&foto_list, false, None);
+        crate::duplicati::assegna_gruppi(&mut foto_list, &gruppi);
+    }
+    let riferimenti: Vec<&FotoData> = foto_list.iter().collect();
+    let piano = crate::organizza::pianifica_organizzazione(&riferimenti, &args.destinazione, &args.template, args.salta_duplicati);
+
+    for (foto, motivo) in &piano.saltate {
+        eprintln!("Skipped {}: {}", foto.display(), motivo);
+    }
+    if args.prova {
+        for gruppo in &piano.gruppi {
+            for op in &gruppo.operazioni {
+                println!("{} -> {}", op.da.display(), op.a.display());
+            }
+        }
+        println!("{} photos would be organized", piano.gruppi.len());
+        return Ok(USCITA_OK);
+    }
+
+    let esito = crate::organizza::esegui_organizzazione(&piano, &args.destinazione, args.modalita, None)?;
+    for errore in &esito.errori {
+        eprintln!("{}", errore);
+    }
+    println!("{}: {} files, errors: {}", args.modalita.display_name(), esito.eseguite.len(), esito.errori.len());
+    if let Some(manifest) = &esito.manifest {
+        println!("Manifest: {}", manifest.display());
+    }
+    if let Some(journal) = &esito.journal {
+        println!("Journal: {}", journal.display());
+    }
+    Ok(if esito.errori.is_empty() { USCITA_OK } else { USCITA_ERRORI_FILE })
+}
+
+fn clock(args: &ArgomentiScan) -> Result<i32, String> {
+    let foto_list = args.leggi()?;
+    let gruppi = crate::orologio::analizza_orologi(&foto_list);
+    println!("offset\tconsistent\tphotos\twithout_json\tmean_s\tstddev_s\tmin_s\tmax_s\tcamera\tfolder");
+    for gruppo in &gruppi {
+        println!("{}{}\t{}/{}\t{}\t{}\t{:.0}\t{:.0}\t{}\t{}\t{}\t{}",
+                 gruppo.scostamento.descrizione(),
+                 if gruppo.da_correggere() { " *" } else if gruppo.probabile_fuso() { " (time zone?)" } else { "" },
+                 gruppo.coerenti, gruppo.campioni,
+                 gruppo.indici.len(),
+                 gruppo.senza_json(&foto_list),
+                 gruppo.media_secondi, gruppo.deviazione_secondi,
+                 gruppo.min_secondi, gruppo.max_secondi,
+                 gruppo.fotocamera, gruppo.cartella.display());
+    }
+    let da_correggere = gruppi.iter().filter(|g| g.da_correggere()).count();
+    eprintln!("{} groups analyzed, {} with a consistent offset (*): use --fix-clock-offsets to correct them", gruppi.len(), da_correggere);
+    let fusi = gruppi.iter().filter(|g| g.probabile_fuso()).count();
+    if fusi > 0 {
+        eprintln!("{} groups differ by whole hours, likely a time zone rather than a wrong clock: not corrected, use --shift if needed", fusi);
+    }
+    Ok(USCITA_OK)
+}
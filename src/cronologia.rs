@@ -0,0 +1,233 @@
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use crate::FotoData;
+
+// Istogramma delle foto nel tempo, per vedere a colpo d'occhio gli accumuli di date
+// sbagliate (un picco sul 2000-01-01) e i buchi. Le date mancanti e il 1900-01-01
+// hanno un secchio a parte, altrimenti schiaccerebbero la scala
+
+// Oltre questo numero di barre le colonne sono più strette di un pixel: si passa alla
+// granularità più ampia (giorni -> mesi -> anni)
+const MASSIMO_PERIODI: i64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularita {
+    Giorno,
+    Mese,
+    Anno,
+}
+
+impl Granularita {
+    pub fn display_name(&self) -> &str {
+        match self {
+            Granularita::Giorno => "Day",
+            Granularita::Mese => "Month",
+            Granularita::Anno => "Year",
+        }
+    }
+
+    // Primo giorno del periodo che contiene la data
+    pub fn inizio(&self, data: NaiveDate) -> NaiveDate {
+        match self {
+            Granularita::Giorno => data,
+            Granularita::Mese => data.with_day(1).unwrap_or(data),
+            Granularita::Anno => NaiveDate::from_ymd_opt(data.year(), 1, 1).unwrap_or(data),
+        }
+    }
+
+    pub fn successivo(&self, inizio: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Granularita::Giorno => inizio.succ_opt(),
+            Granularita::Mese => inizio.checked_add_months(Months::new(1)),
+            Granularita::Anno => inizio.checked_add_months(Months::new(12)),
+        }
+    }
+
+    pub fn piu_ampia(&self) -> Option<Granularita> {
+        match self {
+            Granularita::Giorno => Some(Granularita::Mese),
+            Granularita::Mese => Some(Granularita::Anno),
+            Granularita::Anno => None,
+        }
+    }
+
+    // Periodi dal primo all'ultimo compresi, vuoti inclusi
+    fn periodi_tra(&self, primo: NaiveDate, ultimo: NaiveDate) -> i64 {
+        let mesi = |d: NaiveDate| d.year() as i64 * 12 + d.month0() as i64;
+        match self {
+            Granularita::Giorno => (ultimo - primo).num_days() + 1,
+            Granularita::Mese => mesi(ultimo) - mesi(primo) + 1,
+            Granularita::Anno => (ultimo.year() - primo.year()) as i64 + 1,
+        }
+    }
+
+    pub fn etichetta(&self, inizio: NaiveDate) -> String {
+        match self {
+            Granularita::Giorno => inizio.format("%Y-%m-%d").to_string(),
+            Granularita::Mese => inizio.format("%Y-%m").to_string(),
+            Granularita::Anno => inizio.format("%Y").to_string(),
+        }
+    }
+}
+
+// Data attuale (EXIF DateTimeOriginal) o proposta
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Serie {
+    Attuale,
+    Proposta,
+}
+
+impl Serie {
+    pub fn display_name(&self) -> &str {
+        match self {
+            Serie::Attuale => "current EXIF",
+            Serie::Proposta => "proposed",
+        }
+    }
+
+    pub fn data(&self, foto: &FotoData) -> Option<DateTime<Utc>> {
+        match self {
+            Serie::Attuale => foto.exif_datetime_original,
+            Serie::Proposta => foto.proposta_datetime_original,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Secchio {
+    Mancante,
+    Sentinella,           // 1900-01-01, foto senza nessuna data
+    Periodo(NaiveDate),   // Inizio del giorno/mese/anno
+}
+
+pub fn secchio(data: Option<DateTime<Utc>>, granularita: Granularita) -> Secchio {
+    match data {
+        None => Secchio::Mancante,
+        Some(d) if d.year() == 1900 && d.month() == 1 && d.day() == 1 => Secchio::Sentinella,
+        Some(d) => Secchio::Periodo(granularita.inizio(d.date_naive())),
+    }
+}
+
+// Filtro della tabella per una barra dell'istogramma
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FiltroPeriodo {
+    pub serie: Serie,
+    pub secchio: Secchio,
+    pub granularita: Granularita,
+}
+
+impl FiltroPeriodo {
+    pub fn accetta(&self, foto: &FotoData) -> bool {
+        secchio(self.serie.data(foto), self.granularita) == self.secchio
+    }
+
+    pub fn descrizione(&self) -> String {
+        let secchio = match self.secchio {
+            Secchio::Mancante => "missing".to_string(),
+            Secchio::Sentinella => "1900-01-01".to_string(),
+            Secchio::Periodo(inizio) => self.granularita.etichetta(inizio),
+        };
+        format!("{} date {}", self.serie.display_name(), secchio)
+    }
+}
+
+// Conteggi (attuali, proposte) per secchio
+#[derive(Debug, Clone)]
+pub struct Istogramma {
+    pub granularita: Granularita, // Quella chiesta, o una più ampia se i periodi erano troppi
+    pub mancanti: (usize, usize),
+    pub sentinella: (usize, usize),
+    // Tutti i periodi dal primo all'ultimo, anche vuoti: i buchi devono vedersi
+    pub periodi: Vec<(NaiveDate, usize, usize)>,
+}
+
+impl Istogramma {
+    pub fn massimo(&self) -> usize {
+        self.periodi.iter()
+            .map(|(_, a, p)| *a.max(p))
+            .chain([self.mancanti.0, self.mancanti.1, self.sentinella.0, self.sentinella.1])
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+pub fn calcola_istogramma(foto_list: &[FotoData], granularita: Granularita) -> Istogramma {
+    let mut istogramma = Istogramma { granularita, mancanti: (0, 0), sentinella: (0, 0), periodi: Vec::new() };
+    let mut conteggi: std::collections::BTreeMap<NaiveDate, (usize, usize)> = std::collections::BTreeMap::new();
+    for foto in foto_list {
+        for serie in [Serie::Attuale, Serie::Proposta] {
+            let conteggio = match secchio(serie.data(foto), granularita) {
+                Secchio::Mancante => &mut istogramma.mancanti,
+                Secchio::Sentinella => &mut istogramma.sentinella,
+                Secchio::Periodo(inizio) => conteggi.entry(inizio).or_default(),
+            };
+            match serie {
+                Serie::Attuale => conteggio.0 += 1,
+                Serie::Proposta => conteggio.1 += 1,
+            }
+        }
+    }
+
+    let (Some((&primo, _)), Some((&ultimo, _))) = (conteggi.first_key_value(), conteggi.last_key_value()) else {
+        return istogramma;
+    };
+    // Anni di date sbagliate a granularità giornaliera sarebbero decine di migliaia di barre
+    let mut granularita = granularita;
+    while granularita.periodi_tra(primo, ultimo) > MASSIMO_PERIODI {
+        let Some(ampia) = granularita.piu_ampia() else {
+            break;
+        };
+        let mut raggruppati: std::collections::BTreeMap<NaiveDate, (usize, usize)> = std::collections::BTreeMap::new();
+        for (inizio, (attuali, proposte)) in conteggi {
+            let conteggio = raggruppati.entry(ampia.inizio(inizio)).or_default();
+            conteggio.0 += attuali;
+            conteggio.1 += proposte;
+        }
+        conteggi = raggruppati;
+        granularita = ampia;
+    }
+    istogramma.granularita = granularita;
+    let (primo, ultimo) = (granularita.inizio(primo), granularita.inizio(ultimo));
+
+    let mut inizio = Some(primo);
+    while let Some(periodo) = inizio.filter(|p| *p <= ultimo) {
+        let (attuali, proposte) = conteggi.get(&periodo).copied().unwrap_or_default();
+        istogramma.periodi.push((periodo, attuali, proposte));
+        inizio = granularita.successivo(periodo);
+    }
+    istogramma
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn foto(anno: i32, mese: u32, giorno: u32) -> FotoData {
+        let data = NaiveDate::from_ymd_opt(anno, mese, giorno).unwrap().and_hms_opt(12, 0, 0).unwrap().and_utc();
+        crate::nuova_foto(PathBuf::from("foto.jpg"), (Some(data), None, None), None)
+    }
+
+    #[test]
+    fn istogramma_giorni_vicini() {
+        let istogramma = calcola_istogramma(&[foto(2020, 5, 1), foto(2020, 5, 3)], Granularita::Giorno);
+        assert_eq!(istogramma.granularita, Granularita::Giorno);
+        let attuali: Vec<usize> = istogramma.periodi.iter().map(|&(_, a, _)| a).collect();
+        assert_eq!(attuali, [1, 0, 1]);
+    }
+
+    #[test]
+    fn istogramma_decenni_passa_a_granularita_piu_ampia() {
+        let lista = [foto(1995, 3, 10), foto(2000, 1, 1), foto(2020, 7, 4)];
+        let istogramma = calcola_istogramma(&lista, Granularita::Giorno);
+        assert_eq!(istogramma.granularita, Granularita::Mese);
+        assert_eq!(istogramma.periodi.len(), 25 * 12 + 5);
+        assert_eq!(istogramma.periodi.first().map(|p| p.0), NaiveDate::from_ymd_opt(1995, 3, 1));
+        assert_eq!(istogramma.periodi.iter().map(|&(_, a, _)| a).sum::<usize>(), 3);
+
+        let istogramma = calcola_istogramma(&[foto(1901, 1, 1), foto(2020, 7, 4)], Granularita::Giorno);
+        assert_eq!(istogramma.granularita, Granularita::Anno);
+        assert_eq!(istogramma.periodi.len(), 120);
+    }
+}
@@ -0,0 +1,326 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::FotoData;
+
+// Date inserite a mano (stampe scansionate, foto 1900-01-01): hanno la precedenza
+// su qualunque strategia. Arrivano da un file CSV/JSON o dalla GUI
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Precisione {
+    Anno,     // "1987": 1 gennaio
+    Mese,     // "1987-07": primo del mese
+    Giorno,   // "1987-07-14": ore 12:00:00, come le date dal nome file
+    Completa, // Data e ora
+}
+
+impl Precisione {
+    fn da_testo(testo: &str) -> Result<Self, String> {
+        match testo.trim().to_lowercase().as_str() {
+            "anno" | "year" => Ok(Precisione::Anno),
+            "mese" | "month" => Ok(Precisione::Mese),
+            "giorno" | "day" => Ok(Precisione::Giorno),
+            "completa" | "time" | "datetime" => Ok(Precisione::Completa),
+            altro => Err(format!("Precisione sconosciuta: {}", altro)),
+        }
+    }
+
+    // Come nel report (serde snake_case)
+    pub fn nome(&self) -> &str {
+        match self {
+            Precisione::Anno => "anno",
+            Precisione::Mese => "mese",
+            Precisione::Giorno => "giorno",
+            Precisione::Completa => "completa",
+        }
+    }
+
+    // Data come la scriverebbe l'utente, senza le parti inventate
+    pub fn formatta(&self, data: DateTime<Utc>) -> String {
+        match self {
+            Precisione::Anno => data.format("%Y").to_string(),
+            Precisione::Mese => data.format("%Y-%m").to_string(),
+            Precisione::Giorno => data.format("%Y-%m-%d").to_string(),
+            Precisione::Completa => data.format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataManuale {
+    pub data: DateTime<Utc>,
+    pub precisione: Precisione,
+    pub origine: String, // es. "date.csv:12" o "gui"
+}
+
+// Quali proposte sostituire con la data manuale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CampoData {
+    DateTimeOriginal,
+    CreateDate,
+    Entrambi,
+}
+
+impl CampoData {
+    pub fn display_name(&self) -> &str {
+        match self {
+            CampoData::DateTimeOriginal => "DateTimeOriginal",
+            CampoData::CreateDate => "CreateDate",
+            CampoData::Entrambi => "Both",
+        }
+    }
+}
+
+// Una riga del file di import: percorso o glob, con la sua data
+#[derive(Debug, Clone)]
+pub struct RigaOverride {
+    pub modello: String,
+    pub data: DateTime<Utc>,
+    pub precisione: Precisione,
+    pub origine: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EsitoImport {
+    pub applicate: usize, // Foto con una data manuale
+    pub non_trovate: Vec<RigaOverride>, // Righe che non corrispondono a nessuna foto
+}
+
+fn ora(data: NaiveDate, h: u32) -> DateTime<Utc> {
+    DateTime::from_naive_utc_and_offset(data.and_hms_opt(h, 0, 0).unwrap(), Utc)
+}
+
+// Accetta "1987", "1987-07", "1987-07-14", "1987-07-14 15:30[:00]", "1987:07:14 15:30:00"
+// (formato EXIF) e le date RFC 3339 del report. L'ora è sempre quella locale dello scatto:
+// un eventuale fuso o "Z" viene ignorato, come per l'EXIF
+pub fn interpreta_data(testo: &str) -> Result<(DateTime<Utc>, Precisione), String> {
+    let testo = testo.trim();
+    let errore = || format!("Data non valida: \"{}\" (usa AAAA, AAAA-MM, AAAA-MM-GG o AAAA-MM-GG HH:MM:SS)", testo);
+    // Le lunghezze e i tagli qui sotto sono in byte: "1987年07月14日" li farebbe cadere dentro un carattere
+    if !testo.is_ascii() {
+        return Err(errore());
+    }
+
+    if testo.len() == 4 {
+        let anno: i32 = testo.parse().map_err(|_| errore())?;
+        let data = NaiveDate::from_ymd_opt(anno, 1, 1).ok_or_else(errore)?;
+        return Ok((ora(data, 12), Precisione::Anno));
+    }
+    if testo.len() == 7 {
+        let data = NaiveDate::parse_from_str(&format!("{}-01", testo), "%Y-%m-%d").map_err(|_| errore())?;
+        return Ok((ora(data, 12), Precisione::Mese));
+    }
+    if testo.len() == 10 {
+        let data = NaiveDate::parse_from_str(&testo.replace(':', "-"), "%Y-%m-%d").map_err(|_| errore())?;
+        return Ok((ora(data, 12), Precisione::Giorno));
+    }
+
+    // Data e ora: si scarta il fuso (Z, +02:00) e si normalizzano i separatori
+    let senza_fuso = match testo.get(19..) {
+        Some(_) => &testo[..19],
+        None => testo,
+    };
+    let (giorno, orario) = senza_fuso.split_at(senza_fuso.len().min(10));
+    let normalizzato = format!("{} {}", giorno.replace(':', "-"), orario.trim_start_matches(['T', ' ']));
+    for formato in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(&normalizzato, formato) {
+            return Ok((DateTime::from_naive_utc_and_offset(dt, Utc), Precisione::Completa));
+        }
+    }
+    Err(errore())
+}
+
+fn riga(modello: &str, data: &str, precisione: Option<&str>, origine: String) -> Result<RigaOverride, String> {
+    let (data, precisione_letta) = interpreta_data(data).map_err(|e| format!("{}: {}", origine, e))?;
+    let precisione = match precisione.map(str::trim).filter(|p| !p.is_empty()) {
+        Some(p) => Precisione::da_testo(p).map_err(|e| format!("{}: {}", origine, e))?,
+        None => precisione_letta,
+    };
+    Ok(RigaOverride { modello: modello.trim().to_string(), data, precisione, origine })
+}
+
+// Nomi di colonna accettati: quelli del report (path, data_manuale, precisione_manuale)
+// o quelli brevi (path, date, precision)
+const COLONNE_DATA: [&str; 2] = ["data_manuale", "date"];
+const COLONNE_PRECISIONE: [&str; 2] = ["precisione_manuale", "precision"];
+
+fn leggi_csv(path: &Path, nome: &str) -> Result<Vec<RigaOverride>, String> {
+    // flexible: la colonna della precisione può mancare nelle singole righe
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)
+        .map_err(|e| format!("Errore apertura {}: {}", path.display(), e))?;
+    let intestazioni = reader.headers().map_err(|e| e.to_string())?.clone();
+    let colonna = |nomi: &[&str]| intestazioni.iter().position(|h| nomi.contains(&h.trim()));
+    let col_path = colonna(&["path"]).ok_or_else(|| format!("{}: manca la colonna \"path\"", path.display()))?;
+    let col_data = colonna(&COLONNE_DATA).ok_or_else(|| format!("{}: manca la colonna \"date\" o \"data_manuale\"", path.display()))?;
+    let col_precisione = colonna(&COLONNE_PRECISIONE);
+
+    let mut righe = Vec::new();
+    for (n, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| format!("{}: {}", path.display(), e))?;
+        let data = record.get(col_data).unwrap_or("").trim();
+        // Un report esportato ha una riga per foto: quelle senza data si ignorano
+        if data.is_empty() {
+            continue;
+        }
+        let modello = record.get(col_path).unwrap_or("");
+        // +2: intestazione e numerazione da 1
+        righe.push(riga(modello, data, col_precisione.and_then(|c| record.get(c)), format!("{}:{}", nome, n + 2))?);
+    }
+    Ok(righe)
+}
+
+fn riga_json(valore: &serde_json::Value, origine: String) -> Result<Option<RigaOverride>, String> {
+    let modello = valore.get("path").and_then(|p| p.as_str())
+        .ok_or_else(|| format!("{}: manca \"path\"", origine))?;
+    let testo = |v: &serde_json::Value| v.as_str().map(str::to_string);
+    // Nel report JSON Lines data_manuale è un oggetto {data, precisione, origine}
+    let (data, precisione) = match COLONNE_DATA.iter().find_map(|c| valore.get(*c).filter(|v| !v.is_null())) {
+        Some(v) if v.is_object() => (v.get("data").and_then(testo), v.get("precisione").and_then(testo)),
+        Some(v) => (testo(v), COLONNE_PRECISIONE.iter().find_map(|c| valore.get(*c).and_then(testo))),
+        None => (None, None),
+    };
+    match data.filter(|d| !d.trim().is_empty()) {
+        Some(data) => riga(modello, &data, precisione.as_deref(), origine).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn leggi_json(path: &Path, nome: &str) -> Result<Vec<RigaOverride>, String> {
+    let contenuto = std::fs::read_to_string(path)
+        .map_err(|e| format!("Errore lettura {}: {}", path.display(), e))?;
+    // Un array JSON, oppure JSON Lines come il report
+    let valori: Vec<(usize, serde_json::Value)> = match serde_json::from_str::<Vec<serde_json::Value>>(&contenuto) {
+        Ok(array) => array.into_iter().enumerate().map(|(i, v)| (i + 1, v)).collect(),
+        Err(_) => contenuto.lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty())
+            .map(|(i, l)| serde_json::from_str(l)
+                .map(|v| (i + 1, v))
+                .map_err(|e| format!("{}:{}: {}", nome, i + 1, e)))
+            .collect::<Result<_, _>>()?,
+    };
+
+    let mut righe = Vec::new();
+    for (n, valore) in valori {
+        if let Some(riga) = riga_json(&valore, format!("{}:{}", nome, n))? {
+            righe.push(riga);
+        }
+    }
+    Ok(righe)
+}
+
+// Legge un file di date manuali: .csv, oppure .json/.jsonl
+pub fn leggi_file_override(path: &Path) -> Result<Vec<RigaOverride>, String> {
+    let nome = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let estensione = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    match estensione.as_str() {
+        "csv" => leggi_csv(path, &nome),
+        "json" | "jsonl" => leggi_json(path, &nome),
+        _ => Err(format!("Formato non supportato: {} (usa .csv, .json o .jsonl)", path.display())),
+    }
+}
+
+fn is_glob(modello: &str) -> bool {
+    modello.contains(['*', '?', '['])
+}
+
+// Un percorso relativo corrisponde alla fine del percorso della foto
+// ("Foto del 1987/IMG_1.jpg"), uno assoluto a tutto il percorso
+fn corrisponde(modello: &str, pattern: Option<&glob::Pattern>, foto_path: &Path) -> bool {
+    match pattern {
+        Some(pattern) => {
+            let opzioni = glob::MatchOptions { require_literal_separator: true, ..Default::default() };
+            pattern.matches_path_with(foto_path, opzioni)
+        }
+        None => {
+            let modello = Path::new(modello);
+            foto_path == modello || (modello.is_relative() && foto_path.ends_with(modello))
+        }
+    }
+}
+
+// Assegna le date alle foto e ricalcola le proposte. Un percorso esatto vince su un glob;
+// tra più glob vince l'ultimo, così si può partire da "*" e poi precisare
+pub fn applica_override(foto_list: &mut [FotoData], righe: &[RigaOverride]) -> Result<EsitoImport, String> {
+    let pattern: Vec<Option<glob::Pattern>> = righe.iter()
+        .map(|r| {
+            if !is_glob(&r.modello) {
+                return Ok(None);
+            }
+            let testo = if Path::new(&r.modello).is_relative() && !r.modello.starts_with("**") {
+                format!("**/{}", r.modello)
+            } else {
+                r.modello.clone()
+            };
+            glob::Pattern::new(&testo)
+                .map(Some)
+                .map_err(|e| format!("{}: glob non valido \"{}\": {}", r.origine, r.modello, e))
+        })
+        .collect::<Result<_, String>>()?;
+
+    let mut usate = vec![false; righe.len()];
+    let mut esito = EsitoImport::default();
+    for foto in foto_list.iter_mut() {
+        let trovata = (0..righe.len()).rev()
+            .find(|&i| pattern[i].is_none() && corrisponde(&righe[i].modello, None, &foto.path))
+            .or_else(|| (0..righe.len()).rev()
+                .find(|&i| pattern[i].is_some() && corrisponde(&righe[i].modello, pattern[i].as_ref(), &foto.path)));
+        if let Some(i) = trovata {
+            usate[i] = true;
+            let riga = &righe[i];
+            imposta_data_manuale(foto, CampoData::Entrambi, Some(DataManuale {
+                data: riga.data,
+                precisione: riga.precisione,
+                origine: riga.origine.clone(),
+            }));
+            esito.applicate += 1;
+        }
+    }
+
+    esito.non_trovate = righe.iter().zip(&usate)
+        .filter(|(_, &usata)| !usata)
+        .map(|(r, _)| r.clone())
+        .collect();
+    Ok(esito)
+}
+
+// Imposta (o toglie, con None) la data manuale e aggiorna subito proposte e incongruenze
+pub fn imposta_data_manuale(foto: &mut FotoData, campo: CampoData, data: Option<DataManuale>) {
+    if campo != CampoData::CreateDate {
+        foto.data_manuale = data.clone();
+    }
+    if campo != CampoData::DateTimeOriginal {
+        foto.data_manuale_create_date = data;
+    }
+    let (dto, cd) = (foto.strategia_datetime_original.clone(), foto.strategia_create_date.clone());
+    foto.applica_strategie(&dto, &cd);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(testo: &str) -> (String, Precisione) {
+        let (data, precisione) = interpreta_data(testo).unwrap();
+        (data.format("%Y-%m-%d %H:%M:%S").to_string(), precisione)
+    }
+
+    #[test]
+    fn interpreta_data_precisioni() {
+        assert_eq!(data("1987"), ("1987-01-01 12:00:00".to_string(), Precisione::Anno));
+        assert_eq!(data("1987-07"), ("1987-07-01 12:00:00".to_string(), Precisione::Mese));
+        assert_eq!(data("1987-07-14"), ("1987-07-14 12:00:00".to_string(), Precisione::Giorno));
+        assert_eq!(data("1987:07:14"), ("1987-07-14 12:00:00".to_string(), Precisione::Giorno));
+        assert_eq!(data("1987-07-14 15:30"), ("1987-07-14 15:30:00".to_string(), Precisione::Completa));
+        assert_eq!(data("1987:07:14 15:30:05"), ("1987-07-14 15:30:05".to_string(), Precisione::Completa));
+        assert_eq!(data(" 1987-07-14T15:30:05+02:00 "), ("1987-07-14 15:30:05".to_string(), Precisione::Completa));
+    }
+
+    #[test]
+    fn interpreta_data_rifiuta_testo_non_valido() {
+        for testo in ["", "abcd", "1987-13", "1987-02-30", "1987-07-14 25:00", "1987年07月14日", "1987-07-14 15:30:0à"] {
+            assert!(interpreta_data(testo).is_err(), "{}", testo);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use chrono::{DateTime, Utc};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::FotoData;
+
+// Distanza di Hamming massima tra due dHash per considerarle la stessa immagine ricodificata
+pub const SOGLIA_PERCETTIVA: u32 = 4;
+
+// Hash del contenuto del file (blake3, esadecimale)
+pub fn hash_contenuto(path: &Path) -> Result<String, String> {
+    let mut file = fs::File::open(path)
+        .map_err(|e| format!("Errore apertura {}: {}", path.display(), e))?;
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut file, &mut hasher)
+        .map_err(|e| format!("Errore lettura {}: {}", path.display(), e))?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+// dHash a 64 bit: confronta pixel adiacenti di una miniatura 9x8 in scala di grigi.
+// Resiste a ricompressione e ridimensionamento; None se l'immagine non è decodificabile (es. RAW)
+pub fn hash_percettivo(path: &Path) -> Option<u64> {
+    let immagine = image::io::Reader::open(path).ok()?
+        .with_guessed_format().ok()?
+        .decode().ok()?;
+    let miniatura = immagine
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let sinistra = miniatura.get_pixel(x, y)[0];
+            let destra = miniatura.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | (sinistra < destra) as u64;
+        }
+    }
+    Some(hash)
+}
+
+// Union-find minimale sugli indici di foto_list
+struct Insiemi {
+    padre: Vec<usize>,
+}
+
+impl Insiemi {
+    fn nuovo(n: usize) -> Self {
+        Self { padre: (0..n).collect() }
+    }
+
+    fn radice(&mut self, mut i: usize) -> usize {
+        while self.padre[i] != i {
+            self.padre[i] = self.padre[self.padre[i]];
+            i = self.padre[i];
+        }
+        i
+    }
+
+    fn unisci(&mut self, a: usize, b: usize) {
+        let (ra, rb) = (self.radice(a), self.radice(b));
+        if ra != rb {
+            self.padre[rb] = ra;
+        }
+    }
+}
+
+fn data_nota(foto: &FotoData) -> Option<DateTime<Utc>> {
+    foto.exif_datetime_original.or(foto.data_json)
+}
+
+// Due foto simili con date di scatto diverse sono una raffica, non una copia ricodificata
+fn date_compatibili(a: &FotoData, b: &FotoData) -> bool {
+    match (data_nota(a), data_nota(b)) {
+        (Some(da), Some(db)) => (da - db).num_seconds().abs() <= 1,
+        _ => true,
+    }
+}
+
+// Ordine dei membri di un gruppo: il primo è la copia da tenere
+// (file più grande, con JSON, nella cartella "Photos from ...", percorso più corto)
+pub(crate) fn chiave_rappresentante(foto: &FotoData) -> (std::cmp::Reverse<u64>, bool, bool, usize, std::path::PathBuf) {
+    let dimensione = fs::metadata(&foto.path).map(|m| m.len()).unwrap_or(0);
+    let in_cartella_anno = foto.path.parent()
+        .and_then(|p| p.file_name())
+        .map(|n| {
+            let n = n.to_string_lossy();
+            n.starts_with("Photos from") || n.starts_with("Foto del")
+        })
+        .unwrap_or(false);
+    (
+        std::cmp::Reverse(dimensione),
+        foto.data_json.is_none(),
+        !in_cartella_anno,
+        foto.path.as_os_str().len(),
+        foto.path.clone(),
+    )
+}
+
+// Raggruppa le copie della stessa foto. Restituisce gruppi di indici in foto_list
+// (solo gruppi con almeno due membri), il rappresentante per primo.
+// `progresso` conta le foto esaminate: foto_list.len(), o il doppio con l'hash percettivo
+pub fn trova_gruppi(foto_list: &[FotoData], percettivo: bool, progresso: Option<&AtomicUsize>) -> Vec<Vec<usize>> {
+    let avanza = |n: usize| {
+        if let Some(contatore) = progresso {
+            contatore.fetch_add(n, Ordering::Relaxed);
+        }
+    };
+    let mut insiemi = Insiemi::nuovo(foto_list.len());
+
+    // Copie identiche: solo file con la stessa dimensione possono avere lo stesso hash
+    let mut per_dimensione: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (idx, foto) in foto_list.iter().enumerate() {
+        if let Ok(meta) = fs::metadata(&foto.path) {
+            per_dimensione.entry(meta.len()).or_default().push(idx);
+        }
+    }
+    let candidati: Vec<usize> = per_dimensione.into_values()
+        .filter(|v| v.len() > 1)
+        .flatten()
+        .collect();
+    avanza(foto_list.len() - candidati.len());
+
+    let hash: Vec<(usize, String)> = candidati.par_iter()
+        .filter_map(|&idx| {
+            let risultato = hash_contenuto(&foto_list[idx].path);
+            avanza(1);
+            match risultato {
+                Ok(h) => Some((idx, h)),
+                Err(e) => {
+                    eprintln!("Hash: {}", e);
+                    None
+                }
+            }
+        })
+        .collect();
+    let mut per_hash: HashMap<&str, usize> = HashMap::new();
+    for (idx, h) in &hash {
+        match per_hash.get(h.as_str()) {
+            Some(&primo) => insiemi.unisci(primo, *idx),
+            None => {
+                per_hash.insert(h, *idx);
+            }
+        }
+    }
+
+    // Copie ricodificate (album, condivisioni): dHash vicini e date di scatto compatibili
+    if percettivo {
+        let dhash: Vec<(usize, u64)> = (0..foto_list.len()).into_par_iter()
+            .filter_map(|idx| {
+                let h = hash_percettivo(&foto_list[idx].path);
+                avanza(1);
+                h.map(|h| (idx, h))
+            })
+            .collect();
+        for (i, &(a, ha)) in dhash.iter().enumerate() {
+            for &(b, hb) in &dhash[i + 1..] {
+                if (ha ^ hb).count_ones() <= SOGLIA_PERCETTIVA && date_compatibili(&foto_list[a], &foto_list[b]) {
+                    insiemi.unisci(a, b);
+                }
+            }
+        }
+    }
+
+    let mut gruppi: HashMap<usize, Vec<usize>> = HashMap::new();
+    for idx in 0..foto_list.len() {
+        let radice = insiemi.radice(idx);
+        gruppi.entry(radice).or_default().push(idx);
+    }
+    let mut gruppi: Vec<Vec<usize>> = gruppi.into_values()
+        .filter(|g| g.len() > 1)
+        .map(|mut g| {
+            g.sort_by_cached_key(|&idx| chiave_rappresentante(&foto_list[idx]));
+            g
+        })
+        .collect();
+    gruppi.sort_by(|a, b| foto_list[a[0]].path.cmp(&foto_list[b[0]].path));
+    gruppi
+}
+
+// Segna ogni foto con il numero del suo gruppo (da 1), None per le foto uniche
+pub fn assegna_gruppi(foto_list: &mut [FotoData], gruppi: &[Vec<usize>]) {
+    for foto in foto_list.iter_mut() {
+        foto.gruppo_duplicati = None;
+    }
+    for (n, gruppo) in gruppi.iter().enumerate() {
+        for &idx in gruppo {
+            foto_list[idx].gruppo_duplicati = Some(n + 1);
+        }
+    }
+}
+
+// Data più frequente tra i membri (a parità, la più vecchia)
+fn data_di_gruppo(date: impl Iterator<Item = DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    let mut conteggi: HashMap<DateTime<Utc>, usize> = HashMap::new();
+    for data in date {
+        *conteggi.entry(data).or_default() += 1;
+    }
+    conteggi.into_iter()
+        .max_by(|(da, na), (db, nb)| na.cmp(nb).then(db.cmp(da)))
+        .map(|(d, _)| d)
+}
+
+// Stessa proposta per tutte le copie di un gruppo, così non finiscono con date diverse.
+// Restituisce il numero di foto la cui proposta è cambiata
+pub fn uniforma_proposte(foto_list: &mut [FotoData]) -> usize {
+    let mut gruppi: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (idx, foto) in foto_list.iter().enumerate() {
+        if let Some(g) = foto.gruppo_duplicati {
+            gruppi.entry(g).or_default().push(idx);
+        }
+    }
+
+    let mut cambiate = 0;
+    for membri in gruppi.values() {
+        // Il segnaposto 1900-01-01 non deve prevalere su una data vera
+        let dto = data_di_gruppo(membri.iter()
+            .filter(|&&i| !foto_list[i].is_foto_1900())
+            .filter_map(|&i| foto_list[i].proposta_datetime_original));
+        let cd = data_di_gruppo(membri.iter()
+            .filter(|&&i| !foto_list[i].is_foto_1900())
+            .filter_map(|&i| foto_list[i].proposta_create_date));
+
+        for &idx in membri {
+            let foto = &mut foto_list[idx];
+            // Una data inserita a mano non si tocca
+            if foto.ha_data_manuale() {
+                continue;
+            }
+            let mut cambiata = false;
+            if dto.is_some() && foto.proposta_datetime_original != dto {
+                foto.proposta_datetime_original = dto;
+                cambiata = true;
+            }
+            if cd.is_some() && foto.proposta_create_date != cd {
+                foto.proposta_create_date = cd;
+                cambiata = true;
+            }
+            if cambiata {
+                foto.incongruenze = crate::rileva_incongruenze(foto);
+                foto.gravita_incongruenza = crate::calcola_gravita_incongruenza(foto);
+                cambiate += 1;
+            }
+        }
+    }
+    cambiate
+}
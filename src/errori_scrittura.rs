@@ -0,0 +1,133 @@
+use serde::Serialize;
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+
+// Errori di scrittura con quanto serve per capirli e riprovare: cosa si stava
+// scrivendo, cosa ha risposto exiftool e una causa probabile dedotta dal messaggio
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CausaErrore {
+    SolaLettura,
+    FileMancante,
+    ExifCorrotto,
+    FormatoNonSupportato,
+    ExiftoolMancante,
+    VerificaFallita,
+    Altro,
+}
+
+impl CausaErrore {
+    pub fn display_name(&self) -> &str {
+        match self {
+            CausaErrore::SolaLettura => "read-only",
+            CausaErrore::FileMancante => "file missing",
+            CausaErrore::ExifCorrotto => "corrupt EXIF",
+            CausaErrore::FormatoNonSupportato => "unsupported format",
+            CausaErrore::ExiftoolMancante => "exiftool missing",
+            CausaErrore::VerificaFallita => "verification failed",
+            CausaErrore::Altro => "other",
+        }
+    }
+
+    pub fn suggerimento(&self) -> &str {
+        match self {
+            CausaErrore::SolaLettura => "Check the permissions of the file and its folder, or write XMP sidecars elsewhere",
+            CausaErrore::FileMancante => "The file was moved or deleted after the scan: reload the folder",
+            CausaErrore::ExifCorrotto => "Retry in repair mode: the metadata is rebuilt from the readable tags",
+            CausaErrore::FormatoNonSupportato => "exiftool cannot write this format: use XMP sidecar output",
+            CausaErrore::ExiftoolMancante => "Install exiftool and make sure it is in the PATH",
+            CausaErrore::VerificaFallita => "The file was written but reads back differently: check it with exiftool, or undo the apply",
+            CausaErrore::Altro => "See the exiftool message",
+        }
+    }
+}
+
+// Causa probabile dal messaggio di exiftool o dall'errore di sistema
+pub fn classifica(messaggio: &str) -> CausaErrore {
+    let messaggio = messaggio.to_lowercase();
+    let contiene = |parole: &[&str]| parole.iter().any(|p| messaggio.contains(p));
+    if contiene(&["exiftool non trovato"]) {
+        CausaErrore::ExiftoolMancante
+    } else if contiene(&["permission denied", "read-only", "error creating temporary file", "error renaming", "error opening file for update"]) {
+        CausaErrore::SolaLettura
+    } else if contiene(&["file not found", "no such file"]) {
+        CausaErrore::FileMancante
+    } else if contiene(&["not supported", "can't currently write", "unknown file type", "writing of this type"]) {
+        CausaErrore::FormatoNonSupportato
+    } else if contiene(&["corrupt", "bad ", "format error", "error reading", "truncated", "ifd", "not a valid", "maker notes"]) {
+        CausaErrore::ExifCorrotto
+    } else {
+        CausaErrore::Altro
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErroreScrittura {
+    pub path: PathBuf,
+    pub tag: String,                // Cosa si stava scrivendo: "DateTimeOriginal, CreateDate", "XMP sidecar", ...
+    pub codice_uscita: Option<i32>, // Di exiftool; None se l'errore non viene da exiftool
+    pub stderr: String,
+    pub causa: CausaErrore,
+}
+
+impl ErroreScrittura {
+    pub fn nuovo(path: PathBuf, tag: impl Into<String>, codice_uscita: Option<i32>, stderr: impl Into<String>) -> Self {
+        let stderr = stderr.into().trim().to_string();
+        let causa = classifica(&stderr);
+        ErroreScrittura { path, tag: tag.into(), codice_uscita, stderr, causa }
+    }
+
+    // Scrittura riuscita secondo exiftool ma smentita dalla rilettura
+    pub fn verifica(path: PathBuf, tag: impl Into<String>, messaggio: impl Into<String>) -> Self {
+        ErroreScrittura { path, tag: tag.into(), codice_uscita: None, stderr: messaggio.into(), causa: CausaErrore::VerificaFallita }
+    }
+}
+
+impl fmt::Display for ErroreScrittura {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} non scritto per {} ({}", self.tag, self.path.display(), self.causa.display_name())?;
+        if let Some(codice) = self.codice_uscita {
+            write!(f, ", exit code {}", codice)?;
+        }
+        write!(f, "): {}", self.stderr)
+    }
+}
+
+// Riga dell'esportazione, uguale per CSV e JSON Lines
+#[derive(Serialize)]
+struct RecordErrore<'a> {
+    path: String,
+    tag: &'a str,
+    codice_uscita: Option<i32>,
+    causa: &'a str,
+    stderr: &'a str,
+}
+
+impl<'a> RecordErrore<'a> {
+    fn da_errore(errore: &'a ErroreScrittura) -> Self {
+        RecordErrore {
+            path: errore.path.to_string_lossy().to_string(),
+            tag: &errore.tag,
+            codice_uscita: errore.codice_uscita,
+            causa: errore.causa.display_name(),
+            stderr: &errore.stderr,
+        }
+    }
+}
+
+pub fn scrivi_csv<'a>(errori: impl IntoIterator<Item = &'a ErroreScrittura>, output: impl Write) -> Result<(), String> {
+    let mut writer = csv::Writer::from_writer(output);
+    for errore in errori {
+        writer.serialize(RecordErrore::da_errore(errore)).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+pub fn scrivi_jsonl<'a>(errori: impl IntoIterator<Item = &'a ErroreScrittura>, mut output: impl Write) -> Result<(), String> {
+    for errore in errori {
+        serde_json::to_writer(&mut output, &RecordErrore::da_errore(errore)).map_err(|e| e.to_string())?;
+        writeln!(output).map_err(|e| e.to_string())?;
+    }
+    output.flush().map_err(|e| e.to_string())
+}
@@ -0,0 +1,114 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::io::Write;
+use crate::FotoData;
+
+// Report leggibile da programmi: JSON Lines (una foto per riga) o CSV.
+// Schema descritto nel README ("Report Schema"): aggiungere campi non cambia versione,
+// rinominarli, toglierli o cambiarne il significato sì
+pub const VERSIONE_SCHEMA: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum FormatoReport {
+    #[value(name = "text")]
+    Testo,
+    #[value(name = "jsonl")]
+    JsonLines,
+    #[value(name = "csv")]
+    Csv,
+}
+
+// Riga JSON Lines: FotoData completa con la versione dello schema
+#[derive(Serialize)]
+struct RecordJson<'a> {
+    versione_schema: u32,
+    #[serde(flatten)]
+    foto: &'a FotoData,
+}
+
+// Riga CSV: solo valori semplici, una colonna per data
+#[derive(Serialize)]
+struct RecordCsv {
+    versione_schema: u32,
+    path: String,
+    nome_file: String,
+    data_nome: Option<String>,
+    data_json: Option<String>,
+    data_json_creation: Option<String>,
+    exif_datetime_original: Option<String>,
+    exif_create_date: Option<String>,
+    exif_modify_date: Option<String>,
+    xmp_datetime_original: Option<String>,
+    xmp_create_date: Option<String>,
+    proposta_datetime_original: Option<String>,
+    strategia_datetime_original: String,
+    proposta_create_date: Option<String>,
+    strategia_create_date: String,
+    incongruenze: String,
+    gravita_incongruenza: i64,
+    data_manuale: Option<String>,
+    precisione_manuale: Option<String>,
+    data_interpolata: Option<String>,
+    confidenza_interpolazione: Option<f64>,
+    origine_interpolazione: Option<String>,
+    fotocamera: Option<String>,
+    sidecar_xmp: Option<String>,
+    gruppo_duplicati: Option<usize>,
+    archivio: Option<String>,
+}
+
+// Stesso formato delle date nel JSON (RFC 3339), così i due report sono confrontabili
+fn data(valore: Option<DateTime<Utc>>) -> Option<String> {
+    valore.map(|d| d.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+}
+
+impl RecordCsv {
+    fn da_foto(foto: &FotoData) -> Self {
+        Self {
+            versione_schema: VERSIONE_SCHEMA,
+            path: foto.path.to_string_lossy().to_string(),
+            nome_file: foto.nome_file.clone(),
+            data_nome: foto.data_nome.map(|(a, m, g)| format!("{:04}-{:02}-{:02}", a, m, g)),
+            data_json: data(foto.data_json),
+            data_json_creation: data(foto.data_json_creation),
+            exif_datetime_original: data(foto.exif_datetime_original),
+            exif_create_date: data(foto.exif_create_date),
+            exif_modify_date: data(foto.exif_modify_date),
+            xmp_datetime_original: data(foto.xmp_datetime_original),
+            xmp_create_date: data(foto.xmp_create_date),
+            proposta_datetime_original: data(foto.proposta_datetime_original),
+            strategia_datetime_original: foto.strategia_datetime_original.clone(),
+            proposta_create_date: data(foto.proposta_create_date),
+            strategia_create_date: foto.strategia_create_date.clone(),
+            incongruenze: foto.incongruenze.join("; "),
+            gravita_incongruenza: foto.gravita_incongruenza,
+            // Colonna da compilare per reimportare il report come date manuali
+            data_manuale: foto.data_manuale.as_ref().map(|m| m.precisione.formatta(m.data)),
+            precisione_manuale: foto.data_manuale.as_ref().map(|m| m.precisione.nome().to_string()),
+            data_interpolata: data(foto.data_interpolata.as_ref().map(|i| i.data)),
+            confidenza_interpolazione: foto.data_interpolata.as_ref().map(|i| i.confidenza),
+            origine_interpolazione: foto.data_interpolata.as_ref().map(|i| i.origine.clone()),
+            fotocamera: foto.fotocamera.clone(),
+            sidecar_xmp: foto.sidecar_xmp.as_ref().map(|p| p.to_string_lossy().to_string()),
+            gruppo_duplicati: foto.gruppo_duplicati,
+            archivio: foto.origine_archivio.as_ref().map(|o| o.foto.archivio.to_string_lossy().to_string()),
+        }
+    }
+}
+
+pub fn scrivi_jsonl<'a>(foto_list: impl IntoIterator<Item = &'a FotoData>, mut output: impl Write) -> Result<(), String> {
+    for foto in foto_list {
+        let record = RecordJson { versione_schema: VERSIONE_SCHEMA, foto };
+        serde_json::to_writer(&mut output, &record).map_err(|e| e.to_string())?;
+        writeln!(output).map_err(|e| e.to_string())?;
+    }
+    output.flush().map_err(|e| e.to_string())
+}
+
+pub fn scrivi_csv<'a>(foto_list: impl IntoIterator<Item = &'a FotoData>, output: impl Write) -> Result<(), String> {
+    let mut writer = csv::Writer::from_writer(output);
+    for foto in foto_list {
+        writer.serialize(RecordCsv::da_foto(foto)).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
@@ -0,0 +1,237 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Condvar, Mutex, OnceLock};
+
+// Processi exiftool sempre aperti (-stay_open True -@ -): avviare Perl costa più della
+// scrittura di una foto, quindi ogni processo riceve un comando dopo l'altro sullo stdin,
+// un argomento per riga e -executeN in fondo, e risponde con {readyN}. Un comando è un
+// file: si aspetta la sua risposta prima del successivo, e il parallelismo viene dai più
+// processi che lavorano insieme; uno che muore viene sostituito e il comando riprovato
+
+// Quanti comandi in parallelo lo decide il pool di thread delle scritture (-j);
+// il limite evita solo di riempire la memoria di processi Perl
+const MASSIMO_PROCESSI: usize = 32;
+
+const EXIFTOOL_MANCANTE: &str = "exiftool non trovato. Assicurati che sia installato e nel PATH.";
+
+#[derive(Debug, Clone)]
+pub struct RispostaExiftool {
+    pub stdout: String,
+    pub stderr: String,
+    // Con un processo aperto exiftool non dà un codice di uscita per comando:
+    // 1 se ha segnalato un errore, come farebbe uscendo
+    pub codice_uscita: i32,
+}
+
+impl RispostaExiftool {
+    pub fn riuscita(&self) -> bool {
+        self.codice_uscita == 0
+    }
+}
+
+struct ProcessoExiftool {
+    figlio: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    // Righe di stderr lette da un thread, così exiftool non si blocca se ne scrive tante
+    stderr: mpsc::Receiver<String>,
+}
+
+impl ProcessoExiftool {
+    fn avvia() -> Result<Self, String> {
+        let mut figlio = Command::new("exiftool")
+            .args(["-stay_open", "True", "-@", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => EXIFTOOL_MANCANTE.to_string(),
+                _ => format!("Errore avvio exiftool: {}", e),
+            })?;
+        let (Some(stdin), Some(stdout), Some(stderr)) = (figlio.stdin.take(), figlio.stdout.take(), figlio.stderr.take()) else {
+            let _ = figlio.kill();
+            return Err("Errore avvio exiftool: pipe non disponibili".to_string());
+        };
+        let (sender, ricevitore) = mpsc::channel();
+        std::thread::spawn(move || {
+            for riga in BufReader::new(stderr).lines() {
+                let Ok(riga) = riga else { break };
+                if sender.send(riga).is_err() {
+                    break;
+                }
+            }
+        });
+        eprintln!("[DEBUG] Avviato exiftool -stay_open (pid {})", figlio.id());
+        Ok(ProcessoExiftool { figlio, stdin, stdout: BufReader::new(stdout), stderr: ricevitore })
+    }
+
+    // Err solo se il processo non risponde più (morto o pipe chiusa)
+    fn esegui(&mut self, args: &[String], numero: u64) -> Result<RispostaExiftool, String> {
+        let fine = format!("{{ready{}}}", numero);
+        let mut comando = String::new();
+        for arg in args {
+            comando.push_str(arg);
+            comando.push('\n');
+        }
+        // -echo4 scrive su stderr dopo il comando: segna dove finisce il suo stderr
+        comando.push_str(&format!("-echo4\n{}\n-execute{}\n", fine, numero));
+        self.stdin.write_all(comando.as_bytes())
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("exiftool non risponde: {}", e))?;
+
+        let mut stdout = String::new();
+        loop {
+            let mut riga = String::new();
+            let letti = self.stdout.read_line(&mut riga).map_err(|e| format!("exiftool non risponde: {}", e))?;
+            if letti == 0 {
+                return Err("exiftool terminato durante il comando".to_string());
+            }
+            if riga.trim_end() == fine {
+                break;
+            }
+            stdout.push_str(&riga);
+        }
+        let mut stderr = String::new();
+        loop {
+            let riga = self.stderr.recv().map_err(|_| "exiftool terminato durante il comando".to_string())?;
+            if riga.trim_end() == fine {
+                break;
+            }
+            stderr.push_str(&riga);
+            stderr.push('\n');
+        }
+
+        let errore = stderr.lines().any(|r| r.starts_with("Error")) || stdout.contains("weren't updated due to errors");
+        Ok(RispostaExiftool { stdout, stderr, codice_uscita: if errore { 1 } else { 0 } })
+    }
+}
+
+impl Drop for ProcessoExiftool {
+    fn drop(&mut self) {
+        // Chiusura ordinata; se il processo è già morto la scrittura fallisce e wait ritorna subito
+        let _ = self.stdin.write_all(b"-stay_open\nFalse\n").and_then(|_| self.stdin.flush());
+        let _ = self.figlio.wait();
+    }
+}
+
+struct StatoPool {
+    liberi: Vec<ProcessoExiftool>,
+    avviati: usize,
+}
+
+pub struct PoolExiftool {
+    stato: Mutex<StatoPool>,
+    segnale: Condvar,
+    massimo: usize,
+    numero_comando: AtomicU64,
+}
+
+impl PoolExiftool {
+    pub fn new(massimo: usize) -> Self {
+        PoolExiftool {
+            stato: Mutex::new(StatoPool { liberi: Vec::new(), avviati: 0 }),
+            segnale: Condvar::new(),
+            massimo: massimo.max(1),
+            numero_comando: AtomicU64::new(1),
+        }
+    }
+
+    // Un processo libero, uno nuovo se non si è al massimo, altrimenti aspetta
+    fn prendi(&self) -> Result<ProcessoExiftool, String> {
+        let mut stato = self.stato.lock().unwrap();
+        loop {
+            if let Some(processo) = stato.liberi.pop() {
+                return Ok(processo);
+            }
+            if stato.avviati < self.massimo {
+                stato.avviati += 1;
+                drop(stato);
+                return ProcessoExiftool::avvia().inspect_err(|_| self.scarta());
+            }
+            stato = self.segnale.wait(stato).unwrap();
+        }
+    }
+
+    fn restituisci(&self, processo: ProcessoExiftool) {
+        self.stato.lock().unwrap().liberi.push(processo);
+        self.segnale.notify_one();
+    }
+
+    fn scarta(&self) {
+        self.stato.lock().unwrap().avviati -= 1;
+        self.segnale.notify_one();
+    }
+
+    pub fn esegui(&self, args: &[String]) -> Result<RispostaExiftool, String> {
+        let mut tentativo = 0;
+        loop {
+            let mut processo = self.prendi()?;
+            let numero = self.numero_comando.fetch_add(1, Ordering::Relaxed);
+            match processo.esegui(args, numero) {
+                Ok(risposta) => {
+                    self.restituisci(processo);
+                    return Ok(risposta);
+                }
+                Err(e) => {
+                    // Processo morto: si sostituisce e il comando si riprova una volta
+                    // (le scritture sono idempotenti)
+                    eprintln!("[DEBUG] Processo exiftool perso, riavvio: {}", e);
+                    drop(processo);
+                    self.scarta();
+                    tentativo += 1;
+                    if tentativo > 1 {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+
+    // Chiude i processi liberi (quelli in uso si chiudono quando tornano e il pool viene lasciato)
+    pub fn chiudi(&self) {
+        let liberi = {
+            let mut stato = self.stato.lock().unwrap();
+            let liberi = std::mem::take(&mut stato.liberi);
+            stato.avviati -= liberi.len();
+            liberi
+        };
+        drop(liberi);
+    }
+}
+
+// Pool condiviso da tutte le scritture
+pub fn pool() -> &'static PoolExiftool {
+    static POOL: OnceLock<PoolExiftool> = OnceLock::new();
+    POOL.get_or_init(|| PoolExiftool::new(MASSIMO_PROCESSI))
+}
+
+// Un argomento va su una riga dell'argfile: niente a capo, spazi ai bordi (exiftool li toglie)
+// o # iniziale (commento)
+fn passabile_in_argfile(arg: &str) -> bool {
+    !arg.is_empty() && !arg.contains(['\n', '\r']) && arg.trim() == arg && !arg.starts_with('#')
+}
+
+// Esegue exiftool con questi argomenti sul file in un processo del pool. Quello che l'argfile
+// non può rappresentare (una descrizione su più righe, un nome file non UTF-8 di una vecchia
+// fotocamera) passa da un processo a parte, con il path così com'è
+pub fn esegui(args: &[String], file: &Path) -> Result<RispostaExiftool, String> {
+    if let Some(nome) = file.to_str().filter(|n| passabile_in_argfile(n)) {
+        if args.iter().all(|a| passabile_in_argfile(a)) {
+            let mut args = args.to_vec();
+            args.push(nome.to_string());
+            return pool().esegui(&args);
+        }
+    }
+    let output = Command::new("exiftool").args(args).arg(file).output().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => EXIFTOOL_MANCANTE.to_string(),
+        _ => format!("Errore esecuzione exiftool: {}", e),
+    })?;
+    Ok(RispostaExiftool {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        codice_uscita: output.status.code().unwrap_or(-1),
+    })
+}
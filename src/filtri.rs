@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use crate::FotoData;
+
+// Strategie e filtri condivisi da GUI e riga di comando
+
+// Nelle impostazioni con gli stessi nomi della riga di comando
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategia {
+    #[value(name = "nome_file")]
+    NomeFile,
+    #[value(name = "json_photo_taken")]
+    JsonPhotoTaken, // photoTakenTime from JSON
+    #[value(name = "json_creation")]
+    JsonCreation,   // creationTime from JSON
+    #[value(name = "exif_attuale")]
+    ExifAttuale,
+    #[value(name = "nome_file_preferito")]
+    NomeFilePreferito,
+    #[value(name = "json_preferito")]
+    JsonPreferito,  // Prefer photoTakenTime, otherwise creationTime
+    #[value(name = "xmp_sidecar")]
+    XmpSidecar,     // exif:DateTimeOriginal from an existing .xmp sidecar
+    #[value(name = "json_interpolato")]
+    JsonInterpolato, // Like JsonPreferito, undated photos interpolated from their neighbors
+}
+
+impl Strategia {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Strategia::NomeFile => "nome_file",
+            Strategia::JsonPhotoTaken => "json_photo_taken",
+            Strategia::JsonCreation => "json_creation",
+            Strategia::ExifAttuale => "exif_attuale",
+            Strategia::NomeFilePreferito => "nome_file_preferito",
+            Strategia::JsonPreferito => "json_preferito",
+            Strategia::XmpSidecar => "xmp_sidecar",
+            Strategia::JsonInterpolato => "json_interpolato",
+        }
+    }
+    
+    #[allow(dead_code)]
+    fn from_str(s: &str) -> Self {
+        match s {
+            "nome_file" => Strategia::NomeFile,
+            "json_photo_taken" => Strategia::JsonPhotoTaken,
+            "json_creation" => Strategia::JsonCreation,
+            "exif_attuale" => Strategia::ExifAttuale,
+            "nome_file_preferito" => Strategia::NomeFilePreferito,
+            "json_preferito" => Strategia::JsonPreferito,
+            "xmp_sidecar" => Strategia::XmpSidecar,
+            "json_interpolato" => Strategia::JsonInterpolato,
+            _ => Strategia::NomeFilePreferito,
+        }
+    }
+    
+    pub fn display_name(&self) -> &str {
+        match self {
+            Strategia::NomeFile => "Use year from filename",
+            Strategia::JsonPhotoTaken => "Use photoTakenTime from JSON",
+            Strategia::JsonCreation => "Use creationTime from JSON",
+            Strategia::ExifAttuale => "Keep current EXIF",
+            Strategia::NomeFilePreferito => "Prefer filename, otherwise JSON",
+            Strategia::JsonPreferito => "Prefer JSON photoTakenTime, otherwise filename",
+            Strategia::XmpSidecar => "Use date from existing XMP sidecar",
+            Strategia::JsonInterpolato => "Prefer JSON, otherwise filename, otherwise interpolate from neighbors",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum, Serialize, Deserialize)]
+pub enum FiltroIncongruenza {
+    #[value(name = "all")]
+    #[serde(rename = "all")]
+    Tutte,
+    #[value(name = "missing-exif")]
+    #[serde(rename = "missing-exif")]
+    SoloExifMancante,
+    #[value(name = "exif-year-vs-filename")]
+    #[serde(rename = "exif-year-vs-filename")]
+    ExifAnnoDiversoFilename,
+    #[value(name = "exif-vs-json")]
+    #[serde(rename = "exif-vs-json")]
+    ExifDiversoJson,
+}
+
+impl FiltroIncongruenza {
+    pub fn display_name(&self) -> &str {
+        match self {
+            FiltroIncongruenza::Tutte => "Tutte le incongruenze",
+            FiltroIncongruenza::SoloExifMancante => "Solo EXIF mancante",
+            FiltroIncongruenza::ExifAnnoDiversoFilename => "EXIF anno ≠ filename",
+            FiltroIncongruenza::ExifDiversoJson => "EXIF ≠ JSON photoTakenTime",
+        }
+    }
+    
+    pub fn matches(&self, foto: &FotoData) -> bool {
+        match self {
+            FiltroIncongruenza::Tutte => !foto.incongruenze.is_empty(),
+            FiltroIncongruenza::SoloExifMancante => {
+                foto.incongruenze.iter().any(|inc| inc.contains("EXIF DateTimeOriginal mancante"))
+            }
+            FiltroIncongruenza::ExifAnnoDiversoFilename => {
+                foto.incongruenze.iter().any(|inc| inc.contains("EXIF anno") && inc.contains("filename anno"))
+            }
+            FiltroIncongruenza::ExifDiversoJson => {
+                foto.incongruenze.iter().any(|inc| inc.contains("EXIF") && inc.contains("JSON photoTakenTime"))
+            }
+        }
+    }
+}
+
+// Gli stessi filtri della tabella della GUI
+#[derive(Debug, Clone, Copy)]
+pub struct FiltroFoto {
+    pub soglia_gravita_giorni: f32,
+    pub mostra_tutte_foto: bool, // Anche le foto senza incongruenze
+    pub solo_exif_mancante: bool, // Solo foto senza DateTimeOriginal e CreateDate
+    pub filtro_incongruenza: FiltroIncongruenza,
+    // Categorie: se almeno una è attiva, solo le foto di quelle categorie
+    pub mostra_foto_1900: bool,
+    pub mostra_foto_whatsapp: bool,
+    pub mostra_foto_raw: bool,
+    pub solo_duplicati: bool,
+    pub periodo: Option<crate::cronologia::FiltroPeriodo>, // Barra dell'istogramma cliccata nella GUI
+}
+
+impl Default for FiltroFoto {
+    fn default() -> Self {
+        Self {
+            soglia_gravita_giorni: 0.0,
+            mostra_tutte_foto: false,
+            solo_exif_mancante: false,
+            filtro_incongruenza: FiltroIncongruenza::Tutte,
+            mostra_foto_1900: false,
+            mostra_foto_whatsapp: false,
+            mostra_foto_raw: false,
+            solo_duplicati: false,
+            periodo: None,
+        }
+    }
+}
+
+impl FiltroFoto {
+    pub fn accetta(&self, f: &FotoData) -> bool {
+        let soglia_secondi = (self.soglia_gravita_giorni * 86400.0) as i64;
+        
+        // Periodo scelto sull'istogramma: tutte le foto del periodo, indipendentemente dagli altri filtri
+        if let Some(periodo) = self.periodo {
+            return periodo.accetta(f);
+        }
+        
+        // Vista duplicati: tutte le foto con copie, indipendentemente dagli altri filtri
+        if self.solo_duplicati {
+            return f.gruppo_duplicati.is_some();
+        }
+        
+        // Filtro principale: solo EXIF mancante se abilitato
+        if self.solo_exif_mancante {
+            // Mostra solo foto con DateTimeOriginal E CreateDate ENTRAMBI mancanti
+            if f.exif_datetime_original.is_some() || f.exif_create_date.is_some() {
+                return false; // Ha almeno uno dei campi EXIF, quindi escludi
+            }
+        }
+        
+        // Filtro per tipo di incongruenza (solo se ci sono incongruenze)
+        if !f.incongruenze.is_empty() {
+            if !self.filtro_incongruenza.matches(f) {
+                return false;
+            }
+        } else {
+            // Se non ci sono incongruenze, mostra solo se:
+            // - mostra_tutte_foto è true, OPPURE
+            // - solo_exif_mancante è true (per mostrare quelle con EXIF mancante anche senza incongruenze)
+            if !self.mostra_tutte_foto && !self.solo_exif_mancante {
+                return false;
+            }
+            // Se solo_exif_mancante è true ma non ci sono incongruenze,
+            // mostra solo se ha EXIF mancante (già controllato sopra)
+            if self.solo_exif_mancante {
+                return true; // Ha EXIF mancante (già verificato sopra)
+            }
+        }
+        
+        // Filtro per categorie di foto
+        // Se almeno un checkbox è selezionato, mostra SOLO quelle categorie
+        let is_1900 = f.is_foto_1900();
+        let is_whatsapp = f.is_foto_whatsapp();
+        let is_raw = f.is_foto_raw();
+        
+        // Controlla se almeno un filtro categoria è attivo
+        let almeno_un_filtro_attivo = self.mostra_foto_1900 || self.mostra_foto_whatsapp || self.mostra_foto_raw;
+        
+        if almeno_un_filtro_attivo {
+            // Se almeno un filtro è attivo, mostra SOLO le foto delle categorie selezionate
+            let categoria_selezionata = 
+                (is_1900 && self.mostra_foto_1900) ||
+                (is_whatsapp && self.mostra_foto_whatsapp) ||
+                (is_raw && self.mostra_foto_raw);
+            
+            if !categoria_selezionata {
+                return false; // Nascondi se non appartiene a nessuna categoria selezionata
+            }
+        }
+        // Se nessun filtro è attivo, mostra tutte le foto (non filtrare per categoria)
+        
+        // Se mostra_tutte_foto è true, mostra anche quelle senza incongruenze
+        if self.mostra_tutte_foto {
+            if f.incongruenze.is_empty() {
+                return true; // Mostra anche quelle senza incongruenze
+            }
+            // Per quelle con incongruenze, applica il filtro sulla soglia
+            let gravita_secondi = (f.gravita_incongruenza as f64 * 86400.0) as i64;
+            gravita_secondi >= soglia_secondi
+        } else {
+            // Mostra solo quelle con incongruenze che superano la soglia
+            if f.incongruenze.is_empty() {
+                // Se solo_exif_mancante è true, mostra anche quelle con EXIF mancante senza incongruenze
+                if self.solo_exif_mancante {
+                    return true;
+                }
+                return false;
+            }
+            // Converti la gravità della foto in secondi per il confronto
+            let gravita_secondi = (f.gravita_incongruenza as f64 * 86400.0) as i64;
+            gravita_secondi >= soglia_secondi
+        }
+    }
+}
@@ -1,55 +1,23 @@
 use eframe::egui;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
-use chrono::Datelike;
-use crate::{FotoData, leggi_foto_da_directory};
-
-#[derive(Clone, PartialEq)]
-enum Strategia {
-    NomeFile,
-    JsonPhotoTaken, // photoTakenTime from JSON
-    JsonCreation,   // creationTime from JSON
-    ExifAttuale,
-    NomeFilePreferito,
-    JsonPreferito,  // Prefer photoTakenTime, otherwise creationTime
-}
-
-impl Strategia {
-    fn as_str(&self) -> &str {
-        match self {
-            Strategia::NomeFile => "nome_file",
-            Strategia::JsonPhotoTaken => "json_photo_taken",
-            Strategia::JsonCreation => "json_creation",
-            Strategia::ExifAttuale => "exif_attuale",
-            Strategia::NomeFilePreferito => "nome_file_preferito",
-            Strategia::JsonPreferito => "json_preferito",
-        }
-    }
-    
-    #[allow(dead_code)]
-    fn from_str(s: &str) -> Self {
-        match s {
-            "nome_file" => Strategia::NomeFile,
-            "json_photo_taken" => Strategia::JsonPhotoTaken,
-            "json_creation" => Strategia::JsonCreation,
-            "exif_attuale" => Strategia::ExifAttuale,
-            "nome_file_preferito" => Strategia::NomeFilePreferito,
-            "json_preferito" => Strategia::JsonPreferito,
-            _ => Strategia::NomeFilePreferito,
-        }
-    }
-    
-    fn display_name(&self) -> &str {
-        match self {
-            Strategia::NomeFile => "Use year from filename",
-            Strategia::JsonPhotoTaken => "Use photoTakenTime from JSON",
-            Strategia::JsonCreation => "Use creationTime from JSON",
-            Strategia::ExifAttuale => "Keep current EXIF",
-            Strategia::NomeFilePreferito => "Prefer filename, otherwise JSON",
-            Strategia::JsonPreferito => "Prefer JSON photoTakenTime, otherwise filename",
-        }
-    }
-}
+use chrono::{Datelike, NaiveDate};
+use crate::FotoData;
+use crate::cache_scansione::CacheScansione;
+use crate::cronologia::{FiltroPeriodo, Granularita, Istogramma, Secchio, Serie};
+use crate::date_manuali::{CampoData, DataManuale};
+use crate::errori_scrittura::ErroreScrittura;
+use crate::interpolazione::DataInterpolata;
+use crate::lavoro::{ControlloLavoro, StatoLavoro};
+use crate::scrittura::ScritturaSospesa;
+use crate::miniature::{CodaMiniature, LATO_ANTEPRIMA, LATO_MINIATURA};
+use crate::filtri::{FiltroFoto, FiltroIncongruenza, Strategia};
+use crate::impostazioni::{ColonnaOrdinamento, Impostazioni, ImpostazioniFiltri, ImpostazioniFinestra, UnitaGravita};
+use crate::organizza::{ModalitaOrganizza, PianoOrganizza};
+use crate::rinomina::PianoRinomina;
+use crate::scrittura::{EsitoScrittura, FusoDataFile, OpzioniScrittura, RichiestaScrittura};
+use crate::sessione::{FiltroRevisione, FotoSessione, Sessione, StatoRevisione};
+use crate::sidecar::{ModalitaOutput, NomeSidecar};
 
 pub struct CorrectorApp {
     directory: Option<PathBuf>,
@@ -74,6 +42,21 @@ pub struct CorrectorApp {
     errori_applicazione: usize,
     // Shared counters for progress (used by write thread)
     progresso_counter: Option<std::sync::Arc<std::sync::Mutex<(usize, usize)>>>,
+    scrittura_thread: Option<std::thread::JoinHandle<Vec<EsitoScrittura>>>,
+    richieste_in_corso: Vec<RichiestaScrittura>,
+    opzioni_in_corso: OpzioniScrittura,
+    journal_in_corso: Option<PathBuf>,
+    // Pause/stop of the running scan and write, checked between files
+    controllo_scansione: Option<ControlloLavoro>,
+    controllo_scrittura: Option<ControlloLavoro>,
+    messaggio_scansione: String,
+    scrittura_sospesa: Option<ScritturaSospesa>, // Apply stopped or interrupted, to resume
+    chiusura_in_corso: bool,                     // Window closed during a write: close when it stops
+    // Failed writes with the request that failed, until retried successfully or cleared
+    errori_scrittura: Vec<(ErroreScrittura, RichiestaScrittura)>,
+    errori_selezionati: std::collections::HashSet<PathBuf>,
+    mostra_errori: bool,
+    messaggio_errori: String,
     // Filter for incongruity severity
     soglia_gravita_giorni: f32,
     unita_gravita: UnitaGravita,
@@ -84,6 +67,8 @@ pub struct CorrectorApp {
     mostra_foto_1900: bool, // Foto senza metadati (1900-01-01)
     mostra_foto_whatsapp: bool, // Foto IMG_* da WhatsApp
     mostra_foto_raw: bool, // Foto RAW (ORF/NEF) con JPG associato
+    // Opzioni di scrittura: metadati JSON, sidecar .xmp, data del file
+    opzioni_scrittura: OpzioniScrittura,
     // Sorting
     colonna_ordinamento: Option<ColonnaOrdinamento>,
     ordine_crescente: bool,
@@ -91,84 +76,332 @@ pub struct CorrectorApp {
     foto_da_mostrare_cached: Vec<(usize, FotoData)>, // (indice_originale, foto)
     filtro_dirty: bool, // True when filters changed and cache needs refresh
     path_to_index: std::collections::HashMap<PathBuf, usize>, // Fast lookup map
+    // Rename by template (preview before execution)
+    template_rinomina: String,
+    piano_rinomina: Option<PianoRinomina>,
+    messaggio_rinomina: String,
+    // Organize into a dated folder tree
+    dir_organizza: Option<PathBuf>,
+    template_organizza: String,
+    modalita_organizza: ModalitaOrganizza,
+    piano_organizza: Option<PianoOrganizza>,
+    organizza_thread: Option<std::thread::JoinHandle<Result<crate::organizza::EsitoOrganizza, String>>>,
+    organizza_progresso: Option<(std::sync::Arc<std::sync::atomic::AtomicUsize>, usize)>, // (completed, total)
+    messaggio_organizza: String,
+    salta_duplicati_organizza: bool,
+    // Duplicate detection
+    cerca_percettivo: bool, // Also group re-encoded copies (perceptual hash)
+    uniforma_date_duplicati: bool, // Same proposal for every copy in a group
+    solo_duplicati: bool, // Filter: show only photos with copies, grouped
+    duplicati_thread: Option<std::thread::JoinHandle<Vec<Vec<PathBuf>>>>,
+    duplicati_progresso: Option<(std::sync::Arc<std::sync::atomic::AtomicUsize>, usize)>, // (examined, total)
+    copie_duplicati: std::collections::HashMap<usize, Vec<PathBuf>>, // group -> members, kept copy first
+    messaggio_duplicati: String,
+    // Takeout archives read without extracting them
+    archivi: Vec<PathBuf>,
+    dir_estrazione: Option<PathBuf>,
+    estrazione_thread: Option<std::thread::JoinHandle<crate::archivio::EsitoEstrazione>>,
+    estrazione_progresso: Option<(std::sync::Arc<std::sync::atomic::AtomicUsize>, usize)>, // (extracted, total)
+    messaggio_estrazione: String,
+    // Manual dates imported from CSV/JSON
+    messaggio_override: String,
+    override_non_trovate: Vec<String>,
+    // Manual dates typed in the table or set for the selection
+    modifica_cella: Option<ModificaCella>,
+    testo_data_selezione: String,
+    giorno_selezione: NaiveDate,
+    campo_selezione: CampoData,
+    messaggio_data_selezione: String,
+    // Bulk time shift for a wrong camera clock
+    modo_scostamento: ModoScostamento,
+    testo_scostamento: String,
+    fotocamera_scostamento: Option<String>, // None = selected photos
+    riferimento_scostamento: Option<usize>,
+    ora_vera_riferimento: String,
+    messaggio_scostamento: String,
+    // Camera clock offsets detected from EXIF vs JSON
+    gruppi_orologio: Vec<crate::orologio::GruppoOrologio>,
+    messaggio_orologio: String,
+    // Thumbnail column and preview pane
+    mostra_miniature: bool,
+    mostra_anteprima: bool,
+    foto_anteprima: Option<usize>,
+    json_anteprima: Option<(PathBuf, String)>, // JSON of the previewed photo, pretty-printed
+    miniature: TextureMiniature,
+    // Timeline histogram
+    mostra_cronologia: bool,
+    granularita_cronologia: Granularita,
+    istogramma: Option<std::sync::Arc<Istogramma>>, // Shared with each frame instead of cloned
+    filtro_periodo: Option<FiltroPeriodo>, // Bar clicked: the table shows only that period
+    // Scan cache: reopening a library re-reads only the changed photos
+    usa_cache_scansione: bool,
+    dir_cache_scansione: Option<PathBuf>, // None = $XDG_CACHE_HOME/corrigi-exif
+    rileggi_tutto: bool,                  // Next scan ignores the cache
+    // Backups before writing: retention for the cleanup button and its outcome
+    giorni_backup: u64,
+    messaggio_backup: String,
+    // Settings kept between launches: saved shortly after they change and on close
+    cartelle_recenti: Vec<Vec<PathBuf>>,
+    finestra: ImpostazioniFinestra, // Size and position of the window when not maximized
+    impostazioni_salvate: Impostazioni,
+    controllo_impostazioni: std::time::Instant,
+    // Review session: status of each photo by path, the open session file and the one being reopened
+    stato_revisione: std::collections::HashMap<PathBuf, StatoRevisione>,
+    filtro_revisione: FiltroRevisione,
+    file_sessione: Option<PathBuf>,     // Saved again by "Save Session" and when the window closes
+    sessione_in_apertura: Option<Sessione>, // Applied when the scan of its folders ends
+    messaggio_sessione: String,
+    file_cambiati_sessione: Vec<PathBuf>,
+    file_spariti_sessione: Vec<PathBuf>,
 }
 
 #[derive(Clone, Copy, PartialEq)]
-#[allow(dead_code)]
-enum ColonnaOrdinamento {
-    NomeFile,
-    Gravita,
-    Incongruenze,
-    DateTimeOriginal,
-    CreateDate,
+enum ModoScostamento {
+    Durata,      // Typed offset (+1h, -1y)
+    Riferimento, // Offset from a photo whose true time is known
 }
 
-#[derive(Clone, Copy, PartialEq)]
-enum UnitaGravita {
-    Secondi,
-    Minuti,
-    Ore,
-    Giorni,
-    Mesi,
-    Anni,
+// Proposal cell being edited in the table
+struct ModificaCella {
+    idx: usize,
+    campo: CampoData,
+    testo: String,
+    giorno: NaiveDate, // Date picker value
+    errore: Option<String>,
+    focus_dato: bool, // Focus the text field only when editing starts
 }
 
-#[derive(Clone, Copy, PartialEq)]
-enum FiltroIncongruenza {
-    Tutte,
-    SoloExifMancante,
-    ExifAnnoDiversoFilename,
-    ExifDiversoJson,
+// Thumbnail textures, loaded in the background by miniature::CodaMiniature
+struct TextureMiniature {
+    coda: CodaMiniature,
+    stati: std::collections::HashMap<(PathBuf, u32), StatoMiniatura>,
+    byte: usize,     // Memory used by the ready textures
+    contatore: u64,  // Grows at every use, to drop the least recently used
+}
+
+enum StatoMiniatura {
+    InCoda,
+    Pronta { texture: egui::TextureHandle, byte: usize, ultimo_uso: u64 },
+    Errore(String),
 }
 
-impl FiltroIncongruenza {
-    fn display_name(&self) -> &str {
-        match self {
-            FiltroIncongruenza::Tutte => "Tutte le incongruenze",
-            FiltroIncongruenza::SoloExifMancante => "Solo EXIF mancante",
-            FiltroIncongruenza::ExifAnnoDiversoFilename => "EXIF anno ≠ filename",
-            FiltroIncongruenza::ExifDiversoJson => "EXIF ≠ JSON photoTakenTime",
+// Textures kept in memory (about 700 thumbnails or 60 previews per 256 MB)
+const MAX_BYTE_TEXTURE: usize = 256 * 1024 * 1024;
+// Pending requests: older ones (rows scrolled past) are dropped
+const MAX_RICHIESTE_MINIATURE: usize = 200;
+
+impl TextureMiniature {
+    fn new() -> Self {
+        Self {
+            coda: CodaMiniature::new(num_cpus::get().clamp(1, 4)),
+            stati: std::collections::HashMap::new(),
+            byte: 0,
+            contatore: 0,
         }
     }
     
-    fn matches(&self, foto: &crate::FotoData) -> bool {
-        match self {
-            FiltroIncongruenza::Tutte => !foto.incongruenze.is_empty(),
-            FiltroIncongruenza::SoloExifMancante => {
-                foto.incongruenze.iter().any(|inc| inc.contains("EXIF DateTimeOriginal mancante"))
+    // Texture if ready (Ok(None) while loading), requesting it the first time
+    fn texture(&mut self, path: &Path, lato: u32) -> Result<Option<egui::TextureHandle>, String> {
+        self.contatore += 1;
+        let chiave = (path.to_path_buf(), lato);
+        match self.stati.get_mut(&chiave) {
+            Some(StatoMiniatura::Pronta { texture, ultimo_uso, .. }) => {
+                *ultimo_uso = self.contatore;
+                Ok(Some(texture.clone()))
             }
-            FiltroIncongruenza::ExifAnnoDiversoFilename => {
-                foto.incongruenze.iter().any(|inc| inc.contains("EXIF anno") && inc.contains("filename anno"))
+            Some(StatoMiniatura::InCoda) => Ok(None),
+            Some(StatoMiniatura::Errore(e)) => Err(e.clone()),
+            None => {
+                self.coda.richiedi(chiave.0.clone(), lato);
+                self.stati.insert(chiave, StatoMiniatura::InCoda);
+                Ok(None)
             }
-            FiltroIncongruenza::ExifDiversoJson => {
-                foto.incongruenze.iter().any(|inc| inc.contains("EXIF") && inc.contains("JSON photoTakenTime"))
+        }
+    }
+    
+    // Turns finished thumbnails into textures; true while some are still loading
+    fn aggiorna(&mut self, ctx: &egui::Context) -> bool {
+        for chiave in self.coda.sfoltisci(MAX_RICHIESTE_MINIATURE) {
+            self.stati.remove(&chiave);
+        }
+        for (path, lato, esito) in self.coda.ricevi() {
+            let stato = match esito {
+                Ok(miniatura) => {
+                    let immagine = egui::ColorImage::from_rgba_unmultiplied(
+                        [miniatura.larghezza as usize, miniatura.altezza as usize], &miniatura.rgba);
+                    let texture = ctx.load_texture(path.to_string_lossy(), immagine, egui::TextureOptions::LINEAR);
+                    self.byte += miniatura.rgba.len();
+                    StatoMiniatura::Pronta { texture, byte: miniatura.rgba.len(), ultimo_uso: self.contatore }
+                }
+                Err(e) => StatoMiniatura::Errore(e),
+            };
+            self.stati.insert((path, lato), stato);
+        }
+        
+        while self.byte > MAX_BYTE_TEXTURE {
+            let meno_usata = self.stati.iter()
+                .filter_map(|(chiave, stato)| match stato {
+                    StatoMiniatura::Pronta { ultimo_uso, .. } => Some((*ultimo_uso, chiave.clone())),
+                    _ => None,
+                })
+                .min();
+            let Some((_, chiave)) = meno_usata else {
+                break;
+            };
+            if let Some(StatoMiniatura::Pronta { byte, .. }) = self.stati.remove(&chiave) {
+                self.byte -= byte;
             }
         }
+        
+        self.stati.values().any(|s| matches!(s, StatoMiniatura::InCoda))
+    }
+}
+
+// Legge le cartelle una dopo l'altra con la stessa cache, poi la salva
+// (anche se la scansione è stata annullata: le foto lette restano utili)
+fn leggi_directory_con_cache(dirs: &[PathBuf], sender: Option<std::sync::mpsc::Sender<usize>>, mut cache: Option<CacheScansione>,
+                             controllo: Option<&ControlloLavoro>) -> Vec<FotoData> {
+    let mut tutte_foto = Vec::new();
+    for dir in dirs {
+        if controllo.is_some_and(|c| c.is_annullato()) {
+            break;
+        }
+        eprintln!("[DEBUG] Inizio caricamento foto da: {:?}", dir);
+        tutte_foto.extend(crate::leggi_foto_da_directory_con_thread(dir, sender.clone(), None, cache.as_mut(), controllo));
+    }
+    if let Some(Err(e)) = cache.as_mut().map(|c| c.salva()) {
+        eprintln!("[DEBUG] {}", e);
     }
+    tutte_foto
+}
+
+// Pause/Resume and Stop for a running job; a stop waits for the files in progress
+fn pulsanti_lavoro(ui: &mut egui::Ui, controllo: &ControlloLavoro) {
+    ui.horizontal(|ui| {
+        match controllo.stato() {
+            StatoLavoro::InCorso => {
+                if ui.button("⏸ Pause").clicked() {
+                    controllo.pausa();
+                }
+            }
+            StatoLavoro::InPausa => {
+                if ui.button("▶ Resume").clicked() {
+                    controllo.riprendi();
+                }
+            }
+            StatoLavoro::Annullato => {
+                ui.label("Stopping after the files in progress...");
+                return;
+            }
+        }
+        if ui.button("⏹ Stop").clicked() {
+            controllo.annulla();
+        }
+    });
 }
 
-impl UnitaGravita {
-    fn display_name(&self) -> &str {
-        match self {
-            UnitaGravita::Secondi => "Seconds",
-            UnitaGravita::Minuti => "Minutes",
-            UnitaGravita::Ore => "Hours",
-            UnitaGravita::Giorni => "Days",
-            UnitaGravita::Mesi => "Months",
-            UnitaGravita::Anni => "Years",
+// Thumbnail cell: the image fitted in the row, a placeholder while it loads
+fn cella_miniatura(ui: &mut egui::Ui, miniature: &mut TextureMiniature, foto: &FotoData) -> egui::Response {
+    let riquadro = egui::vec2(72.0, 56.0);
+    if foto.origine_archivio.is_some() {
+        return ui.add_sized(riquadro, egui::Label::new("📦").sense(egui::Sense::click()))
+            .on_hover_text("Inside a Takeout archive: extract it to see the photo");
+    }
+    match miniature.texture(&foto.path, LATO_MINIATURA) {
+        Ok(Some(texture)) => {
+            let dimensione = texture.size_vec2();
+            let scala = (riquadro.x / dimensione.x).min(riquadro.y / dimensione.y);
+            ui.add_sized(riquadro, egui::Image::new((texture.id(), dimensione * scala)).sense(egui::Sense::click()))
         }
+        Ok(None) => ui.add_sized(riquadro, egui::Label::new("…").sense(egui::Sense::click())),
+        Err(e) => ui.add_sized(riquadro, egui::Label::new("🚫").sense(egui::Sense::click())).on_hover_text(e),
+    }
+}
+
+enum AzioneCella {
+    Nessuna,
+    Modifica,
+    Conferma,
+    Annulla,
+    Cancella, // Remove the manual date, back to the strategy
+}
+
+// Proposal cell: colored proposal, click to edit. While editing: text with partial dates
+// ("1987-07"), date picker, confirm/cancel
+fn cella_proposta(ui: &mut egui::Ui, proposta: Option<chrono::DateTime<chrono::Utc>>, attuale: Option<chrono::DateTime<chrono::Utc>>,
+                  manuale: Option<&DataManuale>, interpolata: Option<&DataInterpolata>,
+                  modifica: Option<&mut ModificaCella>, id: &str) -> AzioneCella {
+    let mut azione = AzioneCella::Nessuna;
+    
+    if let Some(modifica) = modifica {
+        ui.horizontal(|ui| {
+            let testo = ui.add(egui::TextEdit::singleline(&mut modifica.testo)
+                .desired_width(130.0)
+                .hint_text("1987-07-14 15:30"));
+            if !modifica.focus_dato {
+                testo.request_focus();
+                modifica.focus_dato = true;
+            }
+            if testo.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                azione = AzioneCella::Conferma;
+            }
+            if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                azione = AzioneCella::Annulla;
+            }
+            if ui.add(egui_extras::DatePickerButton::new(&mut modifica.giorno).id_source(id).calendar_week(false)).changed() {
+                // Keep the time already typed, if any
+                let orario = modifica.testo.get(10..).unwrap_or("").to_string();
+                modifica.testo = format!("{}{}", modifica.giorno.format("%Y-%m-%d"), orario);
+            }
+            if ui.small_button("✔").clicked() {
+                azione = AzioneCella::Conferma;
+            }
+            if ui.small_button("✖").clicked() {
+                azione = AzioneCella::Annulla;
+            }
+            if manuale.is_some() && ui.small_button("⟲").on_hover_text("Remove the manual date").clicked() {
+                azione = AzioneCella::Cancella;
+            }
+            if let Some(ref errore) = modifica.errore {
+                ui.colored_label(egui::Color32::from_rgb(255, 80, 80), "⚠").on_hover_text(errore);
+            }
+        });
+        return azione;
     }
     
-    fn to_giorni(&self, valore: f32) -> f32 {
-        match self {
-            UnitaGravita::Secondi => valore / 86400.0,
-            UnitaGravita::Minuti => valore / 1440.0,
-            UnitaGravita::Ore => valore / 24.0,
-            UnitaGravita::Giorni => valore,
-            UnitaGravita::Mesi => valore * 30.0,
-            UnitaGravita::Anni => valore * 365.0,
+    let Some(dt_proposta) = proposta else {
+        if ui.selectable_label(false, "-").on_hover_text("Click to enter a date").clicked() {
+            azione = AzioneCella::Modifica;
         }
+        return azione;
+    };
+    
+    let (testo_proposta, colore) = if let Some(manuale) = manuale {
+        // Manual date: shown at the precision it was entered with
+        (format!("✍ {}", manuale.precisione.formatta(manuale.data)), egui::Color32::from_rgb(100, 200, 255)) // Light blue
+    } else if interpolata.is_some() {
+        // Date inferred from the neighbors: shown with ≈, to be checked
+        (format!("≈ {}", dt_proposta.format("%Y-%m-%d %H:%M:%S")), egui::Color32::from_rgb(230, 200, 80)) // Yellow
+    } else if dt_proposta.year() == 1900 && dt_proposta.month() == 1 && dt_proposta.day() == 1 {
+        // Flag date 1900-01-01 for photos without metadata, to classify manually
+        (format!("→ {}", dt_proposta.format("%Y-%m-%d %H:%M:%S")), egui::Color32::from_rgb(255, 0, 255)) // Magenta
+    } else {
+        // Orange if it changes the current EXIF (or the EXIF is missing), gray otherwise
+        let cambia = attuale.map(|dt_exif| dt_exif != dt_proposta).unwrap_or(true);
+        let colore = if cambia { egui::Color32::from_rgb(255, 165, 0) } else { egui::Color32::from_rgb(150, 150, 150) };
+        (format!("→ {}", dt_proposta.format("%Y-%m-%d %H:%M:%S")), colore)
+    };
+    
+    let suggerimento = match (manuale, interpolata) {
+        (Some(manuale), _) => format!("Manual date ({}), click to edit", manuale.origine),
+        (None, Some(interpolata)) => format!("Interpolated date, {:.0}% confidence: {}\nClick to enter a date",
+                                             interpolata.confidenza * 100.0, interpolata.origine),
+        (None, None) => "Click to enter a date".to_string(),
+    };
+    if ui.selectable_label(false, egui::RichText::new(testo_proposta).color(colore)).on_hover_text(suggerimento).clicked() {
+        azione = AzioneCella::Modifica;
     }
+    azione
 }
 
 impl CorrectorApp {
@@ -199,7 +432,12 @@ impl CorrectorApp {
         
         cc.egui_ctx.set_style(style);
         
-        let ultima_cartella = Self::carica_ultima_cartella();
+        let impostazioni = Impostazioni::carica();
+        // The last folder only if it still exists, in canonical form for the dialog
+        let ultima_cartella = impostazioni.ultima_cartella.clone()
+            .filter(|p| p.is_dir())
+            .map(|p| p.canonicalize().unwrap_or(p));
+        let filtri = impostazioni.filtri.clone();
         Self {
             directory: None,
             directories: Vec::new(),
@@ -207,8 +445,8 @@ impl CorrectorApp {
             foto_list: Vec::new(),
             foto_selezionate: std::collections::HashSet::new(),
             ultimo_indice_selezionato: None,
-            strategia_datetime_original: Strategia::JsonPreferito, // JSON se disponibile, altrimenti filename
-            strategia_create_date: Strategia::JsonPreferito, // JSON se disponibile, altrimenti filename
+            strategia_datetime_original: impostazioni.strategia_datetime_original,
+            strategia_create_date: impostazioni.strategia_create_date,
             loading: false,
             loading_message: String::new(),
             loading_progress: None,
@@ -221,67 +459,197 @@ impl CorrectorApp {
             foto_modificate: 0,
             errori_applicazione: 0,
             progresso_counter: None,
-            soglia_gravita_giorni: 0.0,
-            unita_gravita: UnitaGravita::Giorni,
-            mostra_tutte_foto: false,
-            solo_exif_mancante: true, // Default: mostra solo EXIF mancante
-            filtro_incongruenza: FiltroIncongruenza::Tutte,
-            mostra_foto_1900: false, // Default: non filtrare per categoria
-            mostra_foto_whatsapp: false, // Default: non filtrare per categoria
-            mostra_foto_raw: false, // Default: non filtrare per categoria
-            colonna_ordinamento: None,
-            ordine_crescente: true,
+            scrittura_thread: None,
+            richieste_in_corso: Vec::new(),
+            opzioni_in_corso: OpzioniScrittura::default(),
+            journal_in_corso: None,
+            controllo_scansione: None,
+            controllo_scrittura: None,
+            messaggio_scansione: String::new(),
+            scrittura_sospesa: ScritturaSospesa::carica().filter(|s| !s.richieste.is_empty()),
+            chiusura_in_corso: false,
+            errori_scrittura: Vec::new(),
+            errori_selezionati: std::collections::HashSet::new(),
+            mostra_errori: false,
+            messaggio_errori: String::new(),
+            soglia_gravita_giorni: filtri.soglia_gravita_giorni,
+            unita_gravita: filtri.unita_gravita,
+            mostra_tutte_foto: filtri.mostra_tutte_foto,
+            solo_exif_mancante: filtri.solo_exif_mancante,
+            filtro_incongruenza: filtri.filtro_incongruenza,
+            mostra_foto_1900: filtri.mostra_foto_1900,
+            mostra_foto_whatsapp: filtri.mostra_foto_whatsapp,
+            mostra_foto_raw: filtri.mostra_foto_raw,
+            opzioni_scrittura: impostazioni.opzioni_scrittura.clone(),
+            colonna_ordinamento: impostazioni.colonna_ordinamento,
+            ordine_crescente: impostazioni.ordine_crescente,
             foto_da_mostrare_cached: Vec::new(),
             filtro_dirty: true,
             path_to_index: std::collections::HashMap::new(),
+            template_rinomina: crate::rinomina::TEMPLATE_PREDEFINITO.to_string(),
+            piano_rinomina: None,
+            messaggio_rinomina: String::new(),
+            dir_organizza: None,
+            template_organizza: crate::organizza::TEMPLATE_CARTELLE_PREDEFINITO.to_string(),
+            modalita_organizza: ModalitaOrganizza::Copia,
+            piano_organizza: None,
+            organizza_thread: None,
+            organizza_progresso: None,
+            messaggio_organizza: String::new(),
+            salta_duplicati_organizza: false,
+            cerca_percettivo: false,
+            uniforma_date_duplicati: true,
+            solo_duplicati: filtri.solo_duplicati,
+            duplicati_thread: None,
+            duplicati_progresso: None,
+            copie_duplicati: std::collections::HashMap::new(),
+            messaggio_duplicati: String::new(),
+            archivi: Vec::new(),
+            dir_estrazione: None,
+            estrazione_thread: None,
+            estrazione_progresso: None,
+            messaggio_estrazione: String::new(),
+            messaggio_override: String::new(),
+            override_non_trovate: Vec::new(),
+            modifica_cella: None,
+            testo_data_selezione: String::new(),
+            giorno_selezione: chrono::Local::now().date_naive(),
+            campo_selezione: CampoData::Entrambi,
+            messaggio_data_selezione: String::new(),
+            modo_scostamento: ModoScostamento::Durata,
+            testo_scostamento: String::new(),
+            fotocamera_scostamento: None,
+            riferimento_scostamento: None,
+            ora_vera_riferimento: String::new(),
+            messaggio_scostamento: String::new(),
+            gruppi_orologio: Vec::new(),
+            messaggio_orologio: String::new(),
+            mostra_miniature: impostazioni.finestra.mostra_miniature,
+            mostra_anteprima: impostazioni.finestra.mostra_anteprima,
+            foto_anteprima: None,
+            json_anteprima: None,
+            miniature: TextureMiniature::new(),
+            mostra_cronologia: impostazioni.finestra.mostra_cronologia,
+            granularita_cronologia: impostazioni.finestra.granularita_cronologia,
+            istogramma: None,
+            filtro_periodo: None,
+            usa_cache_scansione: impostazioni.usa_cache_scansione,
+            dir_cache_scansione: impostazioni.dir_cache_scansione.clone(),
+            rileggi_tutto: false,
+            giorni_backup: impostazioni.giorni_backup,
+            messaggio_backup: String::new(),
+            cartelle_recenti: impostazioni.cartelle_recenti.clone(),
+            finestra: impostazioni.finestra.clone(),
+            impostazioni_salvate: impostazioni,
+            controllo_impostazioni: std::time::Instant::now(),
+            stato_revisione: std::collections::HashMap::new(),
+            filtro_revisione: FiltroRevisione::Tutte,
+            file_sessione: None,
+            sessione_in_apertura: None,
+            messaggio_sessione: String::new(),
+            file_cambiati_sessione: Vec::new(),
+            file_spariti_sessione: Vec::new(),
         }
     }
     
-    fn percorso_config() -> Option<PathBuf> {
-        std::env::var("HOME")
-            .ok()
-            .map(|home| PathBuf::from(home).join(".corrigi-exif-config.json"))
+    fn impostazioni_correnti(&self) -> Impostazioni {
+        Impostazioni {
+            ultima_cartella: self.ultima_cartella.clone(),
+            cartelle_recenti: self.cartelle_recenti.clone(),
+            strategia_datetime_original: self.strategia_datetime_original,
+            strategia_create_date: self.strategia_create_date,
+            filtri: ImpostazioniFiltri {
+                soglia_gravita_giorni: self.soglia_gravita_giorni,
+                unita_gravita: self.unita_gravita,
+                mostra_tutte_foto: self.mostra_tutte_foto,
+                solo_exif_mancante: self.solo_exif_mancante,
+                filtro_incongruenza: self.filtro_incongruenza,
+                mostra_foto_1900: self.mostra_foto_1900,
+                mostra_foto_whatsapp: self.mostra_foto_whatsapp,
+                mostra_foto_raw: self.mostra_foto_raw,
+                solo_duplicati: self.solo_duplicati,
+            },
+            colonna_ordinamento: self.colonna_ordinamento,
+            ordine_crescente: self.ordine_crescente,
+            finestra: ImpostazioniFinestra {
+                mostra_miniature: self.mostra_miniature,
+                mostra_anteprima: self.mostra_anteprima,
+                mostra_cronologia: self.mostra_cronologia,
+                granularita_cronologia: self.granularita_cronologia,
+                ..self.finestra.clone()
+            },
+            opzioni_scrittura: self.opzioni_scrittura.clone(),
+            usa_cache_scansione: self.usa_cache_scansione,
+            dir_cache_scansione: self.dir_cache_scansione.clone(),
+            giorni_backup: self.giorni_backup,
+        }
     }
     
-    fn carica_ultima_cartella() -> Option<PathBuf> {
-        let config_path = Self::percorso_config()?;
-        let content = fs::read_to_string(&config_path).ok()?;
-        let config: serde_json::Value = serde_json::from_str(&content).ok()?;
-        let path_str = config.get("ultima_cartella")?.as_str()?;
-        let path = PathBuf::from(path_str);
-        // Verifica che la cartella esista ancora e prova a renderla canonica
-        if path.exists() && path.is_dir() {
-            // Prova a rendere il percorso canonico (assoluto)
-            path.canonicalize().ok().or(Some(path))
-        } else {
-            None
+    // Writes the settings if they changed; unless forced at most once per second,
+    // so dragging a slider or resizing the window doesn't write the file at every frame
+    fn salva_impostazioni(&mut self, forza: bool) {
+        if !forza && self.controllo_impostazioni.elapsed() < std::time::Duration::from_secs(1) {
+            return;
         }
+        self.controllo_impostazioni = std::time::Instant::now();
+        let attuali = self.impostazioni_correnti();
+        if attuali == self.impostazioni_salvate {
+            return;
+        }
+        match attuali.salva() {
+            Ok(()) => eprintln!("[DEBUG] Impostazioni salvate"),
+            Err(e) => eprintln!("{}", e),
+        }
+        // Even after an error: retrying at every frame would only repeat it
+        self.impostazioni_salvate = attuali;
     }
     
-    fn salva_ultima_cartella(path: &PathBuf) {
-        if let Some(config_path) = Self::percorso_config() {
-            // Assicurati che il percorso sia assoluto e canonico per il salvataggio
-            let path_to_save = if let Ok(canonical) = path.canonicalize() {
-                canonical
-            } else {
-                path.clone()
-            };
-            
-            let config = serde_json::json!({
-                "ultima_cartella": path_to_save.to_string_lossy().to_string()
-            });
-            if let Ok(json_str) = serde_json::to_string_pretty(&config) {
-                if let Err(e) = fs::write(&config_path, json_str) {
-                    eprintln!("Errore nel salvataggio della configurazione in {:?}: {}", config_path, e);
-                } else {
-                    eprintln!("Configurazione salvata in: {:?}", config_path);
-                }
-            }
-        } else {
-            eprintln!("Impossibile determinare il percorso HOME per salvare la configurazione");
+    // Size and position of the window, remembered only when it's not maximized
+    fn aggiorna_finestra(&mut self, ctx: &egui::Context) {
+        let (massimizzata, interno, esterno) = ctx.input(|i| {
+            let viewport = i.viewport();
+            (viewport.maximized, viewport.inner_rect, viewport.outer_rect)
+        });
+        if let Some(massimizzata) = massimizzata {
+            self.finestra.massimizzata = massimizzata;
+        }
+        if self.finestra.massimizzata {
+            return;
+        }
+        if let Some(interno) = interno {
+            self.finestra.larghezza = interno.width();
+            self.finestra.altezza = interno.height();
+        }
+        if let Some(esterno) = esterno {
+            self.finestra.posizione = Some((esterno.min.x, esterno.min.y));
         }
     }
     
+    // Folders just opened: first among the recent ones and starting point of the next dialog
+    fn registra_cartelle_recenti(&mut self, cartelle: &[PathBuf]) {
+        let cartelle: Vec<PathBuf> = cartelle.iter()
+            .map(|c| c.canonicalize().unwrap_or_else(|_| c.clone()))
+            .collect();
+        let mut impostazioni = self.impostazioni_correnti();
+        impostazioni.aggiungi_recenti(&cartelle);
+        self.cartelle_recenti = impostazioni.cartelle_recenti;
+        self.ultima_cartella = impostazioni.ultima_cartella;
+        self.salva_impostazioni(true);
+    }
+    
+    // Cache di scansione secondo le impostazioni; una rilettura completa richiesta vale per questa sola scansione
+    fn apri_cache_scansione(&mut self) -> Option<CacheScansione> {
+        let rileggi_tutto = std::mem::take(&mut self.rileggi_tutto);
+        if !self.usa_cache_scansione {
+            return None;
+        }
+        let mut cache = CacheScansione::carica_da(self.dir_cache_scansione.as_deref())?;
+        if rileggi_tutto {
+            cache.forza_rilettura();
+        }
+        Some(cache)
+    }
+    
     fn seleziona_cartella(&mut self) {
         // Su Linux, rfd potrebbe non supportare set_directory() correttamente con XDG Portal
         // Quindi cambiamo temporaneamente la directory di lavoro corrente
@@ -323,11 +691,12 @@ impl CorrectorApp {
         if let Some(paths) = dialog.clone().pick_folders() {
             if !paths.is_empty() {
                 eprintln!("Cartelle selezionate: {:?}", paths);
+                self.file_sessione = None;
+                self.archivi.clear();
                 self.directories = paths.clone();
                 // Usa la prima cartella come principale per compatibilità
                 self.directory = Some(paths[0].clone());
-                self.ultima_cartella = Some(paths[0].clone());
-                Self::salva_ultima_cartella(&paths[0]);
+                self.registra_cartelle_recenti(&paths);
                 // Carica foto da tutte le cartelle selezionate
                 self.avvia_caricamento_foto_multiplo();
             }
@@ -335,10 +704,11 @@ impl CorrectorApp {
             // Fallback: se pick_folders non è supportato, usa pick_folder
             if let Some(path) = dialog.pick_folder() {
                 eprintln!("Cartella selezionata: {:?}", path);
+                self.file_sessione = None;
+                self.archivi.clear();
                 self.directory = Some(path.clone());
                 self.directories = vec![path.clone()];
-                self.ultima_cartella = Some(path.clone());
-                Self::salva_ultima_cartella(&path);
+                self.registra_cartelle_recenti(std::slice::from_ref(&path));
                 self.avvia_caricamento_foto();
             }
         }
@@ -349,6 +719,169 @@ impl CorrectorApp {
         }
     }
     
+    // Reopens folders from the Recent menu; the ones no longer there are left out
+    fn riapri_cartelle(&mut self, cartelle: Vec<PathBuf>) {
+        let esistenti: Vec<PathBuf> = cartelle.into_iter().filter(|c| c.is_dir()).collect();
+        if esistenti.is_empty() {
+            self.messaggio_scansione = "⚠ The folders are no longer available".to_string();
+            return;
+        }
+        self.file_sessione = None;
+        self.archivi.clear();
+        self.directory = Some(esistenti[0].clone());
+        self.directories = esistenti.clone();
+        self.registra_cartelle_recenti(&esistenti);
+        self.avvia_caricamento_foto_multiplo();
+    }
+    
+    fn sessione_corrente(&self) -> Sessione {
+        let foto = self.foto_list.iter()
+            .enumerate()
+            .map(|(idx, f)| FotoSessione::da_foto(f, self.foto_selezionate.contains(&idx), self.stato_revisione.get(&f.path).copied()))
+            .collect();
+        let impostazioni = self.impostazioni_correnti();
+        let mut sessione = Sessione::new(self.directories.clone(), self.archivi.clone(), foto);
+        sessione.strategia_datetime_original = self.strategia_datetime_original;
+        sessione.strategia_create_date = self.strategia_create_date;
+        sessione.filtri = impostazioni.filtri;
+        sessione.filtro_revisione = self.filtro_revisione;
+        sessione.colonna_ordinamento = self.colonna_ordinamento;
+        sessione.ordine_crescente = self.ordine_crescente;
+        sessione
+    }
+    
+    // Saves to the open session file, or asks for one (always with `chiedi`)
+    fn salva_sessione(&mut self, chiedi: bool) {
+        if self.foto_list.is_empty() {
+            self.messaggio_sessione = "No photos loaded: nothing to save".to_string();
+            return;
+        }
+        let file = match self.file_sessione.clone().filter(|_| !chiedi) {
+            Some(file) => file,
+            None => {
+                let mut dialog = rfd::FileDialog::new()
+                    .add_filter("Review session", &["json"])
+                    .set_file_name("review-session.json");
+                if let Some(ref cartella) = self.ultima_cartella {
+                    dialog = dialog.set_directory(cartella);
+                }
+                let Some(file) = dialog.save_file() else {
+                    return;
+                };
+                file
+            }
+        };
+        let sessione = self.sessione_corrente();
+        match sessione.salva(&file) {
+            Ok(()) => {
+                self.messaggio_sessione = format!("💾 Session saved: {} photos, {} reviewed ({})",
+                    sessione.foto.len(), self.stato_revisione.len(), file.display());
+                self.file_sessione = Some(file);
+            }
+            Err(e) => self.messaggio_sessione = format!("⚠️ {}", e),
+        }
+    }
+    
+    // Reads a session and scans its folders again; the photos get their state back when the scan ends
+    fn apri_sessione(&mut self) {
+        let mut dialog = rfd::FileDialog::new().add_filter("Review session", &["json"]);
+        if let Some(ref cartella) = self.ultima_cartella {
+            dialog = dialog.set_directory(cartella);
+        }
+        let Some(file) = dialog.pick_file() else {
+            return;
+        };
+        let sessione = match Sessione::carica(&file) {
+            Ok(sessione) => sessione,
+            Err(e) => {
+                self.messaggio_sessione = format!("⚠️ {}", e);
+                return;
+            }
+        };
+        let cartelle: Vec<PathBuf> = sessione.cartelle.iter().filter(|c| c.is_dir()).cloned().collect();
+        let archivi: Vec<PathBuf> = sessione.archivi.iter().filter(|a| a.is_file()).cloned().collect();
+        if cartelle.is_empty() && archivi.is_empty() {
+            self.messaggio_sessione = "⚠️ None of the session's folders is available".to_string();
+            return;
+        }
+        
+        // View settings now, so the scan already shows the session's proposals and filters
+        self.strategia_datetime_original = sessione.strategia_datetime_original;
+        self.strategia_create_date = sessione.strategia_create_date;
+        let filtri = sessione.filtri.clone();
+        self.soglia_gravita_giorni = filtri.soglia_gravita_giorni;
+        self.unita_gravita = filtri.unita_gravita;
+        self.mostra_tutte_foto = filtri.mostra_tutte_foto;
+        self.solo_exif_mancante = filtri.solo_exif_mancante;
+        self.filtro_incongruenza = filtri.filtro_incongruenza;
+        self.mostra_foto_1900 = filtri.mostra_foto_1900;
+        self.mostra_foto_whatsapp = filtri.mostra_foto_whatsapp;
+        self.mostra_foto_raw = filtri.mostra_foto_raw;
+        self.solo_duplicati = filtri.solo_duplicati;
+        self.filtro_revisione = sessione.filtro_revisione;
+        self.colonna_ordinamento = sessione.colonna_ordinamento;
+        self.ordine_crescente = sessione.ordine_crescente;
+        self.filtro_periodo = None;
+        
+        self.messaggio_sessione = format!("Opening session saved on {}...", sessione.salvata.format("%Y-%m-%d %H:%M"));
+        self.file_cambiati_sessione.clear();
+        self.file_spariti_sessione.clear();
+        self.sessione_in_apertura = Some(sessione);
+        if archivi.is_empty() {
+            self.archivi.clear();
+            self.directory = Some(cartelle[0].clone());
+            self.directories = cartelle.clone();
+            self.registra_cartelle_recenti(&cartelle);
+            self.avvia_caricamento_foto_multiplo();
+        } else {
+            self.directory = None;
+            self.directories.clear();
+            self.archivi = archivi;
+            self.avvia_caricamento_archivi();
+        }
+        self.file_sessione = Some(file);
+    }
+    
+    fn applica_sessione(&mut self, sessione: &Sessione) {
+        let esito = sessione.ripristina(&mut self.foto_list);
+        if self.uniforma_date_duplicati {
+            crate::duplicati::uniforma_proposte(&mut self.foto_list);
+        }
+        self.foto_selezionate = esito.selezionate;
+        self.ultimo_indice_selezionato = None;
+        self.stato_revisione = esito.stati;
+        let mut messaggio = format!("📂 Session of {}: {} photos found, {} reviewed",
+            sessione.salvata.format("%Y-%m-%d %H:%M"), esito.ritrovate, self.stato_revisione.len());
+        if !esito.cambiate.is_empty() {
+            messaggio.push_str(&format!(", {} changed since", esito.cambiate.len()));
+        }
+        if !esito.sparite.is_empty() {
+            messaggio.push_str(&format!(", {} missing", esito.sparite.len()));
+        }
+        if esito.nuove > 0 {
+            messaggio.push_str(&format!(", {} new", esito.nuove));
+        }
+        self.messaggio_sessione = messaggio;
+        self.file_cambiati_sessione = esito.cambiate;
+        self.file_spariti_sessione = esito.sparite;
+        self.filtro_dirty = true;
+    }
+    
+    fn imposta_stato_revisione(&mut self, indici: &[usize], stato: Option<StatoRevisione>) {
+        for &idx in indici {
+            let Some(foto) = self.foto_list.get(idx) else {
+                continue;
+            };
+            match stato {
+                Some(stato) => self.stato_revisione.insert(foto.path.clone(), stato),
+                None => self.stato_revisione.remove(&foto.path),
+            };
+        }
+        if self.filtro_revisione != FiltroRevisione::Tutte {
+            self.filtro_dirty = true;
+        }
+    }
+    
     fn avvia_caricamento_foto(&mut self) {
         if let Some(ref dir) = self.directory {
             self.loading = true;
@@ -361,15 +894,137 @@ impl CorrectorApp {
             
             // Avvia il caricamento in un thread separato
             let dir_clone = dir.clone();
+            let cache = self.apri_cache_scansione();
+            let controllo = ControlloLavoro::new();
+            self.controllo_scansione = Some(controllo.clone());
+            self.messaggio_scansione.clear();
             let handle = std::thread::spawn(move || {
-                eprintln!("[DEBUG] Inizio caricamento foto da: {:?}", dir_clone);
-                crate::leggi_foto_da_directory_con_progresso(&dir_clone, Some(sender))
+                leggi_directory_con_cache(&[dir_clone], Some(sender), cache, Some(&controllo))
             });
             
             self.loading_thread = Some(handle);
         }
     }
     
+    fn seleziona_archivi(&mut self) {
+        let mut dialog = rfd::FileDialog::new()
+            .add_filter("Takeout archives", &["zip", "tgz", "gz", "tar"]);
+        if let Some(ref ultima_cartella) = self.ultima_cartella {
+            dialog = dialog.set_directory(ultima_cartella);
+        }
+        
+        if let Some(paths) = dialog.pick_files() {
+            let archivi: Vec<PathBuf> = paths.into_iter()
+                .filter(|p| crate::archivio::is_archivio(p))
+                .collect();
+            if archivi.is_empty() {
+                return;
+            }
+            eprintln!("Archivi selezionati: {:?}", archivi);
+            // Archives replace the loaded folders: nothing to reload from disk
+            self.file_sessione = None;
+            self.directory = None;
+            self.directories.clear();
+            self.archivi = archivi;
+            self.avvia_caricamento_archivi();
+        }
+    }
+    
+    fn avvia_caricamento_archivi(&mut self) {
+        self.loading = true;
+        self.loading_message = format!("Reading {} Takeout archives (MB)...", self.archivi.len());
+        self.loading_progress = None;
+        self.messaggio_estrazione.clear();
+        
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.loading_progress_receiver = Some(receiver);
+        
+        let archivi = self.archivi.clone();
+        self.controllo_scansione = None;
+        self.messaggio_scansione.clear();
+        self.loading_thread = Some(std::thread::spawn(move || {
+            match crate::archivio::leggi_foto_da_archivi(&archivi, Some(sender)) {
+                Ok(foto) => foto,
+                Err(e) => {
+                    eprintln!("Errore lettura archivi: {}", e);
+                    Vec::new()
+                }
+            }
+        }));
+    }
+    
+    fn avvia_estrazione(&mut self) {
+        let dir_output = match self.dir_estrazione {
+            Some(ref d) => d.clone(),
+            None => {
+                self.messaggio_estrazione = "Select an output folder first".to_string();
+                return;
+            }
+        };
+        let selezionate: Vec<&FotoData> = self.foto_list.iter()
+            .enumerate()
+            .filter(|(idx, f)| self.foto_selezionate.contains(idx) && f.origine_archivio.is_some())
+            .map(|(_, f)| f)
+            .collect();
+        let voci = crate::archivio::voci_da_estrarre(&selezionate);
+        if voci.is_empty() {
+            self.messaggio_estrazione = "No selected photo from archives".to_string();
+            return;
+        }
+        
+        let contatore = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        self.estrazione_progresso = Some((contatore.clone(), voci.len()));
+        self.messaggio_estrazione.clear();
+        self.estrazione_thread = Some(std::thread::spawn(move || {
+            crate::archivio::estrai_voci(&voci, &dir_output, Some(&contatore))
+        }));
+    }
+    
+    fn verifica_estrazione_completata(&mut self, ctx: &egui::Context) {
+        let finito = match self.estrazione_thread {
+            Some(ref handle) => handle.is_finished(),
+            None => return,
+        };
+        if !finito {
+            ctx.request_repaint();
+            return;
+        }
+        
+        if let Some(handle) = self.estrazione_thread.take() {
+            self.estrazione_progresso = None;
+            match handle.join() {
+                Ok(esito) => {
+                    for errore in &esito.errori {
+                        eprintln!("Extract error: {}", errore);
+                    }
+                    // Extracted photos become normal files: the usual write path applies to them
+                    let destinazioni: std::collections::HashMap<_, _> = esito.estratte.iter()
+                        .map(|(voce, dest)| (voce, dest))
+                        .collect();
+                    for foto in self.foto_list.iter_mut() {
+                        let dest = foto.origine_archivio.as_ref().and_then(|o| destinazioni.get(&o.foto));
+                        if let Some(dest) = dest {
+                            if let Some(stato) = self.stato_revisione.remove(&foto.path) {
+                                self.stato_revisione.insert((*dest).clone(), stato);
+                            }
+                            foto.path = (*dest).clone();
+                            foto.origine_archivio = None;
+                        }
+                    }
+                    self.path_to_index.clear();
+                    for (idx, foto) in self.foto_list.iter().enumerate() {
+                        self.path_to_index.insert(foto.path.clone(), idx);
+                    }
+                    self.filtro_dirty = true;
+                    
+                    self.messaggio_estrazione = format!("✅ {} files extracted ({} errors)", esito.estratte.len(), esito.errori.len());
+                    self.avvia_applicazione_modifiche(ctx);
+                }
+                Err(e) => self.messaggio_estrazione = format!("⚠️ Extraction thread failed: {:?}", e),
+            }
+        }
+    }
+    
     fn avvia_caricamento_foto_multiplo(&mut self) {
         if !self.directories.is_empty() {
             self.loading = true;
@@ -386,14 +1041,12 @@ impl CorrectorApp {
             
             // Avvia il caricamento in un thread separato per tutte le cartelle
             let dirs_clone = self.directories.clone();
+            let cache = self.apri_cache_scansione();
+            let controllo = ControlloLavoro::new();
+            self.controllo_scansione = Some(controllo.clone());
+            self.messaggio_scansione.clear();
             let handle = std::thread::spawn(move || {
-                let mut tutte_foto = Vec::new();
-                for dir in dirs_clone {
-                    eprintln!("[DEBUG] Inizio caricamento foto da: {:?}", dir);
-                    let foto = crate::leggi_foto_da_directory_con_progresso(&dir, Some(sender.clone()));
-                    tutte_foto.extend(foto);
-                }
-                tutte_foto
+                leggi_directory_con_cache(&dirs_clone, Some(sender), cache, Some(&controllo))
             });
             
             self.loading_thread = Some(handle);
@@ -408,7 +1061,16 @@ impl CorrectorApp {
                     match handle.join() {
                     Ok(foto_list) => {
                         eprintln!("[DEBUG] Caricamento completato: {} foto", foto_list.len());
+                        // A stopped scan still shows what it read, flagged as partial
+                        if self.controllo_scansione.take().is_some_and(|c| c.is_annullato()) {
+                            self.messaggio_scansione = format!("⏹ Scan stopped: {} photos loaded, the list is partial", foto_list.len());
+                        }
                         self.foto_list = foto_list;
+                        self.foto_anteprima = None;
+                        self.json_anteprima = None;
+                        // Duplicate groups refer to the previous list
+                        self.copie_duplicati.clear();
+                        self.messaggio_duplicati.clear();
                         
                         // Ricostruisci la mappa path->indice per lookup veloce
                         eprintln!("[DEBUG] Costruzione mappa path->indice...");
@@ -422,6 +1084,9 @@ impl CorrectorApp {
                         eprintln!("[DEBUG] Calcolo proposte...");
                         self.calcola_proposte();
                         eprintln!("[DEBUG] Proposte calcolate");
+                        if let Some(sessione) = self.sessione_in_apertura.take() {
+                            self.applica_sessione(&sessione);
+                        }
                         
                         eprintln!("[DEBUG] Aggiornamento statistiche...");
                         self.aggiorna_statistiche();
@@ -436,6 +1101,8 @@ impl CorrectorApp {
                     }
                     Err(e) => {
                         eprintln!("[ERROR] Errore nel caricamento: {:?}", e);
+                        self.controllo_scansione = None;
+                        self.sessione_in_apertura = None;
                         self.loading = false;
                         self.loading_message = format!("Error loading photos: {:?}", e);
                     }
@@ -471,89 +1138,37 @@ impl CorrectorApp {
         }
     }
     
+    fn filtro_corrente(&self) -> FiltroFoto {
+        FiltroFoto {
+            soglia_gravita_giorni: self.soglia_gravita_giorni,
+            mostra_tutte_foto: self.mostra_tutte_foto,
+            solo_exif_mancante: self.solo_exif_mancante,
+            filtro_incongruenza: self.filtro_incongruenza,
+            mostra_foto_1900: self.mostra_foto_1900,
+            mostra_foto_whatsapp: self.mostra_foto_whatsapp,
+            mostra_foto_raw: self.mostra_foto_raw,
+            solo_duplicati: self.solo_duplicati,
+            periodo: self.filtro_periodo,
+        }
+    }
+    
     fn calcola_foto_da_mostrare(&mut self) {
+        if self.filtro_dirty {
+            self.istogramma = None; // Proposals or photos may have changed
+        }
         if !self.filtro_dirty && !self.foto_da_mostrare_cached.is_empty() {
             return; // Cache ancora valida
         }
         
-        let soglia_secondi = (self.soglia_gravita_giorni * 86400.0) as i64;
+        let filtro = self.filtro_corrente();
         
         // Filtra le foto mantenendo l'indice originale
         let mut foto_filtrate: Vec<(usize, &FotoData)> = self.foto_list
             .iter()
             .enumerate()
-            .filter(|(_, f)| {
-                // Filtro principale: solo EXIF mancante se abilitato
-                if self.solo_exif_mancante {
-                    // Mostra solo foto con DateTimeOriginal E CreateDate ENTRAMBI mancanti
-                    if f.exif_datetime_original.is_some() || f.exif_create_date.is_some() {
-                        return false; // Ha almeno uno dei campi EXIF, quindi escludi
-                    }
-                }
-                
-                // Filtro per tipo di incongruenza (solo se ci sono incongruenze)
-                if !f.incongruenze.is_empty() {
-                    if !self.filtro_incongruenza.matches(f) {
-                        return false;
-                    }
-                } else {
-                    // Se non ci sono incongruenze, mostra solo se:
-                    // - mostra_tutte_foto è true, OPPURE
-                    // - solo_exif_mancante è true (per mostrare quelle con EXIF mancante anche senza incongruenze)
-                    if !self.mostra_tutte_foto && !self.solo_exif_mancante {
-                        return false;
-                    }
-                    // Se solo_exif_mancante è true ma non ci sono incongruenze,
-                    // mostra solo se ha EXIF mancante (già controllato sopra)
-                    if self.solo_exif_mancante {
-                        return true; // Ha EXIF mancante (già verificato sopra)
-                    }
-                }
-                
-                // Filtro per categorie di foto
-                // Se almeno un checkbox è selezionato, mostra SOLO quelle categorie
-                let is_1900 = f.is_foto_1900();
-                let is_whatsapp = f.is_foto_whatsapp();
-                let is_raw = f.is_foto_raw();
-                
-                // Controlla se almeno un filtro categoria è attivo
-                let almeno_un_filtro_attivo = self.mostra_foto_1900 || self.mostra_foto_whatsapp || self.mostra_foto_raw;
-                
-                if almeno_un_filtro_attivo {
-                    // Se almeno un filtro è attivo, mostra SOLO le foto delle categorie selezionate
-                    let categoria_selezionata = 
-                        (is_1900 && self.mostra_foto_1900) ||
-                        (is_whatsapp && self.mostra_foto_whatsapp) ||
-                        (is_raw && self.mostra_foto_raw);
-                    
-                    if !categoria_selezionata {
-                        return false; // Nascondi se non appartiene a nessuna categoria selezionata
-                    }
-                }
-                // Se nessun filtro è attivo, mostra tutte le foto (non filtrare per categoria)
-                
-                // Se mostra_tutte_foto è true, mostra anche quelle senza incongruenze
-                if self.mostra_tutte_foto {
-                    if f.incongruenze.is_empty() {
-                        return true; // Mostra anche quelle senza incongruenze
-                    }
-                    // Per quelle con incongruenze, applica il filtro sulla soglia
-                    let gravita_secondi = (f.gravita_incongruenza as f64 * 86400.0) as i64;
-                    gravita_secondi >= soglia_secondi
-                } else {
-                    // Mostra solo quelle con incongruenze che superano la soglia
-                    if f.incongruenze.is_empty() {
-                        // Se solo_exif_mancante è true, mostra anche quelle con EXIF mancante senza incongruenze
-                        if self.solo_exif_mancante {
-                            return true;
-                        }
-                        return false;
-                    }
-                    // Converti la gravità della foto in secondi per il confronto
-                    let gravita_secondi = (f.gravita_incongruenza as f64 * 86400.0) as i64;
-                    gravita_secondi >= soglia_secondi
-                }
-            })
+            .filter(|(_, f)| filtro.accetta(f))
+            // A timeline bar shows all its photos, like for the other filters
+            .filter(|(_, f)| self.filtro_periodo.is_some() || self.filtro_revisione.accetta(self.stato_revisione.get(&f.path).copied()))
             .collect();
         
         // Applica ordinamento se selezionato
@@ -593,6 +1208,11 @@ impl CorrectorApp {
                     cmp.reverse()
                 }
             });
+        } else if self.solo_duplicati {
+            // Copie dello stesso gruppo una sotto l'altra
+            foto_filtrate.sort_by(|(_, a), (_, b)| {
+                a.gruppo_duplicati.cmp(&b.gruppo_duplicati).then_with(|| a.path.cmp(&b.path))
+            });
         }
         
         // Converti in owned data per la cache
@@ -604,151 +1224,1131 @@ impl CorrectorApp {
     }
     
     fn calcola_proposte(&mut self) {
+        crate::interpolazione::interpola_date(&mut self.foto_list);
         for foto in &mut self.foto_list {
-            foto.strategia_datetime_original = self.strategia_datetime_original.as_str().to_string();
-            foto.strategia_create_date = self.strategia_create_date.as_str().to_string();
-            
-            foto.proposta_datetime_original = crate::calcola_proposta_con_strategia(foto, &foto.strategia_datetime_original);
-            foto.proposta_create_date = crate::calcola_proposta_con_strategia(foto, &foto.strategia_create_date);
-            
-            // Recalculate incongruities and severity after calculating proposals
-            foto.incongruenze = crate::rileva_incongruenze(foto);
-            foto.gravita_incongruenza = crate::calcola_gravita_incongruenza(foto);
+            foto.applica_strategie(self.strategia_datetime_original.as_str(), self.strategia_create_date.as_str());
+        }
+        if self.uniforma_date_duplicati {
+            crate::duplicati::uniforma_proposte(&mut self.foto_list);
         }
         self.filtro_dirty = true; // Le proposte cambiate possono influenzare il filtro
     }
     
-    fn avvia_applicazione_modifiche(&mut self, _ctx: &egui::Context) {
-        // Apply modifications only to selected photos
-        let foto_da_modificare: Vec<_> = self.foto_list
-            .iter()
-            .enumerate()
-            .filter(|(idx, f)| {
-                // Must be selected AND have at least one proposal
-                self.foto_selezionate.contains(idx) && (
-                    f.proposta_datetime_original.is_some() ||
-                    f.proposta_create_date.is_some()
-                )
-            })
-            .map(|(_, f)| f.clone())
-            .collect();
-        
-        if foto_da_modificare.is_empty() {
-            return;
+    fn importa_date_manuali(&mut self) {
+        let mut dialog = rfd::FileDialog::new()
+            .add_filter("Date overrides", &["csv", "json", "jsonl"]);
+        if let Some(ref ultima_cartella) = self.ultima_cartella {
+            dialog = dialog.set_directory(ultima_cartella);
         }
+        let Some(file) = dialog.pick_file() else {
+            return;
+        };
         
-        self.foto_totali_da_modificare = foto_da_modificare.len();
-        self.foto_modificate = 0;
-        self.errori_applicazione = 0;
-        self.applicando_modifiche = true;
-        
-        // Prepara i dati per la scrittura parallela
-        let dati_scrittura: Vec<_> = foto_da_modificare.iter().map(|foto| {
-            let mut campi_da_scrivere = Vec::new();
-            
-            if let Some(data) = foto.proposta_datetime_original {
-                campi_da_scrivere.push(("DateTimeOriginal", data));
+        let esito = crate::date_manuali::leggi_file_override(&file)
+            .and_then(|righe| crate::date_manuali::applica_override(&mut self.foto_list, &righe));
+        match esito {
+            Ok(esito) => {
+                self.override_non_trovate = esito.non_trovate.iter()
+                    .map(|r| format!("{}: {}", r.origine, r.modello))
+                    .collect();
+                self.messaggio_override = format!("📅 {} photos with a manual date, {} rows not matched",
+                                                  esito.applicate, esito.non_trovate.len());
+                if self.uniforma_date_duplicati {
+                    crate::duplicati::uniforma_proposte(&mut self.foto_list);
+                }
+                self.filtro_dirty = true;
             }
-            if let Some(data) = foto.proposta_create_date {
-                campi_da_scrivere.push(("CreateDate", data));
+            Err(e) => {
+                self.override_non_trovate.clear();
+                self.messaggio_override = format!("⚠️ {}", e);
             }
-            
-            (foto.path.clone(), campi_da_scrivere)
-        }).collect();
-        
-        // Use shared counters to communicate progress
-        use std::sync::{Arc, Mutex};
-        let progresso = Arc::new(Mutex::new((0usize, 0usize))); // (successi, errori)
-        self.progresso_counter = Some(progresso.clone());
-        
-        // Start writing in a separate thread
-        std::thread::spawn(move || {
-            use rayon::prelude::*;
-            
-            let _risultati: Vec<_> = dati_scrittura
-                .into_par_iter()
-                .map(|(path, campi)| {
-                    let risultato = if campi.is_empty() {
-                        Ok(())
-                    } else {
-                        crate::scrivi_tutti_campi_exif(&path, &campi)
-                    };
-                    
-                    // Update shared counters
-                    let mut counter = progresso.lock().unwrap();
-                    if risultato.is_ok() {
-                        counter.0 += 1;
-                    } else {
-                        counter.1 += 1;
-                        // Print error for debug
-                        if let Err(ref e) = risultato {
-                            eprintln!("EXIF write error for {}: {}", path.display(), e);
-                        }
-                    }
-                    
-                    risultato
-                })
-                .collect();
-            
-            // Mark as completed by setting a special value
-            // (we'll use foto_totali_da_modificare + 1 as completion indicator)
-        });
+        }
     }
     
-    fn aggiorna_progresso_da_counter(&mut self, ctx: &egui::Context) {
-        let mut completato = false;
-        
-        if let Some(ref counter_arc) = self.progresso_counter {
-            if let Ok(counter) = counter_arc.try_lock() {
-                let (successi, errori) = *counter;
-                let totale_elaborate = successi + errori;
-                
-                // Update state
-                self.foto_modificate = successi;
-                self.errori_applicazione = errori;
-                
-                // If all photos have been processed, complete
-                if totale_elaborate >= self.foto_totali_da_modificare && self.foto_totali_da_modificare > 0 {
-                    completato = true;
-                } else {
-                    ctx.request_repaint();
-                }
+    // Manual date typed in the GUI for the given photos; None clears it
+    fn imposta_data_manuale(&mut self, indici: &[usize], campo: CampoData, testo: Option<&str>) -> Result<(), String> {
+        let data = match testo {
+            Some(testo) => {
+                let (data, precisione) = crate::date_manuali::interpreta_data(testo)?;
+                Some(DataManuale { data, precisione, origine: "gui".to_string() })
+            }
+            None => None,
+        };
+        for &idx in indici {
+            if let Some(foto) = self.foto_list.get_mut(idx) {
+                crate::date_manuali::imposta_data_manuale(foto, campo, data.clone());
             }
         }
+        if self.uniforma_date_duplicati {
+            crate::duplicati::uniforma_proposte(&mut self.foto_list);
+        }
+        self.filtro_dirty = true;
+        Ok(())
+    }
+    
+    fn inizia_modifica_cella(&mut self, idx: usize, campo: CampoData) {
+        let Some(foto) = self.foto_list.get(idx) else {
+            return;
+        };
+        let (manuale, proposta) = match campo {
+            CampoData::CreateDate => (foto.data_manuale_create_date.as_ref(), foto.proposta_create_date),
+            _ => (foto.data_manuale.as_ref(), foto.proposta_datetime_original),
+        };
+        let testo = match (manuale, proposta) {
+            (Some(manuale), _) => manuale.precisione.formatta(manuale.data),
+            // The 1900 placeholder is not a useful starting point
+            (None, Some(dt)) if dt.year() != 1900 => dt.format("%Y-%m-%d %H:%M:%S").to_string(),
+            _ => String::new(),
+        };
+        let giorno = proposta.filter(|dt| dt.year() != 1900)
+            .map(|dt| dt.date_naive())
+            .unwrap_or_else(|| chrono::Local::now().date_naive());
+        self.modifica_cella = Some(ModificaCella { idx, campo, testo, giorno, errore: None, focus_dato: false });
+    }
+    
+    fn gestisci_azione_cella(&mut self, idx: usize, campo: CampoData, azione: AzioneCella) {
+        match azione {
+            AzioneCella::Nessuna => {}
+            AzioneCella::Modifica => self.inizia_modifica_cella(idx, campo),
+            AzioneCella::Annulla => self.modifica_cella = None,
+            AzioneCella::Cancella => {
+                let _ = self.imposta_data_manuale(&[idx], campo, None);
+                self.modifica_cella = None;
+            }
+            AzioneCella::Conferma => {
+                let testo = self.modifica_cella.as_ref().map(|m| m.testo.trim().to_string()).unwrap_or_default();
+                // An empty cell removes the manual date
+                let risultato = if testo.is_empty() {
+                    self.imposta_data_manuale(&[idx], campo, None)
+                } else {
+                    self.imposta_data_manuale(&[idx], campo, Some(&testo))
+                };
+                match risultato {
+                    Ok(()) => self.modifica_cella = None,
+                    Err(e) => if let Some(ref mut modifica) = self.modifica_cella {
+                        modifica.errore = Some(e);
+                    },
+                }
+            }
+        }
+    }
+    
+    fn imposta_data_selezione(&mut self, cancella: bool) {
+        let mut indici: Vec<usize> = self.foto_selezionate.iter().copied().collect();
+        indici.sort();
+        let testo = self.testo_data_selezione.trim().to_string();
+        let risultato = self.imposta_data_manuale(&indici, self.campo_selezione, (!cancella).then_some(testo.as_str()));
+        self.messaggio_data_selezione = match risultato {
+            Ok(()) if cancella => format!("Manual date removed from {} photos", indici.len()),
+            Ok(()) => format!("✍ {} set on {} photos", testo, indici.len()),
+            Err(e) => format!("⚠️ {}", e),
+        };
+    }
+    
+    fn calcola_scostamento(&self) -> Result<crate::scostamento::Scostamento, String> {
+        match self.modo_scostamento {
+            ModoScostamento::Durata => crate::scostamento::interpreta_scostamento(&self.testo_scostamento),
+            ModoScostamento::Riferimento => {
+                let foto = self.riferimento_scostamento
+                    .and_then(|idx| self.foto_list.get(idx))
+                    .ok_or("Choose a reference photo first")?;
+                let (ora_vera, _) = crate::date_manuali::interpreta_data(&self.ora_vera_riferimento)?;
+                crate::scostamento::scostamento_da_riferimento(foto, ora_vera)
+                    .ok_or_else(|| "The reference photo has no EXIF date".to_string())
+            }
+        }
+    }
+    
+    fn indici_scostamento(&self) -> Vec<usize> {
+        let mut indici: Vec<usize> = match self.fotocamera_scostamento {
+            Some(ref fotocamera) => self.foto_list.iter().enumerate()
+                .filter(|(_, f)| f.fotocamera.as_ref() == Some(fotocamera))
+                .map(|(idx, _)| idx)
+                .collect(),
+            None => self.foto_selezionate.iter().copied().collect(),
+        };
+        indici.sort();
+        indici
+    }
+    
+    // Shifted dates become manual dates: visible as proposals, written by "Apply Modifications"
+    fn anteprima_scostamento(&mut self) {
+        let scostamento = match self.calcola_scostamento() {
+            Ok(s) => s,
+            Err(e) => {
+                self.messaggio_scostamento = format!("⚠️ {}", e);
+                return;
+            }
+        };
+        let indici = self.indici_scostamento();
+        let (spostate, senza_data) = crate::scostamento::applica_scostamento(&mut self.foto_list, &indici, scostamento, "shift");
+        self.messaggio_scostamento = format!("🕑 {} photos shifted by {} ({} without EXIF date skipped). Review the proposals, then Apply Modifications",
+                                             spostate, scostamento.descrizione(), senza_data);
+        self.filtro_dirty = true;
+    }
+    
+    fn rileva_orologi(&mut self) {
+        let gruppi = crate::orologio::analizza_orologi(&self.foto_list);
+        let analizzati = gruppi.len();
+        let fusi = gruppi.iter().filter(|g| g.probabile_fuso()).count();
+        self.gruppi_orologio = gruppi.into_iter().filter(|g| g.da_correggere()).collect();
+        self.messaggio_orologio = format!("{} camera/folder groups analyzed, {} with a consistent clock offset",
+                                          analizzati, self.gruppi_orologio.len());
+        if fusi > 0 {
+            // Whole hours: most likely photos taken in another time zone, not a wrong clock
+            self.messaggio_orologio.push_str(&format!(", {} off by whole hours (time zone, not corrected: use Shift Time if needed)", fusi));
+        }
+    }
+    
+    fn correggi_orologio(&mut self, quale: Option<usize>) {
+        let gruppi: Vec<crate::orologio::GruppoOrologio> = match quale {
+            Some(i) => self.gruppi_orologio.get(i).cloned().into_iter().collect(),
+            None => self.gruppi_orologio.clone(),
+        };
+        let mut corrette = 0;
+        for gruppo in &gruppi {
+            corrette += crate::orologio::applica_correzione(&mut self.foto_list, gruppo).0;
+        }
+        self.messaggio_orologio = format!("🕑 {} photos corrected in {} groups. Review the proposals, then Apply Modifications",
+                                          corrette, gruppi.len());
+        self.filtro_dirty = true;
+    }
+    
+    fn cancella_date_manuali(&mut self) {
+        for foto in self.foto_list.iter_mut().filter(|f| f.ha_data_manuale()) {
+            crate::date_manuali::imposta_data_manuale(foto, crate::date_manuali::CampoData::Entrambi, None);
+        }
+        self.override_non_trovate.clear();
+        self.messaggio_override.clear();
+        self.calcola_proposte();
+    }
+    
+    fn avvia_ricerca_duplicati(&mut self) {
+        let foto_list = self.foto_list.clone();
+        let percettivo = self.cerca_percettivo;
+        let totale = if percettivo { foto_list.len() * 2 } else { foto_list.len() };
+        let contatore = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        self.duplicati_progresso = Some((contatore.clone(), totale));
+        self.messaggio_duplicati.clear();
+        
+        // The thread returns paths: the list may be reloaded in the meantime
+        self.duplicati_thread = Some(std::thread::spawn(move || {
+            crate::duplicati::trova_gruppi(&foto_list, percettivo, Some(&contatore))
+                .into_iter()
+                .map(|g| g.into_iter().map(|idx| foto_list[idx].path.clone()).collect())
+                .collect()
+        }));
+    }
+    
+    fn verifica_ricerca_duplicati(&mut self, ctx: &egui::Context) {
+        let finito = match self.duplicati_thread {
+            Some(ref handle) => handle.is_finished(),
+            None => return,
+        };
+        if !finito {
+            ctx.request_repaint();
+            return;
+        }
+        
+        if let Some(handle) = self.duplicati_thread.take() {
+            self.duplicati_progresso = None;
+            match handle.join() {
+                Ok(gruppi_path) => {
+                    let gruppi: Vec<Vec<usize>> = gruppi_path.iter()
+                        .map(|g| g.iter().filter_map(|p| self.path_to_index.get(p).copied()).collect::<Vec<_>>())
+                        .filter(|g| g.len() > 1)
+                        .collect();
+                    crate::duplicati::assegna_gruppi(&mut self.foto_list, &gruppi);
+                    self.copie_duplicati = gruppi.iter()
+                        .enumerate()
+                        .map(|(n, g)| (n + 1, g.iter().map(|&idx| self.foto_list[idx].path.clone()).collect()))
+                        .collect();
+                    
+                    let copie: usize = gruppi.iter().map(|g| g.len() - 1).sum();
+                    let mut messaggio = format!("{} groups, {} redundant copies", gruppi.len(), copie);
+                    if self.uniforma_date_duplicati {
+                        let cambiate = crate::duplicati::uniforma_proposte(&mut self.foto_list);
+                        messaggio.push_str(&format!("\n{} proposals aligned within groups", cambiate));
+                    }
+                    self.messaggio_duplicati = messaggio;
+                    self.aggiorna_statistiche();
+                    self.filtro_dirty = true;
+                }
+                Err(e) => self.messaggio_duplicati = format!("⚠️ Duplicate search failed: {:?}", e),
+            }
+        }
+    }
+    
+    fn avvia_applicazione_modifiche(&mut self, _ctx: &egui::Context) {
+        // Apply modifications only to selected photos
+        let richieste: Vec<_> = self.foto_list
+            .iter()
+            .enumerate()
+            .filter(|(idx, f)| {
+                // Must be selected AND have at least one proposal (or JSON metadata to restore).
+                // Photos still inside an archive are written by Extract & Fix
+                self.foto_selezionate.contains(idx) && f.origine_archivio.is_none() && (
+                    f.proposta_datetime_original.is_some() ||
+                    f.proposta_create_date.is_some() ||
+                    (self.opzioni_scrittura.scrivi_metadati_json && f.metadati_json.is_some())
+                )
+            })
+            .map(|(_, f)| RichiestaScrittura::da_foto(f, &self.opzioni_scrittura))
+            .collect();
+        
+        self.avvia_scrittura(richieste, self.opzioni_scrittura.clone());
+    }
+    
+    fn avvia_impostazione_date_file(&mut self) {
+        // Only file mtime/atime, also for photos whose EXIF is already correct
+        let richieste: Vec<_> = self.foto_list
+            .iter()
+            .enumerate()
+            .filter(|(idx, f)| self.foto_selezionate.contains(idx) && f.origine_archivio.is_none())
+            .filter_map(|(_, f)| RichiestaScrittura::solo_data_file(f))
+            .collect();
+        
+        self.avvia_scrittura(richieste, self.opzioni_scrittura.clone());
+    }
+    
+    fn avvia_scrittura(&mut self, richieste: Vec<RichiestaScrittura>, opzioni: OpzioniScrittura) {
+        if richieste.is_empty() {
+            return;
+        }
+        // Journal of the written dates, so the CLI `undo` can revert them
+        let journal = match crate::journal::Journal::nuovo("scrittura") {
+            Ok(j) => Some(j),
+            Err(e) => {
+                eprintln!("Journal non disponibile, scrittura non annullabile: {}", e);
+                None
+            }
+        };
+        self.avvia_scrittura_con_journal(richieste, opzioni, journal);
+    }
+    
+    // Continues an interrupted apply with the files not yet written, in the same journal
+    fn riprendi_scrittura_sospesa(&mut self) {
+        let Some(sospesa) = self.scrittura_sospesa.take() else {
+            return;
+        };
+        let richieste = sospesa.da_eseguire();
+        if richieste.is_empty() {
+            ScritturaSospesa::elimina();
+            return;
+        }
+        let journal = sospesa.journal.as_deref()
+            .filter(|j| j.exists())
+            .map(crate::journal::Journal::riapri)
+            .unwrap_or_else(|| crate::journal::Journal::nuovo("scrittura"));
+        let journal = match journal {
+            Ok(j) => Some(j),
+            Err(e) => {
+                eprintln!("Journal non disponibile, scrittura non annullabile: {}", e);
+                None
+            }
+        };
+        self.avvia_scrittura_con_journal(richieste, sospesa.opzioni, journal);
+    }
+    
+    fn avvia_scrittura_con_journal(&mut self, richieste: Vec<RichiestaScrittura>, opzioni: OpzioniScrittura, journal: Option<crate::journal::Journal>) {
+        // Kept to pair the results with their requests, so failures can be retried as they were
+        self.richieste_in_corso = richieste.clone();
+        self.opzioni_in_corso = opzioni.clone();
+        self.journal_in_corso = journal.as_ref().map(|j| j.path().to_path_buf());
+        
+        // Saved until the end: if the app is closed or crashes, the apply can be resumed.
+        // Only one apply can be pending, so this replaces any older one
+        let sospesa = ScritturaSospesa { journal: self.journal_in_corso.clone(), opzioni: opzioni.clone(), richieste: richieste.clone() };
+        if let Err(e) = sospesa.salva() {
+            eprintln!("[DEBUG] {}", e);
+        }
+        self.scrittura_sospesa = None;
+        
+        self.foto_totali_da_modificare = richieste.len();
+        self.foto_modificate = 0;
+        self.errori_applicazione = 0;
+        self.applicando_modifiche = true;
+        
+        let controllo = ControlloLavoro::new();
+        self.controllo_scrittura = Some(controllo.clone());
+        
+        // Use shared counters to communicate progress
+        use std::sync::{Arc, Mutex};
+        let progresso = Arc::new(Mutex::new((0usize, 0usize))); // (successi, errori)
+        self.progresso_counter = Some(progresso.clone());
+        
+        // Start writing in a separate thread; the results say which files to re-read.
+        // The written files leave the scan cache even when it is off, so it never serves them stale
+        let dir_cache = self.dir_cache_scansione.clone();
+        self.scrittura_thread = Some(std::thread::spawn(move || {
+            let mut cache = CacheScansione::carica_da(dir_cache.as_deref());
+            let esiti = crate::scrittura::esegui_scritture(&richieste, &opzioni, journal.as_ref(), cache.as_mut(),
+                                                           Some(&progresso), Some(&controllo));
+            if let Some(Err(e)) = cache.as_mut().map(|c| c.salva()) {
+                eprintln!("[DEBUG] {}", e);
+            }
+            esiti
+        }));
+    }
+    
+    fn aggiorna_progresso_da_counter(&mut self, ctx: &egui::Context) {
+        if let Some(ref counter_arc) = self.progresso_counter {
+            if let Ok(counter) = counter_arc.try_lock() {
+                let (successi, errori) = *counter;
+                self.foto_modificate = successi;
+                self.errori_applicazione = errori;
+            }
+        }
+        
+        let finito = match self.scrittura_thread {
+            Some(ref handle) => handle.is_finished(),
+            None => return,
+        };
+        if !finito {
+            ctx.request_repaint();
+            return;
+        }
+        
+        self.applicando_modifiche = false;
+        self.progresso_counter = None;
+        self.controllo_scrittura = None;
+        if let Some(handle) = self.scrittura_thread.take() {
+            match handle.join() {
+                Ok(esiti) => {
+                    let errori = esiti.iter().filter(|e| e.risultato.is_err()).count();
+                    self.foto_modificate = esiti.len() - errori;
+                    self.errori_applicazione = errori;
+                    // Stopped before the end: the files not attempted stay pending
+                    self.scrittura_sospesa = ScritturaSospesa::aggiorna_dopo(self.journal_in_corso.as_deref(), self.opzioni_in_corso.clone(),
+                                                                              &self.richieste_in_corso, &esiti);
+                    self.registra_errori_scrittura(&esiti);
+                    if self.chiusura_in_corso {
+                        // The window is waiting to close: no need to re-read anything
+                        ctx.request_repaint();
+                        return;
+                    }
+                    self.ricarica_foto_scritte(&esiti);
+                }
+                Err(e) => eprintln!("[ERROR] Thread di scrittura fallito: {:?}", e),
+            }
+        }
+    }
+    
+    // I file appena tentati sostituiscono i loro errori precedenti: chi è riuscito esce dall'elenco
+    fn registra_errori_scrittura(&mut self, esiti: &[EsitoScrittura]) {
+        // After a stop only part of the requests have a result: pair them by path
+        let mut richieste: std::collections::HashMap<PathBuf, RichiestaScrittura> = std::mem::take(&mut self.richieste_in_corso)
+            .into_iter()
+            .map(|r| (r.path.clone(), r))
+            .collect();
+        let tentati: std::collections::HashSet<&PathBuf> = esiti.iter().map(|e| &e.path).collect();
+        self.errori_scrittura.retain(|(errore, _)| !tentati.contains(&errore.path));
+        let mut nuovi = 0;
+        for esito in esiti {
+            if let (Err(ref errore), Some(richiesta)) = (&esito.risultato, richieste.remove(&esito.path)) {
+                self.errori_scrittura.push((errore.clone(), richiesta));
+                nuovi += 1;
+            }
+        }
+        let rimasti: std::collections::HashSet<&PathBuf> = self.errori_scrittura.iter().map(|(e, _)| &e.path).collect();
+        self.errori_selezionati.retain(|p| rimasti.contains(p));
+        self.messaggio_errori.clear();
+        if nuovi > 0 {
+            self.mostra_errori = true;
+        }
+    }
+    
+    fn riprova_errori(&mut self, riparazione: bool) {
+        let richieste: Vec<RichiestaScrittura> = self.errori_scrittura.iter()
+            .filter(|(errore, _)| self.errori_selezionati.contains(&errore.path))
+            .map(|(_, richiesta)| richiesta.clone())
+            .collect();
+        if richieste.is_empty() {
+            self.messaggio_errori = "No failed file selected".to_string();
+            return;
+        }
+        let opzioni = OpzioniScrittura { riparazione, ..self.opzioni_scrittura.clone() };
+        self.messaggio_errori = format!("Retrying {} files{}...", richieste.len(), if riparazione { " in repair mode" } else { "" });
+        self.avvia_scrittura(richieste, opzioni);
+    }
+    
+    fn esporta_errori(&mut self) {
+        let Some(file) = rfd::FileDialog::new()
+            .add_filter("CSV", &["csv"])
+            .add_filter("JSON Lines", &["jsonl"])
+            .set_file_name("write-errors.csv")
+            .save_file() else {
+            return;
+        };
+        let errori = self.errori_scrittura.iter().map(|(e, _)| e);
+        let esito = fs::File::create(&file)
+            .map_err(|e| format!("Errore creazione {}: {}", file.display(), e))
+            .and_then(|output| if file.extension().is_some_and(|e| e == "jsonl") {
+                crate::errori_scrittura::scrivi_jsonl(errori, output)
+            } else {
+                crate::errori_scrittura::scrivi_csv(errori, output)
+            });
+        self.messaggio_errori = match esito {
+            Ok(()) => format!("✅ {} errors exported to {}", self.errori_scrittura.len(), file.display()),
+            Err(e) => format!("⚠️ {}", e),
+        };
+    }
+    
+    fn mostra_finestra_errori(&mut self, ctx: &egui::Context) {
+        if !self.mostra_errori || self.errori_scrittura.is_empty() {
+            return;
+        }
+        let mut aperta = true;
+        let mut azione = None;
+        egui::Window::new(format!("Write errors ({})", self.errori_scrittura.len()))
+            .open(&mut aperta)
+            .default_width(900.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Select all").clicked() {
+                        self.errori_selezionati = self.errori_scrittura.iter().map(|(e, _)| e.path.clone()).collect();
+                    }
+                    if ui.button("Select none").clicked() {
+                        self.errori_selezionati.clear();
+                    }
+                    ui.separator();
+                    let puo_riprovare = !self.applicando_modifiche && !self.errori_selezionati.is_empty();
+                    if ui.add_enabled(puo_riprovare, egui::Button::new("🔁 Retry selected")).clicked() {
+                        azione = Some(false);
+                    }
+                    if ui.add_enabled(puo_riprovare, egui::Button::new("🛠 Retry in repair mode"))
+                        .on_hover_text("Rebuild the metadata from the tags exiftool can still read, ignoring minor errors")
+                        .clicked() {
+                        azione = Some(true);
+                    }
+                    ui.separator();
+                    if ui.button("💾 Export...").clicked() {
+                        self.esporta_errori();
+                    }
+                    if ui.add_enabled(!self.applicando_modifiche, egui::Button::new("Clear")).clicked() {
+                        self.errori_scrittura.clear();
+                        self.errori_selezionati.clear();
+                    }
+                });
+                if !self.messaggio_errori.is_empty() {
+                    ui.label(&self.messaggio_errori);
+                }
+                ui.separator();
+                
+                egui::ScrollArea::both().max_height(400.0).show(ui, |ui| {
+                    egui::Grid::new("write_errors_grid").striped(true).show(ui, |ui| {
+                        ui.label("");
+                        ui.strong("File");
+                        ui.strong("Tag");
+                        ui.strong("Exit code");
+                        ui.strong("Cause");
+                        ui.strong("Message");
+                        ui.end_row();
+                        for (errore, _) in &self.errori_scrittura {
+                            let mut selezionato = self.errori_selezionati.contains(&errore.path);
+                            if ui.checkbox(&mut selezionato, "").changed() {
+                                if selezionato {
+                                    self.errori_selezionati.insert(errore.path.clone());
+                                } else {
+                                    self.errori_selezionati.remove(&errore.path);
+                                }
+                            }
+                            let nome = errore.path.file_name().unwrap_or_default().to_string_lossy();
+                            ui.label(nome.as_ref()).on_hover_text(errore.path.display().to_string());
+                            ui.label(&errore.tag);
+                            ui.label(errore.codice_uscita.map(|c| c.to_string()).unwrap_or_else(|| "-".to_string()));
+                            ui.label(errore.causa.display_name()).on_hover_text(errore.causa.suggerimento());
+                            // First line only: the full stderr is in the tooltip and in the export
+                            let prima_riga = errore.stderr.lines().next().unwrap_or("");
+                            let breve: String = prima_riga.chars().take(100).collect();
+                            ui.label(breve).on_hover_text(&errore.stderr);
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        if let Some(riparazione) = azione {
+            self.riprova_errori(riparazione);
+        }
+        if !aperta {
+            self.mostra_errori = false;
+        }
+    }
+    
+    // Rilegge dal disco solo le foto appena scritte (anche quelle fallite, che possono
+    // essere state toccate a metà) e le rimette allo stesso indice: selezione, ordinamento
+    // e cartelle caricate restano come sono
+    fn ricarica_foto_scritte(&mut self, esiti: &[EsitoScrittura]) {
+        let da_rileggere: Vec<(usize, PathBuf)> = esiti.iter()
+            .filter_map(|e| self.path_to_index.get(&e.path).map(|&idx| (idx, e.path.clone())))
+            .collect();
+        if da_rileggere.is_empty() {
+            return;
+        }
+        
+        // Files just written are always read from disk: their old entry must not be used
+        let mut cache = self.apri_cache_scansione();
+        if let Some(ref mut cache) = cache {
+            cache.rimuovi_tutti(da_rileggere.iter().map(|(_, path)| path.as_path()));
+        }
+        let rilette: Vec<_> = {
+            use rayon::prelude::*;
+            let cache = cache.as_ref();
+            da_rileggere.into_par_iter()
+                .map(|(idx, path)| (idx, CacheScansione::leggi(cache, path)))
+                .collect()
+        };
+        eprintln!("[DEBUG] Rilette {} foto scritte", rilette.len());
+        for (idx, (mut foto, voce)) in rilette {
+            if let (Some(cache), Some(voce)) = (cache.as_mut(), voce) {
+                cache.inserisci(&foto.path, voce);
+            }
+            // Quello che non viene dai file resta: date manuali e gruppi di duplicati
+            let vecchia = &self.foto_list[idx];
+            foto.data_manuale = vecchia.data_manuale.clone();
+            foto.data_manuale_create_date = vecchia.data_manuale_create_date.clone();
+            foto.gruppo_duplicati = vecchia.gruppo_duplicati;
+            if self.foto_anteprima == Some(idx) {
+                self.json_anteprima = None;
+            }
+            self.foto_list[idx] = foto;
+        }
+        if let Some(Err(e)) = cache.as_mut().map(|c| c.salva()) {
+            eprintln!("[DEBUG] {}", e);
+        }
+        
+        // IMPORTANTE: Ricalcola le proposte con le strategie corrette della GUI,
+        // altrimenti le foto rilette restano con quelle di default
+        self.calcola_proposte();
+        self.aggiorna_statistiche();
+        self.filtro_dirty = true;
+    }
+    
+    fn pianifica_rinomina(&mut self) {
+        let mut selezionate: Vec<&FotoData> = self.foto_list.iter()
+            .enumerate()
+            .filter(|(idx, _)| self.foto_selezionate.contains(idx))
+            .map(|(_, f)| f)
+            .collect();
+        selezionate.sort_by(|a, b| a.path.cmp(&b.path));
+        
+        if selezionate.is_empty() {
+            self.messaggio_rinomina = "No photos selected".to_string();
+            return;
+        }
+        
+        self.piano_rinomina = Some(crate::rinomina::pianifica_rinomina(&selezionate, &self.template_rinomina));
+    }
+    
+    fn esegui_rinomina(&mut self) {
+        let piano = match self.piano_rinomina.take() {
+            Some(p) => p,
+            None => return,
+        };
+        
+        match crate::rinomina::esegui_rinomina(&piano) {
+            Ok(esito) => {
+                // Update paths in place so selection and proposals are kept
+                self.aggiorna_percorsi(&esito.eseguite);
+                for errore in &esito.errori {
+                    eprintln!("Rename error: {}", errore);
+                }
+                self.messaggio_rinomina = if esito.errori.is_empty() {
+                    format!("✅ Renamed {} files", esito.eseguite.len())
+                } else {
+                    format!("✅ Renamed {} files\n⚠️ Errors: {}", esito.eseguite.len(), esito.errori.len())
+                };
+                self.filtro_dirty = true;
+            }
+            Err(e) => {
+                self.messaggio_rinomina = format!("⚠️ {}", e);
+            }
+        }
+    }
+    
+    // Annulla l'ultimo journal di un tipo ("rinomina", "organizza") e restituisce il messaggio
+    fn annulla_ultimo_journal(&mut self, operazione: &str) -> String {
+        let suffisso = format!("-{}.jsonl", operazione);
+        let ultimo = crate::journal::elenca_journal()
+            .into_iter()
+            .find(|p| p.to_string_lossy().ends_with(&suffisso));
+        let mut cache = CacheScansione::carica_da(self.dir_cache_scansione.as_deref());
+        
+        match ultimo {
+            Some(journal) => match crate::journal::annulla_journal(&journal, cache.as_mut()) {
+                Ok((annullate, errori)) => {
+                    if let Some(Err(e)) = cache.as_mut().map(|c| c.salva()) {
+                        eprintln!("[DEBUG] {}", e);
+                    }
+                    for errore in &errori {
+                        eprintln!("Undo error: {}", errore);
+                    }
+                    // Paths changed on disk: reload everything
+                    self.avvia_caricamento_foto_multiplo();
+                    format!("↩️ Restored {} files ({} errors)", annullate, errori.len())
+                }
+                Err(e) => format!("⚠️ {}", e),
+            },
+            None => "Nothing to undo".to_string(),
+        }
+    }
+    
+    fn annulla_ultima_rinomina(&mut self) {
+        self.messaggio_rinomina = self.annulla_ultimo_journal("rinomina");
+    }
+    
+    fn annulla_ultima_organizzazione(&mut self) {
+        self.messaggio_organizza = self.annulla_ultimo_journal("organizza");
+    }
+    
+    fn aggiorna_percorsi(&mut self, operazioni: &[crate::rinomina::OperazioneRinomina]) {
+        let nuovi: std::collections::HashMap<&PathBuf, &PathBuf> = operazioni.iter()
+            .map(|op| (&op.da, &op.a))
+            .collect();
+        
+        for foto in self.foto_list.iter_mut() {
+            if let Some(nuovo) = nuovi.get(&foto.path) {
+                // The review status follows the photo
+                if let Some(stato) = self.stato_revisione.remove(&foto.path) {
+                    self.stato_revisione.insert((*nuovo).clone(), stato);
+                }
+                foto.path = (*nuovo).clone();
+                foto.nome_file = nuovo.file_name().unwrap_or_default().to_string_lossy().to_string();
+            }
+            if let Some(nuovo) = foto.sidecar_xmp.as_ref().and_then(|x| nuovi.get(x)) {
+                foto.sidecar_xmp = Some((*nuovo).clone());
+            }
+        }
+        
+        self.path_to_index.clear();
+        for (idx, foto) in self.foto_list.iter().enumerate() {
+            self.path_to_index.insert(foto.path.clone(), idx);
+        }
+        self.filtro_dirty = true;
+    }
+    
+    // Preview window shared by rename and organize: returns (execute, cancel)
+    fn finestra_anteprima(ctx: &egui::Context, titolo: &str, riepilogo: &[String], righe: &[String], testo_esegui: &str, eseguibile: bool) -> (bool, bool) {
+        let mut esegui = false;
+        let mut annulla = false;
+        
+        egui::Window::new(titolo)
+            .collapsible(false)
+            .default_width(800.0)
+            .show(ctx, |ui| {
+                for riga in riepilogo {
+                    ui.label(riga);
+                }
+                ui.separator();
+                
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show_rows(ui, 18.0, righe.len(), |ui, range| {
+                        for riga in &righe[range] {
+                            ui.label(riga);
+                        }
+                    });
+                
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(eseguibile, egui::Button::new(testo_esegui)).clicked() {
+                        esegui = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        annulla = true;
+                    }
+                });
+            });
+        
+        (esegui, annulla)
+    }
+    
+    fn mostra_anteprima_rinomina(&mut self, ctx: &egui::Context) {
+        let (esegui, annulla) = match self.piano_rinomina {
+            Some(ref piano) => {
+                let totale_file: usize = piano.gruppi.iter().map(|g| g.operazioni.len()).sum();
+                let mut riepilogo = vec![format!("{} photos, {} files to rename (JSON, XMP and RAW+JPG pairs included)", piano.gruppi.len(), totale_file)];
+                if !piano.saltate.is_empty() {
+                    riepilogo.push(format!("⚠️ {} photos skipped", piano.saltate.len()));
+                }
+                
+                let righe: Vec<String> = piano.gruppi.iter()
+                    .flat_map(|g| g.operazioni.iter())
+                    .map(|op| format!("{}  →  {}",
+                                      op.da.file_name().unwrap_or_default().to_string_lossy(),
+                                      op.a.file_name().unwrap_or_default().to_string_lossy()))
+                    .chain(piano.saltate.iter().map(|(p, motivo)| format!("⏭ {} ({})",
+                                      p.file_name().unwrap_or_default().to_string_lossy(), motivo)))
+                    .collect();
+                
+                Self::finestra_anteprima(ctx, "Rename Preview", &riepilogo, &righe, "Execute Rename", !piano.gruppi.is_empty())
+            }
+            None => (false, false),
+        };
+        
+        if esegui {
+            self.esegui_rinomina();
+        } else if annulla {
+            self.piano_rinomina = None;
+        }
+    }
+    
+    fn foto_da_organizzare(&self) -> Vec<&FotoData> {
+        // Selected photos, or all loaded photos if nothing is selected
+        let mut foto: Vec<&FotoData> = if self.foto_selezionate.is_empty() {
+            self.foto_list.iter().collect()
+        } else {
+            self.foto_list.iter()
+                .enumerate()
+                .filter(|(idx, _)| self.foto_selezionate.contains(idx))
+                .map(|(_, f)| f)
+                .collect()
+        };
+        foto.sort_by(|a, b| a.path.cmp(&b.path));
+        foto
+    }
+    
+    fn pianifica_organizzazione(&mut self) {
+        let destinazione = match self.dir_organizza {
+            Some(ref d) => d.clone(),
+            None => {
+                self.messaggio_organizza = "Select a target folder first".to_string();
+                return;
+            }
+        };
+        let foto = self.foto_da_organizzare();
+        if foto.is_empty() {
+            self.messaggio_organizza = "No photos loaded".to_string();
+            return;
+        }
+        self.piano_organizza = Some(crate::organizza::pianifica_organizzazione(&foto, &destinazione, &self.template_organizza, self.salta_duplicati_organizza));
+    }
+    
+    // Bottom pane: photos per period, current EXIF and proposed side by side.
+    // Missing dates and 1900-01-01 have their own bars on the left
+    fn mostra_pannello_cronologia(&mut self, ctx: &egui::Context) {
+        if !self.mostra_cronologia {
+            return;
+        }
+        let istogramma = std::sync::Arc::clone(self.istogramma.get_or_insert_with(|| {
+            std::sync::Arc::new(crate::cronologia::calcola_istogramma(&self.foto_list, self.granularita_cronologia))
+        }));
+        // May be coarser than the one selected when the dates span too many periods
+        let granularita = istogramma.granularita;
+        let colore_attuale = egui::Color32::from_rgb(90, 140, 200);
+        let colore_proposta = egui::Color32::from_rgb(255, 165, 0);
+        
+        let mut scelto: Option<FiltroPeriodo> = None;
+        egui::TopBottomPanel::bottom("timeline_panel")
+            .resizable(true)
+            .default_height(180.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong("Timeline");
+                    for g in [Granularita::Giorno, Granularita::Mese, Granularita::Anno] {
+                        if ui.selectable_value(&mut self.granularita_cronologia, g, g.display_name()).changed() {
+                            self.istogramma = None;
+                        }
+                    }
+                    if granularita != self.granularita_cronologia {
+                        ui.weak(format!("(too many periods: by {})", granularita.display_name().to_lowercase()));
+                    }
+                    ui.separator();
+                    ui.colored_label(colore_attuale, "■ current EXIF");
+                    ui.colored_label(colore_proposta, "■ proposed");
+                });
+                
+                // Columns: missing, 1900, a gap, then every period
+                let mut colonne: Vec<Option<(Secchio, usize, usize)>> = vec![
+                    Some((Secchio::Mancante, istogramma.mancanti.0, istogramma.mancanti.1)),
+                    Some((Secchio::Sentinella, istogramma.sentinella.0, istogramma.sentinella.1)),
+                    None,
+                ];
+                colonne.extend(istogramma.periodi.iter().map(|&(inizio, a, p)| Some((Secchio::Periodo(inizio), a, p))));
+                
+                let altezza_etichette = 16.0;
+                let dimensione = egui::vec2(ui.available_width(), (ui.available_height() - altezza_etichette).max(40.0));
+                let (area, response) = ui.allocate_exact_size(dimensione, egui::Sense::click());
+                let painter = ui.painter_at(area.expand2(egui::vec2(0.0, altezza_etichette)));
+                let larghezza_colonna = area.width() / colonne.len() as f32;
+                let massimo = istogramma.massimo().max(1) as f32;
+                
+                // Column and series under the pointer (left half current, right half proposed)
+                let sotto = |pos: egui::Pos2| -> Option<(usize, Serie)> {
+                    if !area.contains(pos) {
+                        return None;
+                    }
+                    let x = (pos.x - area.left()) / larghezza_colonna;
+                    let serie = if x.fract() < 0.5 { Serie::Attuale } else { Serie::Proposta };
+                    Some((x as usize, serie))
+                };
+                let filtro = |i: usize, serie: Serie| {
+                    colonne.get(i).copied().flatten().map(|(secchio, _, _)| FiltroPeriodo { serie, secchio, granularita })
+                };
+                
+                let mut ultima_etichetta = f32::NEG_INFINITY;
+                for (i, colonna) in colonne.iter().enumerate() {
+                    let Some((secchio, attuali, proposte)) = *colonna else {
+                        continue;
+                    };
+                    let sinistra = area.left() + i as f32 * larghezza_colonna;
+                    let meta = larghezza_colonna / 2.0;
+                    let margine = if meta > 3.0 { 1.0 } else { 0.0 };
+                    for (j, (conteggio, serie, colore)) in [(attuali, Serie::Attuale, colore_attuale), (proposte, Serie::Proposta, colore_proposta)].into_iter().enumerate() {
+                        let x = sinistra + j as f32 * meta;
+                        let altezza = conteggio as f32 / massimo * area.height();
+                        let barra = egui::Rect::from_min_max(
+                            egui::pos2(x + margine, area.bottom() - altezza),
+                            egui::pos2(x + meta - margine, area.bottom()));
+                        painter.rect_filled(barra, 0.0, colore);
+                        if self.filtro_periodo == Some(FiltroPeriodo { serie, secchio, granularita }) {
+                            let evidenziata = egui::Rect::from_min_max(egui::pos2(barra.left(), area.top()), barra.max);
+                            painter.rect_stroke(evidenziata, 0.0, egui::Stroke::new(1.5, egui::Color32::WHITE));
+                        }
+                    }
+                    
+                    // Labels under the bars, skipping those that would overlap
+                    let etichetta = match secchio {
+                        Secchio::Mancante => Some("∅".to_string()),
+                        Secchio::Sentinella => Some("1900".to_string()),
+                        Secchio::Periodo(inizio) => match granularita {
+                            Granularita::Anno => Some(granularita.etichetta(inizio)),
+                            Granularita::Mese if inizio.month() == 1 => Some(inizio.format("%Y").to_string()),
+                            Granularita::Giorno if inizio.day() == 1 => Some(inizio.format("%Y-%m").to_string()),
+                            _ => None,
+                        },
+                    };
+                    if let Some(etichetta) = etichetta {
+                        if sinistra - ultima_etichetta > 40.0 {
+                            painter.text(egui::pos2(sinistra, area.bottom() + 2.0), egui::Align2::LEFT_TOP, etichetta,
+                                         egui::FontId::proportional(11.0), ui.visuals().weak_text_color());
+                            ultima_etichetta = sinistra;
+                        }
+                    }
+                }
+                
+                if let Some((i, serie)) = response.hover_pos().and_then(sotto) {
+                    if let Some(Some((_, attuali, proposte))) = colonne.get(i) {
+                        let descrizione = filtro(i, serie).map(|f| f.descrizione()).unwrap_or_default();
+                        response.clone().on_hover_text_at_pointer(format!(
+                            "{}\ncurrent EXIF: {}   proposed: {}\nClick to show these photos", descrizione, attuali, proposte));
+                    }
+                }
+                if response.clicked() {
+                    scelto = response.interact_pointer_pos().and_then(sotto).and_then(|(i, serie)| filtro(i, serie));
+                }
+            });
+        
+        if let Some(periodo) = scelto {
+            // Clicking the selected bar again removes the filter
+            self.filtro_periodo = if self.filtro_periodo == Some(periodo) { None } else { Some(periodo) };
+            self.filtro_dirty = true;
+        }
+    }
+    
+    // Right pane: the photo large, every date source and the JSON
+    fn mostra_pannello_anteprima(&mut self, ctx: &egui::Context) {
+        if !self.mostra_anteprima {
+            return;
+        }
+        let Some(foto) = self.foto_anteprima.and_then(|idx| self.foto_list.get(idx)).cloned() else {
+            return;
+        };
+        
+        // JSON read once per photo, not at every frame
+        let json_path = if foto.origine_archivio.is_none() { crate::trova_file_json(&foto.path) } else { None };
+        if self.json_anteprima.as_ref().map(|(p, _)| p) != Some(&foto.path) {
+            let testo = match json_path {
+                Some(ref json_path) => fs::read_to_string(json_path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|t| serde_json::from_str::<serde_json::Value>(&t).map_err(|e| e.to_string()))
+                    .and_then(|v| serde_json::to_string_pretty(&v).map_err(|e| e.to_string()))
+                    .unwrap_or_else(|e| format!("⚠️ {}", e)),
+                None => String::new(),
+            };
+            self.json_anteprima = Some((foto.path.clone(), testo));
+        }
+        
+        let mut chiudi = false;
+        egui::SidePanel::right("preview_panel")
+            .resizable(true)
+            .default_width(ctx.screen_rect().width() * 0.3)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong(&foto.nome_file);
+                    if ui.small_button("✖").on_hover_text("Close the preview").clicked() {
+                        chiudi = true;
+                    }
+                    if foto.origine_archivio.is_none() && ui.small_button("Open").on_hover_text("Open with the default viewer").clicked() {
+                        let foto_path = foto.path.clone();
+                        std::thread::spawn(move || {
+                            let _ = std::process::Command::new("xdg-open").arg(&foto_path).spawn();
+                        });
+                    }
+                });
+                ui.label(egui::RichText::new(foto.path.display().to_string()).small().weak());
+                
+                egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+                    // Photo fitted to the pane width and at most 60% of its height
+                    let massimo = egui::vec2(ui.available_width(), ctx.screen_rect().height() * 0.6);
+                    if foto.origine_archivio.is_some() {
+                        ui.label("📦 Inside a Takeout archive: extract it to see the photo");
+                    } else {
+                        match self.miniature.texture(&foto.path, LATO_ANTEPRIMA) {
+                            Ok(Some(texture)) => {
+                                let dimensione = texture.size_vec2();
+                                let scala = (massimo.x / dimensione.x).min(massimo.y / dimensione.y);
+                                ui.image((texture.id(), dimensione * scala));
+                            }
+                            Ok(None) => {
+                                ui.spinner();
+                            }
+                            Err(e) => {
+                                ui.colored_label(egui::Color32::from_rgb(255, 80, 80), format!("⚠️ {}", e));
+                            }
+                        }
+                    }
+                    ui.separator();
+                    
+                    let formatta = |dt: Option<chrono::DateTime<chrono::Utc>>| {
+                        dt.map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "-".to_string())
+                    };
+                    egui::Grid::new("preview_dates").num_columns(2).striped(true).show(ui, |ui| {
+                        let mut riga = |etichetta: &str, valore: String| {
+                            ui.label(etichetta);
+                            ui.label(valore);
+                            ui.end_row();
+                        };
+                        riga("File name", foto.data_nome
+                            .map(|(a, m, g)| format!("{:04}-{:02}-{:02}", a, m, g))
+                            .unwrap_or_else(|| "-".to_string()));
+                        riga("JSON photoTakenTime", formatta(foto.data_json));
+                        riga("JSON creationTime", formatta(foto.data_json_creation));
+                        riga("EXIF DateTimeOriginal", formatta(foto.exif_datetime_original));
+                        riga("EXIF CreateDate", formatta(foto.exif_create_date));
+                        riga("EXIF ModifyDate", formatta(foto.exif_modify_date));
+                        riga("XMP DateTimeOriginal", formatta(foto.xmp_datetime_original));
+                        riga("XMP CreateDate", formatta(foto.xmp_create_date));
+                        if let Some(ref manuale) = foto.data_manuale {
+                            riga("Manual DateTimeOriginal", format!("{} ({})", manuale.precisione.formatta(manuale.data), manuale.origine));
+                        }
+                        if let Some(ref manuale) = foto.data_manuale_create_date {
+                            riga("Manual CreateDate", format!("{} ({})", manuale.precisione.formatta(manuale.data), manuale.origine));
+                        }
+                        if let Some(ref interpolata) = foto.data_interpolata {
+                            riga("Interpolated", format!("{} ({:.0}%, {})", formatta(Some(interpolata.data)),
+                                                        interpolata.confidenza * 100.0, interpolata.origine));
+                        }
+                        riga("→ DateTimeOriginal", format!("{} ({})", formatta(foto.proposta_datetime_original), foto.strategia_datetime_original));
+                        riga("→ CreateDate", format!("{} ({})", formatta(foto.proposta_create_date), foto.strategia_create_date));
+                        riga("Camera", foto.fotocamera.clone().unwrap_or_else(|| "-".to_string()));
+                    });
+                    
+                    if !foto.incongruenze.is_empty() {
+                        ui.separator();
+                        for incongruenza in &foto.incongruenze {
+                            ui.label(format!("⚠️ {}", incongruenza));
+                        }
+                    }
+                    
+                    ui.separator();
+                    match (&json_path, &self.json_anteprima) {
+                        (Some(json_path), Some((_, testo))) => {
+                            egui::CollapsingHeader::new(format!("JSON: {}", json_path.file_name().unwrap_or_default().to_string_lossy()))
+                                .default_open(true)
+                                .show(ui, |ui| {
+                                    ui.label(egui::RichText::new(testo).monospace().small());
+                                });
+                        }
+                        _ => {
+                            ui.label(egui::RichText::new("No JSON").weak());
+                        }
+                    }
+                });
+            });
+        if chiudi {
+            self.foto_anteprima = None;
+        }
+    }
+    
+    fn mostra_anteprima_organizzazione(&mut self, ctx: &egui::Context) {
+        let destinazione = self.dir_organizza.clone().unwrap_or_default();
+        let (esegui, annulla) = match self.piano_organizza {
+            Some(ref piano) => {
+                let totale_file: usize = piano.gruppi.iter().map(|g| g.operazioni.len()).sum();
+                let senza_data = piano.gruppi.iter().filter(|g| g.data.is_none()).count();
+                let mut riepilogo = vec![
+                    format!("{}: {} photos, {} files into {}", self.modalita_organizza.display_name(), piano.gruppi.len(), totale_file, destinazione.display()),
+                    format!("{} photos without a usable date go to {}/", senza_data, crate::organizza::CARTELLA_DATA_SCONOSCIUTA),
+                ];
+                if !piano.saltate.is_empty() {
+                    riepilogo.push(format!("⚠️ {} photos skipped", piano.saltate.len()));
+                }
+                
+                let righe: Vec<String> = piano.gruppi.iter()
+                    .flat_map(|g| g.operazioni.iter())
+                    .map(|op| format!("{}  →  {}",
+                                      op.da.file_name().unwrap_or_default().to_string_lossy(),
+                                      op.a.strip_prefix(&destinazione).unwrap_or(&op.a).display()))
+                    .chain(piano.saltate.iter().map(|(p, motivo)| format!("⏭ {} ({})",
+                                      p.file_name().unwrap_or_default().to_string_lossy(), motivo)))
+                    .collect();
+                
+                Self::finestra_anteprima(ctx, "Organize Preview", &riepilogo, &righe, "Execute", !piano.gruppi.is_empty() && self.organizza_thread.is_none())
+            }
+            None => (false, false),
+        };
+        
+        if esegui {
+            self.avvia_organizzazione();
+        } else if annulla {
+            self.piano_organizza = None;
+        }
+    }
+    
+    fn avvia_organizzazione(&mut self) {
+        let (piano, destinazione) = match (self.piano_organizza.take(), self.dir_organizza.clone()) {
+            (Some(p), Some(d)) => (p, d),
+            _ => return,
+        };
+        let modalita = self.modalita_organizza;
+        let contatore = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        self.organizza_progresso = Some((contatore.clone(), piano.gruppi.len()));
+        self.messaggio_organizza.clear();
+        
+        self.organizza_thread = Some(std::thread::spawn(move || {
+            crate::organizza::esegui_organizzazione(&piano, &destinazione, modalita, Some(&contatore))
+        }));
+    }
+    
+    fn verifica_organizzazione_completata(&mut self, ctx: &egui::Context) {
+        let finito = match self.organizza_thread {
+            Some(ref handle) => handle.is_finished(),
+            None => return,
+        };
+        if !finito {
+            ctx.request_repaint();
+            return;
+        }
         
-        // Release borrow before modifying self
-        if completato {
-            self.applicando_modifiche = false;
-            self.progresso_counter = None;
-            
-            // Reload photos after modifications
-            if let Some(ref dir) = self.directory {
-                // Save paths of selected photos before reloading
-                let vecchie_selezioni: Vec<_> = self.foto_selezionate.iter()
-                    .filter_map(|idx| self.foto_list.get(*idx).map(|f| f.path.clone()))
-                    .collect();
-                
-                self.foto_list = leggi_foto_da_directory(dir);
-                
-                // Ricostruisci la mappa path->indice
-                self.path_to_index.clear();
-                for (idx, foto) in self.foto_list.iter().enumerate() {
-                    self.path_to_index.insert(foto.path.clone(), idx);
-                }
-                
-                // Restore selections based on path
-                self.foto_selezionate.clear();
-                for path in vecchie_selezioni {
-                    if let Some(&idx) = self.path_to_index.get(&path) {
-                        self.foto_selezionate.insert(idx);
+        if let Some(handle) = self.organizza_thread.take() {
+            self.organizza_progresso = None;
+            match handle.join() {
+                Ok(Ok(esito)) => {
+                    if self.modalita_organizza == crate::organizza::ModalitaOrganizza::Sposta {
+                        self.aggiorna_percorsi(&esito.eseguite);
+                    }
+                    for errore in &esito.errori {
+                        eprintln!("Organize error: {}", errore);
                     }
+                    let mut messaggio = format!("✅ {} files processed ({} errors)", esito.eseguite.len(), esito.errori.len());
+                    if let Some(ref manifest) = esito.manifest {
+                        messaggio.push_str(&format!("\nManifest: {}", manifest.display()));
+                    }
+                    self.messaggio_organizza = messaggio;
                 }
-                
-                // IMPORTANTE: Ricalcola le proposte con le strategie corrette della GUI
-                // dopo il reload, altrimenti rimangono quelle di default
-                self.calcola_proposte();
-                self.aggiorna_statistiche();
-                self.filtro_dirty = true; // Ricalcola il filtro dopo il reload
+                Ok(Err(e)) => self.messaggio_organizza = format!("⚠️ {}", e),
+                Err(e) => self.messaggio_organizza = format!("⚠️ Organize thread failed: {:?}", e),
             }
         }
     }
@@ -771,9 +2371,43 @@ impl CorrectorApp {
 
 impl eframe::App for CorrectorApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Closing during a write: stop between files, then close (the rest can be resumed)
+        if ctx.input(|i| i.viewport().close_requested()) && self.scrittura_thread.is_some() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            if let Some(ref controllo) = self.controllo_scrittura {
+                controllo.annulla();
+            }
+            self.chiusura_in_corso = true;
+        }
+        if self.chiusura_in_corso && self.scrittura_thread.is_none() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+        self.aggiorna_finestra(ctx);
+        if ctx.input(|i| i.viewport().close_requested()) && self.scrittura_thread.is_none() {
+            // The exiftool processes kept open for writing
+            crate::exiftool::pool().chiudi();
+            self.salva_impostazioni(true);
+            if self.file_sessione.is_some() && self.sessione_in_apertura.is_none() && !self.loading {
+                self.salva_sessione(false);
+            }
+        } else {
+            self.salva_impostazioni(false);
+        }
+        
         // Verifica se il caricamento è completato
         self.verifica_caricamento_completato(ctx);
         
+        // Finestre di anteprima di rinomina e organizzazione (se c'è un piano in attesa)
+        self.mostra_anteprima_rinomina(ctx);
+        self.mostra_anteprima_organizzazione(ctx);
+        self.verifica_organizzazione_completata(ctx);
+        self.verifica_ricerca_duplicati(ctx);
+        self.verifica_estrazione_completata(ctx);
+        self.mostra_finestra_errori(ctx);
+        if self.miniature.aggiorna(ctx) {
+            ctx.request_repaint_after(std::time::Duration::from_millis(50));
+        }
+        
         // ============================================
         // PRIMA COLONNA: SIDEPANEL SINISTRO - SOLO SETTING
         // ============================================
@@ -793,15 +2427,146 @@ impl eframe::App for CorrectorApp {
             ui.group(|ui| {
                 ui.label("Phase 1: Read Folder");
                 ui.separator();
-                if ui.button("Select Folder").clicked() {
-                    self.seleziona_cartella();
-                }
-                if let Some(ref dir) = self.directory {
+                ui.horizontal(|ui| {
+                    if ui.button("Select Folder").clicked() {
+                        self.seleziona_cartella();
+                    }
+                    if ui.button("Open Takeout Archives").on_hover_text("Read takeout-*.zip / .tgz parts without extracting them").clicked() {
+                        self.seleziona_archivi();
+                    }
+                    let mut da_riaprire = None;
+                    ui.add_enabled_ui(!self.loading && !self.cartelle_recenti.is_empty(), |ui| {
+                        ui.menu_button("Recent", |ui| {
+                            for cartelle in &self.cartelle_recenti {
+                                let nomi: Vec<String> = cartelle.iter().map(|c| c.display().to_string()).collect();
+                                if ui.button(nomi.join(" + ")).clicked() {
+                                    da_riaprire = Some(cartelle.clone());
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    });
+                    if let Some(cartelle) = da_riaprire {
+                        self.riapri_cartelle(cartelle);
+                    }
+                });
+                if !self.archivi.is_empty() {
+                    ui.label(format!("Archives: {} parts", self.archivi.len()));
+                } else if let Some(ref dir) = self.directory {
                     ui.label(format!("Folder: {}", dir.file_name().unwrap_or_default().to_string_lossy()));
                 } else {
                     ui.label("No folder selected");
                 }
                 
+                ui.collapsing("Scan cache", |ui| {
+                    if ui.checkbox(&mut self.usa_cache_scansione, "Reuse unchanged photos from the last scan").changed() {
+                        self.salva_impostazioni(true);
+                    }
+                    let posizione = match self.dir_cache_scansione {
+                        Some(ref dir) => dir.join(crate::cache_scansione::NOME_FILE_CACHE).display().to_string(),
+                        None => crate::cache_scansione::dir_predefinita()
+                            .map(|d| format!("{} (default)", d.join(crate::cache_scansione::NOME_FILE_CACHE).display()))
+                            .unwrap_or_else(|| "no cache folder available".to_string()),
+                    };
+                    ui.label(format!("Location: {}", posizione));
+                    ui.horizontal(|ui| {
+                        if ui.button("Change...").clicked() {
+                            if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                                self.dir_cache_scansione = Some(dir);
+                                self.salva_impostazioni(true);
+                            }
+                        }
+                        if ui.add_enabled(self.dir_cache_scansione.is_some(), egui::Button::new("Default")).clicked() {
+                            self.dir_cache_scansione = None;
+                            self.salva_impostazioni(true);
+                        }
+                    });
+                    let puo_rileggere = !self.loading && !self.directories.is_empty();
+                    if ui.add_enabled(puo_rileggere, egui::Button::new("🔄 Rescan All (ignore cache)"))
+                        .on_hover_text("Re-read every photo from disk and refresh its cache entry")
+                        .clicked() {
+                        self.rileggi_tutto = true;
+                        self.avvia_caricamento_foto_multiplo();
+                    }
+                });
+                
+                ui.collapsing("Review session", |ui| {
+                    ui.horizontal(|ui| {
+                        let puo_salvare = !self.loading && !self.foto_list.is_empty();
+                        if ui.add_enabled(puo_salvare, egui::Button::new("💾 Save Session")).clicked() {
+                            self.salva_sessione(false);
+                        }
+                        if ui.add_enabled(puo_salvare, egui::Button::new("Save As...")).clicked() {
+                            self.salva_sessione(true);
+                        }
+                        if ui.add_enabled(!self.loading, egui::Button::new("📂 Open Session...")).clicked() {
+                            self.apri_sessione();
+                        }
+                    });
+                    if let Some(ref file) = self.file_sessione {
+                        ui.label(egui::RichText::new(format!("Session: {} (saved again on close)", file.display())).small().weak());
+                    }
+                    
+                    let indici: Vec<usize> = self.foto_selezionate.iter().copied().collect();
+                    ui.label(format!("Mark {} selected photos as:", indici.len()));
+                    ui.horizontal(|ui| {
+                        for stato in StatoRevisione::TUTTI {
+                            if ui.add_enabled(!indici.is_empty(), egui::Button::new(format!("{} {}", stato.icona(), stato.display_name()))).clicked() {
+                                self.imposta_stato_revisione(&indici, Some(stato));
+                            }
+                        }
+                        if ui.add_enabled(!indici.is_empty(), egui::Button::new("Clear")).clicked() {
+                            self.imposta_stato_revisione(&indici, None);
+                        }
+                    });
+                    
+                    ui.horizontal(|ui| {
+                        ui.label("Show:");
+                        egui::ComboBox::from_id_source("filtro_revisione")
+                            .selected_text(self.filtro_revisione.display_name())
+                            .show_ui(ui, |ui| {
+                                let filtri = [FiltroRevisione::Tutte, FiltroRevisione::DaRevisionare]
+                                    .into_iter()
+                                    .chain(StatoRevisione::TUTTI.map(FiltroRevisione::Stato));
+                                for filtro in filtri {
+                                    if ui.selectable_value(&mut self.filtro_revisione, filtro, filtro.display_name()).changed() {
+                                        self.filtro_dirty = true;
+                                    }
+                                }
+                            });
+                    });
+                    if !self.foto_list.is_empty() {
+                        let mut conteggi = [0usize; 3];
+                        for foto in &self.foto_list {
+                            if let Some(stato) = self.stato_revisione.get(&foto.path) {
+                                conteggi[StatoRevisione::TUTTI.iter().position(|s| s == stato).unwrap_or(0)] += 1;
+                            }
+                        }
+                        let da_revisionare = self.foto_list.len() - conteggi.iter().sum::<usize>();
+                        ui.label(format!("{} reviewed · {} skipped · {} need attention · {} to review",
+                                         conteggi[0], conteggi[1], conteggi[2], da_revisionare));
+                    }
+                    
+                    if !self.messaggio_sessione.is_empty() {
+                        ui.label(&self.messaggio_sessione);
+                    }
+                    for (titolo, id, file) in [("changed since the session was saved", "file_cambiati_sessione", &self.file_cambiati_sessione),
+                                                ("no longer found", "file_spariti_sessione", &self.file_spariti_sessione)] {
+                        if file.is_empty() {
+                            continue;
+                        }
+                        egui::CollapsingHeader::new(format!("{} files {}", file.len(), titolo))
+                            .id_source(id)
+                            .show(ui, |ui| {
+                                egui::ScrollArea::vertical().id_source(id).max_height(120.0).show(ui, |ui| {
+                                    for path in file {
+                                        ui.label(egui::RichText::new(path.display().to_string()).small());
+                                    }
+                                });
+                            });
+                    }
+                });
+                
                 // Mostra indicatore di caricamento se sta caricando
                 if self.loading {
                     ui.separator();
@@ -823,7 +2588,46 @@ impl eframe::App for CorrectorApp {
                                 .desired_width(ui.available_width() - 20.0));
                             ui.label("Scanning...");
                         }
+                        if let Some(ref controllo) = self.controllo_scansione {
+                            pulsanti_lavoro(ui, controllo);
+                        }
                     });
+                } else if !self.messaggio_scansione.is_empty() {
+                    ui.label(&self.messaggio_scansione);
+                }
+            });
+            
+            ui.separator();
+            
+            // Duplicates: same photo in "Photos from YYYY", albums and repeated export parts
+            ui.group(|ui| {
+                ui.label("Duplicates");
+                ui.separator();
+                
+                ui.checkbox(&mut self.cerca_percettivo, "Include re-encoded copies (perceptual hash, slower)");
+                if ui.checkbox(&mut self.uniforma_date_duplicati, "Same proposed date for all copies").changed()
+                    && self.uniforma_date_duplicati {
+                    crate::duplicati::uniforma_proposte(&mut self.foto_list);
+                    self.filtro_dirty = true;
+                }
+                
+                let abilitato = self.duplicati_thread.is_none() && !self.loading && !self.foto_list.is_empty();
+                if ui.add_enabled(abilitato, egui::Button::new("Find Duplicates")).clicked() {
+                    self.avvia_ricerca_duplicati();
+                }
+                
+                if let Some((ref contatore, totale)) = self.duplicati_progresso {
+                    let fatti = contatore.load(std::sync::atomic::Ordering::Relaxed);
+                    let progresso = if totale > 0 { fatti as f32 / totale as f32 } else { 0.0 };
+                    ui.add(egui::ProgressBar::new(progresso).show_percentage());
+                }
+                
+                if !self.messaggio_duplicati.is_empty() {
+                    ui.label(&self.messaggio_duplicati);
+                }
+                
+                if ui.checkbox(&mut self.solo_duplicati, "Show only duplicates (grouped)").changed() {
+                    self.filtro_dirty = true;
                 }
             });
             
@@ -847,8 +2651,10 @@ impl eframe::App for CorrectorApp {
                             Strategia::NomeFile,
                             Strategia::JsonPreferito,
                             Strategia::ExifAttuale,
+                            Strategia::XmpSidecar,
+                            Strategia::JsonInterpolato,
                         ] {
-                            if ui.selectable_value(&mut self.strategia_datetime_original, strategia.clone(), strategia.display_name()).changed() {
+                            if ui.selectable_value(&mut self.strategia_datetime_original, strategia, strategia.display_name()).changed() {
                                 strategia_cambiata = true;
                             }
                         }
@@ -867,8 +2673,10 @@ impl eframe::App for CorrectorApp {
                             Strategia::NomeFile,
                             Strategia::JsonPreferito,
                             Strategia::ExifAttuale,
+                            Strategia::XmpSidecar,
+                            Strategia::JsonInterpolato,
                         ] {
-                            if ui.selectable_value(&mut self.strategia_create_date, strategia.clone(), strategia.display_name()).changed() {
+                            if ui.selectable_value(&mut self.strategia_create_date, strategia, strategia.display_name()).changed() {
                                 strategia_cambiata = true;
                             }
                         }
@@ -876,29 +2684,278 @@ impl eframe::App for CorrectorApp {
                 
                 ui.separator();
                 
+                // Manual dates always win over the strategies above
+                ui.horizontal(|ui| {
+                    if ui.button("📅 Import Date Overrides...")
+                        .on_hover_text("CSV or JSON with path (or glob) and date, e.g. a report exported with --format csv with the data_manuale column filled in")
+                        .clicked() {
+                        self.importa_date_manuali();
+                    }
+                    let con_data_manuale = self.foto_list.iter().filter(|f| f.ha_data_manuale()).count();
+                    if con_data_manuale > 0 && ui.button(format!("Clear {} Manual Dates", con_data_manuale)).clicked() {
+                        self.cancella_date_manuali();
+                    }
+                });
+                if !self.messaggio_override.is_empty() {
+                    ui.label(&self.messaggio_override);
+                }
+                ui.label(format!("Set date for {} selected photos:", self.foto_selezionate.len()));
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.testo_data_selezione)
+                        .desired_width(140.0)
+                        .hint_text("1987, 1987-07, 1987-07-14 15:30"));
+                    if ui.add(egui_extras::DatePickerButton::new(&mut self.giorno_selezione).id_source("giorno_selezione").calendar_week(false)).changed() {
+                        let orario = self.testo_data_selezione.get(10..).unwrap_or("").to_string();
+                        self.testo_data_selezione = format!("{}{}", self.giorno_selezione.format("%Y-%m-%d"), orario);
+                    }
+                    egui::ComboBox::from_id_source("campo_selezione")
+                        .selected_text(self.campo_selezione.display_name())
+                        .show_ui(ui, |ui| {
+                            for campo in [CampoData::Entrambi, CampoData::DateTimeOriginal, CampoData::CreateDate] {
+                                ui.selectable_value(&mut self.campo_selezione, campo, campo.display_name());
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    let con_selezione = !self.foto_selezionate.is_empty();
+                    if ui.add_enabled(con_selezione && !self.testo_data_selezione.trim().is_empty(), egui::Button::new("✍ Set Date")).clicked() {
+                        self.imposta_data_selezione(false);
+                    }
+                    if ui.add_enabled(con_selezione, egui::Button::new("Clear Manual Date")).clicked() {
+                        self.imposta_data_selezione(true);
+                    }
+                });
+                if !self.messaggio_data_selezione.is_empty() {
+                    ui.label(&self.messaggio_data_selezione);
+                }
+                
+                if !self.override_non_trovate.is_empty() {
+                    egui::CollapsingHeader::new(format!("{} unmatched rows", self.override_non_trovate.len()))
+                        .id_source("override_non_trovate")
+                        .show(ui, |ui| {
+                            egui::ScrollArea::vertical().max_height(120.0).show(ui, |ui| {
+                                for riga in &self.override_non_trovate {
+                                    ui.label(egui::RichText::new(riga).small());
+                                }
+                            });
+                        });
+                }
+                
+                ui.separator();
+                
+                // Bulk time shift (wrong camera clock)
+                ui.label("Shift Time:");
+                ui.horizontal(|ui| {
+                    ui.label("Photos:");
+                    let testo_bersaglio = match self.fotocamera_scostamento {
+                        Some(ref fotocamera) => fotocamera.clone(),
+                        None => format!("Selected ({})", self.foto_selezionate.len()),
+                    };
+                    egui::ComboBox::from_id_source("fotocamera_scostamento")
+                        .selected_text(testo_bersaglio)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.fotocamera_scostamento, None, format!("Selected ({})", self.foto_selezionate.len()));
+                            for (fotocamera, quante) in crate::scostamento::fotocamere(&self.foto_list) {
+                                let etichetta = format!("{} ({})", fotocamera, quante);
+                                ui.selectable_value(&mut self.fotocamera_scostamento, Some(fotocamera), etichetta);
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.modo_scostamento, ModoScostamento::Durata, "By offset");
+                    ui.radio_value(&mut self.modo_scostamento, ModoScostamento::Riferimento, "From reference photo");
+                });
+                match self.modo_scostamento {
+                    ModoScostamento::Durata => {
+                        ui.add(egui::TextEdit::singleline(&mut self.testo_scostamento)
+                            .desired_width(180.0)
+                            .hint_text("+1h, -1y, +2d 3h, -01:30"));
+                    }
+                    ModoScostamento::Riferimento => {
+                        ui.horizontal(|ui| {
+                            let riferimento = self.riferimento_scostamento
+                                .and_then(|idx| self.foto_list.get(idx))
+                                .map(|f| match f.exif_datetime_original.or(f.xmp_datetime_original) {
+                                    Some(dt) => format!("{} (camera: {})", f.nome_file, dt.format("%Y-%m-%d %H:%M:%S")),
+                                    None => format!("{} (no EXIF date)", f.nome_file),
+                                })
+                                .unwrap_or_else(|| "No reference photo".to_string());
+                            ui.label(riferimento);
+                            if ui.add_enabled(self.foto_selezionate.len() == 1, egui::Button::new("Use Selected"))
+                                .on_hover_text("Select exactly one photo whose true time you know")
+                                .clicked() {
+                                self.riferimento_scostamento = self.foto_selezionate.iter().next().copied();
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("True time:");
+                            ui.add(egui::TextEdit::singleline(&mut self.ora_vera_riferimento)
+                                .desired_width(160.0)
+                                .hint_text("2015-07-04 10:10:10"));
+                        });
+                    }
+                }
+                let scostamento = self.calcola_scostamento().ok();
+                if let Some(scostamento) = scostamento {
+                    ui.label(egui::RichText::new(format!("Offset: {}", scostamento.descrizione())).small().weak());
+                }
+                let scostamento_valido = scostamento.map(|s| !s.is_zero()).unwrap_or(false);
+                if ui.add_enabled(scostamento_valido && !self.indici_scostamento().is_empty(), egui::Button::new("🕑 Preview Shift"))
+                    .on_hover_text("Shifted dates replace the proposals (shown with ✍); use Clear Manual Date to revert")
+                    .clicked() {
+                    self.anteprima_scostamento();
+                }
+                if !self.messaggio_scostamento.is_empty() {
+                    ui.label(&self.messaggio_scostamento);
+                }
+                
+                ui.horizontal(|ui| {
+                    if ui.button("Detect Clock Offsets")
+                        .on_hover_text("Per camera and folder: EXIF that differs from the JSON photoTakenTime always by the same amount")
+                        .clicked() {
+                        self.rileva_orologi();
+                    }
+                    if self.gruppi_orologio.len() > 1 && ui.button("Correct All").clicked() {
+                        self.correggi_orologio(None);
+                    }
+                });
+                if !self.messaggio_orologio.is_empty() {
+                    ui.label(&self.messaggio_orologio);
+                }
+                let mut correggi = None;
+                egui::ScrollArea::vertical().id_source("gruppi_orologio").max_height(150.0).show(ui, |ui| {
+                    for (i, gruppo) in self.gruppi_orologio.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            if ui.small_button("Correct").clicked() {
+                                correggi = Some(i);
+                            }
+                            let cartella = gruppo.cartella.file_name().unwrap_or_default().to_string_lossy();
+                            ui.label(format!("{} {} — {}", gruppo.scostamento.descrizione(), gruppo.fotocamera, cartella))
+                                .on_hover_text(format!(
+                                    "{}\n{}/{} photos with JSON agree within ±{} s\n{} photos, {} without JSON\nmean {:.0} s, std dev {:.0} s, range {} … {} s",
+                                    gruppo.cartella.display(),
+                                    gruppo.coerenti, gruppo.campioni, crate::orologio::TOLLERANZA_SECONDI,
+                                    gruppo.indici.len(), gruppo.senza_json(&self.foto_list),
+                                    gruppo.media_secondi, gruppo.deviazione_secondi, gruppo.min_secondi, gruppo.max_secondi));
+                        });
+                    }
+                });
+                if let Some(i) = correggi {
+                    self.correggi_orologio(Some(i));
+                }
+                
+                ui.separator();
+                
                 if strategia_cambiata {
                     self.calcola_proposte();
                     self.filtro_dirty = true;
                 }
                 
+                ui.checkbox(&mut self.opzioni_scrittura.scrivi_metadati_json, "Also write description, people and favorites from JSON");
+                if self.opzioni_scrittura.scrivi_metadati_json {
+                    let con_metadati = self.foto_list.iter()
+                        .filter(|f| f.metadati_json.is_some())
+                        .count();
+                    ui.label(egui::RichText::new(format!("{} photos with JSON metadata (see \"JSON Metadata\" column)", con_metadati)).small().weak());
+                }
+                
+                ui.label("Output:");
+                egui::ComboBox::from_id_source("modalita_output")
+                    .selected_text(self.opzioni_scrittura.modalita_output.display_name())
+                    .show_ui(ui, |ui| {
+                        for modalita in [
+                            ModalitaOutput::Incorporato,
+                            ModalitaOutput::SidecarPerRaw,
+                            ModalitaOutput::SidecarSempre,
+                        ] {
+                            ui.selectable_value(&mut self.opzioni_scrittura.modalita_output, modalita, modalita.display_name());
+                        }
+                    });
+                if self.opzioni_scrittura.modalita_output != ModalitaOutput::Incorporato {
+                    egui::ComboBox::from_id_source("nome_sidecar")
+                        .selected_text(self.opzioni_scrittura.nome_sidecar.display_name())
+                        .show_ui(ui, |ui| {
+                            for nome in [NomeSidecar::ConEstensione, NomeSidecar::SenzaEstensione] {
+                                ui.selectable_value(&mut self.opzioni_scrittura.nome_sidecar, nome, nome.display_name());
+                            }
+                        });
+                }
+                
+                ui.checkbox(&mut self.opzioni_scrittura.imposta_data_file, "Set file modification time to DateTimeOriginal");
+                if self.opzioni_scrittura.imposta_data_file {
+                    ui.horizontal(|ui| {
+                        ui.label("Read the date as:");
+                        egui::ComboBox::from_id_source("fuso_data_file")
+                            .selected_text(self.opzioni_scrittura.fuso_data_file.display_name())
+                            .show_ui(ui, |ui| {
+                                for fuso in [FusoDataFile::Locale, FusoDataFile::Utc] {
+                                    ui.selectable_value(&mut self.opzioni_scrittura.fuso_data_file, fuso, fuso.display_name());
+                                }
+                            });
+                    });
+                    ui.label(egui::RichText::new("Also applied to the JSON and XMP sidecars").small().weak());
+                }
+                
+                ui.checkbox(&mut self.opzioni_scrittura.verifica, "Verify written tags by reading them back")
+                    .on_hover_text("Files whose tags read back differently are reported as write errors");
+                ui.checkbox(&mut self.opzioni_scrittura.verifica_pixel, "Check that image data is unchanged (JPEG)")
+                    .on_hover_text("Hashes the image data without metadata before and after writing");
+                
+                ui.collapsing("Backups", |ui| {
+                    if ui.checkbox(&mut self.opzioni_scrittura.backup, "Back up each file before writing it").changed() {
+                        self.salva_impostazioni(true);
+                    }
+                    let posizione = match self.opzioni_scrittura.dir_backup {
+                        Some(ref dir) => dir.display().to_string(),
+                        None => crate::backup::dir_predefinita()
+                            .map(|d| format!("{} (default)", d.display()))
+                            .unwrap_or_else(|| "no backup folder available".to_string()),
+                    };
+                    ui.label(format!("Location: {}", posizione));
+                    ui.horizontal(|ui| {
+                        if ui.button("Change...").clicked() {
+                            if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                                self.opzioni_scrittura.dir_backup = Some(dir);
+                                self.salva_impostazioni(true);
+                            }
+                        }
+                        if ui.add_enabled(self.opzioni_scrittura.dir_backup.is_some(), egui::Button::new("Default")).clicked() {
+                            self.opzioni_scrittura.dir_backup = None;
+                            self.salva_impostazioni(true);
+                        }
+                    });
+                    let puo_eliminare = self.opzioni_scrittura.backup && self.opzioni_scrittura.verifica;
+                    if ui.add_enabled(puo_eliminare, egui::Checkbox::new(&mut self.opzioni_scrittura.elimina_backup_verificati,
+                                                                        "Delete a backup once its write is verified"))
+                        .on_disabled_hover_text("Needs backups and read-back verification")
+                        .changed() {
+                        self.salva_impostazioni(true);
+                    }
+                    ui.label(egui::RichText::new("Undo restores backed-up files as they were, including the tags exiftool removed").small().weak());
+                    ui.horizontal(|ui| {
+                        // Not while writing: the current run's backups could go too
+                        let clicked = ui.add_enabled(!self.applicando_modifiche, egui::Button::new("Delete backups older than")).clicked();
+                        if ui.add(egui::DragValue::new(&mut self.giorni_backup).clamp_range(0..=3650).suffix(" days")).changed() {
+                            self.salva_impostazioni(true);
+                        }
+                        if clicked {
+                            self.messaggio_backup = match crate::backup::pulisci(self.opzioni_scrittura.dir_backup.as_deref(), self.giorni_backup) {
+                                Ok((cancellate, byte)) => format!("Deleted {} backups ({:.1} MB)", cancellate, byte as f64 / (1024.0 * 1024.0)),
+                                Err(e) => format!("Error: {}", e),
+                            };
+                        }
+                    });
+                    if !self.messaggio_backup.is_empty() {
+                        ui.label(&self.messaggio_backup);
+                    }
+                });
+                
+                ui.separator();
+                
                 if ui.button("Apply Modifications").clicked() {
                     // Calcola quante foto hanno proposte diverse dall'EXIF attuale
                     let foto_con_proposte = self.foto_list.iter()
-                        .filter(|f| {
-                            let ha_proposta_dt = f.proposta_datetime_original.is_some();
-                            let ha_proposta_cd = f.proposta_create_date.is_some();
-                            let cambia_dt = match (f.exif_datetime_original, f.proposta_datetime_original) {
-                                (Some(exif), Some(prop)) => exif != prop,
-                                (None, Some(_)) => true,
-                                _ => false,
-                            };
-                            let cambia_cd = match (f.exif_create_date, f.proposta_create_date) {
-                                (Some(exif), Some(prop)) => exif != prop,
-                                (None, Some(_)) => true,
-                                _ => false,
-                            };
-                            (ha_proposta_dt && cambia_dt) || (ha_proposta_cd && cambia_cd)
-                        })
+                        .filter(|f| crate::scrittura::richiede_scrittura(f, &self.opzioni_scrittura))
                         .count();
                     
                     if foto_con_proposte > 0 {
@@ -908,6 +2965,44 @@ impl eframe::App for CorrectorApp {
                     }
                 }
                 
+                if ui.button("Set File Dates Only").on_hover_text("Set mtime/atime of the selected photos to their final DateTimeOriginal without writing EXIF").clicked() && !self.applicando_modifiche {
+                    self.avvia_impostazione_date_file();
+                }
+                
+                // Photos read from archives: extract the selected ones, then write them
+                if self.foto_list.iter().any(|f| f.origine_archivio.is_some()) {
+                    ui.separator();
+                    ui.label("Photos from Takeout archives:");
+                    ui.horizontal(|ui| {
+                        if ui.button("Output Folder").clicked() {
+                            if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                                self.dir_estrazione = Some(path);
+                            }
+                        }
+                        match self.dir_estrazione {
+                            Some(ref dir) => ui.label(dir.display().to_string()),
+                            None => ui.label("No output folder"),
+                        };
+                    });
+                    
+                    let abilitato = self.estrazione_thread.is_none() && !self.applicando_modifiche;
+                    if ui.add_enabled(abilitato, egui::Button::new("Extract & Fix Selected"))
+                        .on_hover_text("Extract the selected photos with their JSON into the output folder, then apply the modifications to the extracted copies")
+                        .clicked() {
+                        self.avvia_estrazione();
+                    }
+                    
+                    if let Some((ref contatore, totale)) = self.estrazione_progresso {
+                        let fatti = contatore.load(std::sync::atomic::Ordering::Relaxed);
+                        let progresso = if totale > 0 { fatti as f32 / totale as f32 } else { 0.0 };
+                        ui.add(egui::ProgressBar::new(progresso).show_percentage());
+                        ui.label(format!("{}/{} files extracted", fatti, totale));
+                    }
+                    if !self.messaggio_estrazione.is_empty() {
+                        ui.label(&self.messaggio_estrazione);
+                    }
+                }
+                
                 // Show progress bar if applying modifications
                 if self.applicando_modifiche {
                     ui.separator();
@@ -919,15 +3014,48 @@ impl eframe::App for CorrectorApp {
                     };
                     ui.add(egui::ProgressBar::new(progresso).show_percentage());
                     ui.label(format!("{}/{} photos processed", self.foto_modificate, self.foto_totali_da_modificare));
+                    if let Some(ref controllo) = self.controllo_scrittura {
+                        pulsanti_lavoro(ui, controllo);
+                    }
                     
                     // Update progress from shared counter
                     self.aggiorna_progresso_da_counter(ctx);
-                } else if self.foto_modificate > 0 {
+                } else if self.foto_modificate > 0 || !self.errori_scrittura.is_empty() {
                     ui.separator();
                     ui.label(format!("✅ Completed: {} photos modified", self.foto_modificate));
                     if self.errori_applicazione > 0 {
                         ui.label(format!("⚠️ Errors: {}", self.errori_applicazione));
                     }
+                    if !self.errori_scrittura.is_empty()
+                        && ui.button(format!("Show {} failed files...", self.errori_scrittura.len())).clicked() {
+                        self.mostra_errori = true;
+                    }
+                }
+                
+                // Apply stopped, or interrupted by closing or a crash (also in an earlier session)
+                if !self.applicando_modifiche {
+                    let mut azione = None;
+                    if let Some(ref sospesa) = self.scrittura_sospesa {
+                        ui.separator();
+                        ui.label(format!("⏸ Interrupted apply: {} files not written yet", sospesa.richieste.len()))
+                            .on_hover_text("Starting another apply replaces it");
+                        ui.horizontal(|ui| {
+                            if ui.button("▶ Resume apply").clicked() {
+                                azione = Some(true);
+                            }
+                            if ui.button("Discard").clicked() {
+                                azione = Some(false);
+                            }
+                        });
+                    }
+                    match azione {
+                        Some(true) => self.riprendi_scrittura_sospesa(),
+                        Some(false) => {
+                            ScritturaSospesa::elimina();
+                            self.scrittura_sospesa = None;
+                        }
+                        None => {}
+                    }
                 }
             });
             
@@ -1092,8 +3220,97 @@ impl eframe::App for CorrectorApp {
             
             ui.label(format!("Selected photos: {}", self.foto_selezionate.len()));
             });
+            
+            ui.separator();
+            
+            // Phase 4: Rename files by template
+            ui.group(|ui| {
+                ui.label("Phase 4: Rename Files");
+                ui.separator();
+                
+                ui.label("Template:");
+                ui.add(egui::TextEdit::singleline(&mut self.template_rinomina)
+                    .desired_width(ui.available_width()));
+                ui.label(egui::RichText::new("{date:FMT} {year} {month} {day} {camera} {orig_stem} {ext}").small().weak());
+                
+                ui.horizontal(|ui| {
+                    if ui.button("Preview Rename").on_hover_text("Plan the rename of the selected photos using the proposed DateTimeOriginal").clicked() {
+                        self.pianifica_rinomina();
+                    }
+                    if ui.button("Undo Last Rename").clicked() {
+                        self.annulla_ultima_rinomina();
+                    }
+                });
+                
+                if !self.messaggio_rinomina.is_empty() {
+                    ui.label(&self.messaggio_rinomina);
+                }
+            });
+            
+            ui.separator();
+            
+            // Phase 5: Organize into a dated folder tree
+            ui.group(|ui| {
+                ui.label("Phase 5: Organize");
+                ui.separator();
+                
+                ui.horizontal(|ui| {
+                    if ui.button("Target Folder").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                            self.dir_organizza = Some(path);
+                        }
+                    }
+                    match self.dir_organizza {
+                        Some(ref dir) => ui.label(dir.display().to_string()),
+                        None => ui.label("No target folder"),
+                    };
+                });
+                
+                ui.label("Folder template:");
+                ui.add(egui::TextEdit::singleline(&mut self.template_organizza)
+                    .desired_width(ui.available_width()));
+                
+                ui.horizontal(|ui| {
+                    ui.label("Mode:");
+                    egui::ComboBox::from_id_source("modalita_organizza")
+                        .selected_text(self.modalita_organizza.display_name())
+                        .show_ui(ui, |ui| {
+                            for modalita in [ModalitaOrganizza::Copia, ModalitaOrganizza::Sposta, ModalitaOrganizza::Hardlink] {
+                                ui.selectable_value(&mut self.modalita_organizza, modalita, modalita.display_name());
+                            }
+                        });
+                });
+                
+                let quante = if self.foto_selezionate.is_empty() { self.foto_list.len() } else { self.foto_selezionate.len() };
+                ui.checkbox(&mut self.salta_duplicati_organizza, "Skip duplicate copies (keep one per group)")
+                    .on_hover_text("Requires Find Duplicates; the largest copy with a JSON is kept");
+                
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(self.organizza_thread.is_none(), egui::Button::new(format!("Preview Organize ({} photos)", quante))).clicked() {
+                        self.pianifica_organizzazione();
+                    }
+                    if ui.add_enabled(self.organizza_thread.is_none(), egui::Button::new("Undo Last Organize")).clicked() {
+                        self.annulla_ultima_organizzazione();
+                    }
+                });
+                
+                if let Some((ref contatore, totale)) = self.organizza_progresso {
+                    let fatti = contatore.load(std::sync::atomic::Ordering::Relaxed);
+                    let progresso = if totale > 0 { fatti as f32 / totale as f32 } else { 0.0 };
+                    ui.add(egui::ProgressBar::new(progresso).show_percentage());
+                    ui.label(format!("{}/{} photos", fatti, totale));
+                }
+                
+                if !self.messaggio_organizza.is_empty() {
+                    ui.label(&self.messaggio_organizza);
+                }
+            });
         }); // Fine SidePanel
         
+        // Il pannello destro e quello in basso vanno aggiunti prima del CentralPanel
+        self.mostra_pannello_anteprima(ctx);
+        self.mostra_pannello_cronologia(ctx);
+        
         // ============================================
         // SECONDA COLONNA: CENTRALPANEL - SOLO TABELLA
         // ============================================
@@ -1101,9 +3318,25 @@ impl eframe::App for CorrectorApp {
             // Calcola la lista filtrata (usa cache se disponibile)
             self.calcola_foto_da_mostrare();
             
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.mostra_miniature, "Show thumbnails");
+                ui.checkbox(&mut self.mostra_anteprima, "Preview pane")
+                    .on_hover_text("Click a file name or thumbnail to preview the photo with all its dates");
+                ui.checkbox(&mut self.mostra_cronologia, "Timeline")
+                    .on_hover_text("Photos per day, month or year, current EXIF vs. proposed; click a bar to filter the table");
+                if let Some(periodo) = self.filtro_periodo {
+                    ui.label(egui::RichText::new(format!("Showing {}", periodo.descrizione())).color(egui::Color32::from_rgb(255, 165, 0)));
+                    if ui.small_button("✖").on_hover_text("Back to the filters on the left").clicked() {
+                        self.filtro_periodo = None;
+                        self.filtro_dirty = true;
+                    }
+                }
+            });
+            let colonne = if self.mostra_miniature { 10 } else { 9 };
+            
             // Renderizza header fuori dalla virtualizzazione
             egui::Grid::new("foto_grid_header")
-                .num_columns(8)
+                .num_columns(colonne)
                 .spacing([10.0, 4.0])
                 .show(ui, |ui| {
                     // Header with checkbox "Select all"
@@ -1122,6 +3355,9 @@ impl eframe::App for CorrectorApp {
                             }
                         }
                     }
+                    if self.mostra_miniature {
+                        ui.label("Preview");
+                    }
                     // Clickable headers for sorting
                     let nome_response = ui.selectable_label(
                         self.colonna_ordinamento == Some(ColonnaOrdinamento::NomeFile),
@@ -1203,9 +3439,13 @@ impl eframe::App for CorrectorApp {
                     }
                     
                     ui.label("→ Proposal");
+                    ui.label("JSON Metadata");
                     ui.end_row();
                 });
             
+            // Click/edit on a proposal cell, handled after drawing the table
+            let mut azione_cella: Option<(usize, CampoData, AzioneCella)> = None;
+            
             // Frame scuro per la tabella
             egui::Frame::default()
                 .fill(egui::Color32::from_rgb(30, 30, 30))
@@ -1215,7 +3455,7 @@ impl eframe::App for CorrectorApp {
                     // ScrollArea che occupa tutto lo spazio verticale disponibile
                     // Usa show_rows per avere controllo migliore sulla scrollbar
                     let available_height = ui.available_height();
-                    let row_height = 25.0;
+                    let row_height = if self.mostra_miniature { 64.0 } else { 25.0 };
                     
                     egui::ScrollArea::vertical()
                         .auto_shrink([false, false])
@@ -1223,7 +3463,7 @@ impl eframe::App for CorrectorApp {
                         .show_rows(ui, row_height, self.foto_da_mostrare_cached.len(), |ui, row_range| {
                             // Renderizza solo le righe visibili (virtualizzazione)
                             egui::Grid::new("foto_grid_rows")
-                                .num_columns(8)
+                                .num_columns(colonne)
                                 .spacing([10.0, 4.0])
                                 .show(ui, |ui| {
                                     // Renderizza solo le righe nel range visibile
@@ -1282,6 +3522,7 @@ impl eframe::App for CorrectorApp {
                                                     self.foto_selezionate.clear();
                                                     self.foto_selezionate.insert(*idx_originale);
                                                     self.ultimo_indice_selezionato = Some(*idx_originale);
+                                                    self.foto_anteprima = Some(*idx_originale);
                                                 }
                                             } else if is_selected {
                                                 // Keep selected state
@@ -1290,14 +3531,37 @@ impl eframe::App for CorrectorApp {
                                                 self.foto_selezionate.remove(idx_originale);
                                             }
                                             
+                                            if self.mostra_miniature && cella_miniatura(ui, &mut self.miniature, foto).clicked() {
+                                                self.foto_anteprima = Some(*idx_originale);
+                                            }
+                                            
                                             // Highlight row if selected
                                             if is_selected {
                                                 ui.visuals_mut().override_text_color = Some(egui::Color32::from_rgb(100, 150, 255));
                                             }
                                             
-                                            // File name - make it clickable for double click
-                                            let nome_response = ui.selectable_label(false, &foto.nome_file);
+                                            // File name - make it clickable for double click, with the review status in front
+                                            let stato = self.stato_revisione.get(&foto.path).copied();
+                                            let segno_revisione = stato.map(|s| format!("{} ", s.icona())).unwrap_or_default();
+                                            let nome_response = match foto.gruppo_duplicati {
+                                                Some(gruppo) => {
+                                                    let copie = self.copie_duplicati.get(&gruppo);
+                                                    let tenuta = copie.and_then(|c| c.first()) == Some(&foto.path);
+                                                    let segno = if tenuta { "" } else { " (copy)" };
+                                                    let dettaglio = copie.map(|c| c.iter()
+                                                        .map(|p| p.display().to_string())
+                                                        .collect::<Vec<_>>()
+                                                        .join("\n"))
+                                                        .unwrap_or_default();
+                                                    ui.selectable_label(false, format!("{}{} 🗐#{}{}", segno_revisione, foto.nome_file, gruppo, segno))
+                                                        .on_hover_text(format!("Duplicate group #{} (first is kept):\n{}", gruppo, dettaglio))
+                                                }
+                                                None => ui.selectable_label(false, format!("{}{}", segno_revisione, foto.nome_file)),
+                                            };
                                             
+                                            if nome_response.clicked() {
+                                                self.foto_anteprima = Some(*idx_originale);
+                                            }
                                             // Handle double click on file name to open photo
                                             if nome_response.double_clicked() {
                                                 let foto_path = foto.path.clone();
@@ -1307,6 +3571,22 @@ impl eframe::App for CorrectorApp {
                                                         .spawn();
                                                 });
                                             }
+                                            // Right click: review status of this photo
+                                            nome_response.context_menu(|ui| {
+                                                let mut nuovo = stato;
+                                                for scelta in StatoRevisione::TUTTI {
+                                                    ui.radio_value(&mut nuovo, Some(scelta), format!("{} {}", scelta.icona(), scelta.display_name()));
+                                                }
+                                                ui.radio_value(&mut nuovo, None, "Not reviewed");
+                                                if nuovo != stato {
+                                                    match nuovo {
+                                                        Some(nuovo) => self.stato_revisione.insert(foto.path.clone(), nuovo),
+                                                        None => self.stato_revisione.remove(&foto.path),
+                                                    };
+                                                    self.filtro_dirty |= self.filtro_revisione != FiltroRevisione::Tutte;
+                                                    ui.close_menu();
+                                                }
+                                            });
                                             
                                             // Severity with thermometric scale
                                             let giorni_diff = foto.gravita_incongruenza;
@@ -1367,74 +3647,55 @@ impl eframe::App for CorrectorApp {
                                                 ui.label(inc_text);
                                             }
                                             
-                                            // Current DateTimeOriginal
+                                            // Current DateTimeOriginal (from the sidecar if the file has none)
                                             if let Some(dt) = foto.exif_datetime_original {
                                                 ui.label(dt.format("%Y-%m-%d %H:%M:%S").to_string());
+                                            } else if let Some(dt) = foto.xmp_datetime_original {
+                                                ui.label(format!("{} (xmp)", dt.format("%Y-%m-%d %H:%M:%S")));
                                             } else {
                                                 ui.label("❌");
                                             }
                                             
-                                            // DateTimeOriginal proposal
-                                            if let Some(dt_proposta) = foto.proposta_datetime_original {
-                                                let testo_proposta = format!("→ {}", dt_proposta.format("%Y-%m-%d %H:%M:%S"));
-                                                // Check if this is the "flag" date 1900-01-01 for photos without metadata
-                                                let is_flag_date = dt_proposta.year() == 1900 && dt_proposta.month() == 1 && dt_proposta.day() == 1;
-                                                
-                                                if is_flag_date {
-                                                    // Red/purple color to indicate this is a flag date for manual classification
-                                                    ui.visuals_mut().override_text_color = Some(egui::Color32::from_rgb(255, 0, 255)); // Magenta
-                                                } else {
-                                                    // Compare with current EXIF to decide color
-                                                    let cambia = match foto.exif_datetime_original {
-                                                        Some(dt_exif) => dt_exif != dt_proposta,
-                                                        None => true, // If EXIF missing, consider it as a change
-                                                    };
-                                                    if cambia {
-                                                        // Orange color to indicate modification
-                                                        ui.visuals_mut().override_text_color = Some(egui::Color32::from_rgb(255, 165, 0)); // Orange
-                                                    } else {
-                                                        // Gray if no change
-                                                        ui.visuals_mut().override_text_color = Some(egui::Color32::from_rgb(150, 150, 150)); // Gray
-                                                    }
-                                                }
-                                                ui.label(testo_proposta);
-                                                ui.visuals_mut().override_text_color = None; // Reset
-                                            } else {
-                                                ui.label("-");
+                                            // DateTimeOriginal proposal (click to edit)
+                                            let modifica = self.modifica_cella.as_mut()
+                                                .filter(|m| m.idx == *idx_originale && m.campo == CampoData::DateTimeOriginal);
+                                            let id = format!("modifica_dt_{}", idx_originale);
+                                            match cella_proposta(ui, foto.proposta_datetime_original, foto.exif_datetime_original,
+                                                                 foto.data_manuale.as_ref(), foto.proposta_interpolata(), modifica, &id) {
+                                                AzioneCella::Nessuna => {}
+                                                azione => azione_cella = Some((*idx_originale, CampoData::DateTimeOriginal, azione)),
                                             }
                                             
-                                            // Current CreateDate
+                                            // Current CreateDate (from the sidecar if the file has none)
                                             if let Some(dt) = foto.exif_create_date {
                                                 ui.label(dt.format("%Y-%m-%d %H:%M:%S").to_string());
+                                            } else if let Some(dt) = foto.xmp_create_date {
+                                                ui.label(format!("{} (xmp)", dt.format("%Y-%m-%d %H:%M:%S")));
                                             } else {
                                                 ui.label("❌");
                                             }
                                             
-                                            // CreateDate proposal
-                                            if let Some(dt_proposta) = foto.proposta_create_date {
-                                                let testo_proposta = format!("→ {}", dt_proposta.format("%Y-%m-%d %H:%M:%S"));
-                                                // Check if this is the "flag" date 1900-01-01 for photos without metadata
-                                                let is_flag_date = dt_proposta.year() == 1900 && dt_proposta.month() == 1 && dt_proposta.day() == 1;
-                                                
-                                                if is_flag_date {
-                                                    // Red/purple color to indicate this is a flag date for manual classification
-                                                    ui.visuals_mut().override_text_color = Some(egui::Color32::from_rgb(255, 0, 255)); // Magenta
+                                            // CreateDate proposal (click to edit)
+                                            let modifica = self.modifica_cella.as_mut()
+                                                .filter(|m| m.idx == *idx_originale && m.campo == CampoData::CreateDate);
+                                            let id = format!("modifica_cd_{}", idx_originale);
+                                            match cella_proposta(ui, foto.proposta_create_date, foto.exif_create_date,
+                                                                 foto.data_manuale_create_date.as_ref(), foto.proposta_create_date_interpolata(),
+                                                                 modifica, &id) {
+                                                AzioneCella::Nessuna => {}
+                                                azione => azione_cella = Some((*idx_originale, CampoData::CreateDate, azione)),
+                                            }
+                                            
+                                            // Description/people/favorites that would be written from JSON
+                                            if let Some(ref metadati) = foto.metadati_json {
+                                                let testo = metadati.anteprima();
+                                                if self.opzioni_scrittura.scrivi_metadati_json {
+                                                    ui.visuals_mut().override_text_color = Some(egui::Color32::from_rgb(255, 165, 0)); // Orange
+                                                    ui.label(format!("→ {}", testo));
+                                                    ui.visuals_mut().override_text_color = None;
                                                 } else {
-                                                    // Compare with current EXIF to decide color
-                                                    let cambia = match foto.exif_create_date {
-                                                        Some(dt_exif) => dt_exif != dt_proposta,
-                                                        None => true, // If EXIF missing, consider it as a change
-                                                    };
-                                                    if cambia {
-                                                        // Orange color to indicate modification
-                                                        ui.visuals_mut().override_text_color = Some(egui::Color32::from_rgb(255, 165, 0)); // Orange
-                                                    } else {
-                                                        // Gray if no change
-                                                        ui.visuals_mut().override_text_color = Some(egui::Color32::from_rgb(150, 150, 150)); // Gray
-                                                    }
+                                                    ui.label(egui::RichText::new(testo).weak());
                                                 }
-                                                ui.label(testo_proposta);
-                                                ui.visuals_mut().override_text_color = None; // Reset
                                             } else {
                                                 ui.label("-");
                                             }
@@ -1447,6 +3708,10 @@ impl eframe::App for CorrectorApp {
                                 });
                         });
                 });
+            
+            if let Some((idx, campo, azione)) = azione_cella {
+                self.gestisci_azione_cella(idx, campo, azione);
+            }
         });
     }
 }
@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::cronologia::Granularita;
+use crate::filtri::{FiltroIncongruenza, Strategia};
+use crate::scrittura::OpzioniScrittura;
+
+// Impostazioni della GUI che restano tra un avvio e l'altro, in
+// $XDG_CONFIG_HOME/corrigi-exif/impostazioni.json. Ogni campo mancante (file di una
+// versione precedente) prende il valore predefinito; il vecchio ~/.corrigi-exif-config.json
+// con la sola ultima cartella (e poche altre chiavi) viene letto una volta e migrato
+
+const NOME_FILE: &str = "impostazioni.json";
+const VECCHIO_FILE: &str = ".corrigi-exif-config.json";
+const MAX_CARTELLE_RECENTI: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColonnaOrdinamento {
+    NomeFile,
+    Gravita,
+    Incongruenze,
+    DateTimeOriginal,
+    CreateDate,
+}
+
+// Unità in cui si mostra e si inserisce la soglia di gravità (salvata sempre in giorni)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnitaGravita {
+    Secondi,
+    Minuti,
+    Ore,
+    Giorni,
+    Mesi,
+    Anni,
+}
+
+impl UnitaGravita {
+    pub fn display_name(&self) -> &str {
+        match self {
+            UnitaGravita::Secondi => "Seconds",
+            UnitaGravita::Minuti => "Minutes",
+            UnitaGravita::Ore => "Hours",
+            UnitaGravita::Giorni => "Days",
+            UnitaGravita::Mesi => "Months",
+            UnitaGravita::Anni => "Years",
+        }
+    }
+
+    pub fn to_giorni(self, valore: f32) -> f32 {
+        match self {
+            UnitaGravita::Secondi => valore / 86400.0,
+            UnitaGravita::Minuti => valore / 1440.0,
+            UnitaGravita::Ore => valore / 24.0,
+            UnitaGravita::Giorni => valore,
+            UnitaGravita::Mesi => valore * 30.0,
+            UnitaGravita::Anni => valore * 365.0,
+        }
+    }
+}
+
+// Filtri della tabella
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImpostazioniFiltri {
+    pub soglia_gravita_giorni: f32,
+    pub unita_gravita: UnitaGravita,
+    pub mostra_tutte_foto: bool,
+    pub solo_exif_mancante: bool,
+    pub filtro_incongruenza: FiltroIncongruenza,
+    pub mostra_foto_1900: bool,
+    pub mostra_foto_whatsapp: bool,
+    pub mostra_foto_raw: bool,
+    pub solo_duplicati: bool,
+}
+
+impl Default for ImpostazioniFiltri {
+    fn default() -> Self {
+        Self {
+            soglia_gravita_giorni: 0.0,
+            unita_gravita: UnitaGravita::Giorni,
+            mostra_tutte_foto: false,
+            solo_exif_mancante: true,
+            filtro_incongruenza: FiltroIncongruenza::Tutte,
+            mostra_foto_1900: false,
+            mostra_foto_whatsapp: false,
+            mostra_foto_raw: false,
+            solo_duplicati: false,
+        }
+    }
+}
+
+// Dimensione e posizione della finestra e pannelli visibili
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImpostazioniFinestra {
+    pub larghezza: f32,
+    pub altezza: f32,
+    pub posizione: Option<(f32, f32)>, // None: la sceglie il sistema
+    pub massimizzata: bool,
+    pub mostra_miniature: bool,
+    pub mostra_anteprima: bool,
+    pub mostra_cronologia: bool,
+    pub granularita_cronologia: Granularita,
+}
+
+impl Default for ImpostazioniFinestra {
+    fn default() -> Self {
+        Self {
+            larghezza: 1400.0,
+            altezza: 800.0,
+            posizione: None,
+            massimizzata: true,
+            mostra_miniature: true,
+            mostra_anteprima: true,
+            mostra_cronologia: false,
+            granularita_cronologia: Granularita::Anno,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Impostazioni {
+    pub ultima_cartella: Option<PathBuf>,
+    pub cartelle_recenti: Vec<Vec<PathBuf>>, // Dalla più recente; una voce per ogni scelta (anche multipla)
+    pub strategia_datetime_original: Strategia,
+    pub strategia_create_date: Strategia,
+    pub filtri: ImpostazioniFiltri,
+    pub colonna_ordinamento: Option<ColonnaOrdinamento>,
+    pub ordine_crescente: bool,
+    pub finestra: ImpostazioniFinestra,
+    pub opzioni_scrittura: OpzioniScrittura, // Compresi fuso della data del file e backup
+    pub usa_cache_scansione: bool,
+    pub dir_cache_scansione: Option<PathBuf>, // None = $XDG_CACHE_HOME/corrigi-exif
+    pub giorni_backup: u64,                    // Per la pulizia dei backup
+}
+
+impl Default for Impostazioni {
+    fn default() -> Self {
+        Self {
+            ultima_cartella: None,
+            cartelle_recenti: Vec::new(),
+            strategia_datetime_original: Strategia::JsonPreferito, // JSON se disponibile, altrimenti filename
+            strategia_create_date: Strategia::JsonPreferito,
+            filtri: ImpostazioniFiltri::default(),
+            colonna_ordinamento: None,
+            ordine_crescente: true,
+            finestra: ImpostazioniFinestra::default(),
+            opzioni_scrittura: OpzioniScrittura::default(),
+            usa_cache_scansione: true,
+            dir_cache_scansione: None,
+            giorni_backup: 30,
+        }
+    }
+}
+
+pub fn file_impostazioni() -> Option<PathBuf> {
+    crate::percorsi::dir_config().map(|d| d.join(NOME_FILE))
+}
+
+fn vecchio_file() -> Option<PathBuf> {
+    std::env::var("HOME").ok().map(|home| PathBuf::from(home).join(VECCHIO_FILE))
+}
+
+impl Impostazioni {
+    // Dal file delle impostazioni; al primo avvio di questa versione dal vecchio file
+    pub fn carica() -> Self {
+        let Some(file) = file_impostazioni() else {
+            return Self::default();
+        };
+        match fs::read(&file) {
+            Ok(contenuto) => match serde_json::from_slice(&contenuto) {
+                Ok(impostazioni) => impostazioni,
+                Err(e) => {
+                    eprintln!("[DEBUG] Impostazioni {} illeggibili, uso i valori predefiniti: {}", file.display(), e);
+                    Self::default()
+                }
+            },
+            Err(_) => {
+                let impostazioni = vecchio_file()
+                    .filter(|v| v.exists())
+                    .map(|v| Self::migra(&v))
+                    .unwrap_or_default();
+                if impostazioni != Self::default() {
+                    if let Err(e) = impostazioni.salva() {
+                        eprintln!("[DEBUG] {}", e);
+                    }
+                }
+                impostazioni
+            }
+        }
+    }
+
+    // Le chiavi del vecchio file; il file resta dov'è per le versioni precedenti
+    fn migra(vecchio: &Path) -> Self {
+        let mut impostazioni = Self::default();
+        let Some(config) = fs::read_to_string(vecchio).ok()
+            .and_then(|c| serde_json::from_str::<serde_json::Value>(&c).ok()) else {
+            return impostazioni;
+        };
+        eprintln!("[DEBUG] Migrazione delle impostazioni da {}", vecchio.display());
+        let testo = |chiave: &str| config.get(chiave).and_then(|v| v.as_str()).map(PathBuf::from);
+        let booleano = |chiave: &str| config.get(chiave).and_then(|v| v.as_bool());
+
+        if let Some(cartella) = testo("ultima_cartella") {
+            impostazioni.cartelle_recenti.push(vec![cartella.clone()]);
+            impostazioni.ultima_cartella = Some(cartella);
+        }
+        if let Some(usa) = booleano("usa_cache_scansione") {
+            impostazioni.usa_cache_scansione = usa;
+        }
+        impostazioni.dir_cache_scansione = testo("dir_cache_scansione");
+        if let Some(backup) = booleano("backup") {
+            impostazioni.opzioni_scrittura.backup = backup;
+        }
+        impostazioni.opzioni_scrittura.dir_backup = testo("dir_backup");
+        if let Some(elimina) = booleano("elimina_backup_verificati") {
+            impostazioni.opzioni_scrittura.elimina_backup_verificati = elimina;
+        }
+        if let Some(giorni) = config.get("giorni_backup").and_then(|v| v.as_u64()) {
+            impostazioni.giorni_backup = giorni;
+        }
+        impostazioni
+    }
+
+    // Scrive su un file temporaneo e lo rinomina: un arresto a metà non lascia un file troncato
+    pub fn salva(&self) -> Result<(), String> {
+        let file = file_impostazioni().ok_or("Impossibile determinare la cartella delle impostazioni")?;
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Errore creazione {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let temporaneo = file.with_extension("json.tmp");
+        fs::write(&temporaneo, json)
+            .and_then(|_| fs::rename(&temporaneo, &file))
+            .map_err(|e| format!("Errore scrittura impostazioni {}: {}", file.display(), e))
+    }
+
+    // Cartelle appena aperte in cima alle recenti, senza doppioni
+    pub fn aggiungi_recenti(&mut self, cartelle: &[PathBuf]) {
+        if cartelle.is_empty() {
+            return;
+        }
+        self.cartelle_recenti.retain(|c| c.as_slice() != cartelle);
+        self.cartelle_recenti.insert(0, cartelle.to_vec());
+        self.cartelle_recenti.truncate(MAX_CARTELLE_RECENTI);
+        self.ultima_cartella = Some(cartelle[0].clone());
+    }
+}
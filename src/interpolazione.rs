@@ -0,0 +1,184 @@
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use crate::FotoData;
+
+// Date dedotte per le foto senza nessuna fonte (né EXIF, né JSON, né data nel nome):
+// se stanno in una sequenza (DSC_0101..DSC_0140) o in una cartella con foto datate,
+// la data si interpola tra i vicini datati. La usa la strategia json_interpolato al
+// posto del 1900-01-01
+
+// Oltre questa distanza (in numeri di sequenza o posizioni in cartella) il vicino non conta
+pub const DISTANZA_MASSIMA: i64 = 50;
+// Vicini più lontani di così nel tempo: l'interpolazione è solo indicativa
+const INTERVALLO_AFFIDABILE_ORE: i64 = 24;
+
+// Confidenza, da 0 a 1, per come è stata ottenuta la data
+const CONFIDENZA_SEQUENZA: f64 = 0.7;          // Tra due vicini di sequenza vicini nel tempo
+const CONFIDENZA_SEQUENZA_AMPIA: f64 = 0.5;    // Tra due vicini di sequenza lontani nel tempo
+const CONFIDENZA_SEQUENZA_UN_LATO: f64 = 0.4;  // Solo un vicino di sequenza
+const CONFIDENZA_CARTELLA: f64 = 0.3;          // Tra due vicini in ordine di nome nella cartella
+const CONFIDENZA_CARTELLA_UN_LATO: f64 = 0.2;  // Solo un vicino nella cartella
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DataInterpolata {
+    pub data: DateTime<Utc>,
+    pub confidenza: f64,
+    pub origine: String, // Es. "interpolated between DSC_0100.JPG and DSC_0105.JPG"
+}
+
+// Data della foto secondo le sue fonti, nell'ordine della strategia JSON;
+// le date manuali non contano, così il risultato dipende solo dai file
+fn data_nota(foto: &FotoData) -> Option<DateTime<Utc>> {
+    foto.data_json
+        .or(foto.data_json_creation)
+        .or(foto.exif_datetime_original)
+        .or(foto.xmp_datetime_original)
+        .or_else(|| {
+            let (anno, mese, giorno) = foto.data_nome?;
+            let dt = NaiveDateTime::parse_from_str(
+                &format!("{:04}-{:02}-{:02} 12:00:00", anno, mese, giorno),
+                "%Y-%m-%d %H:%M:%S"
+            ).ok()?;
+            Some(DateTime::from_naive_utc_and_offset(dt, Utc))
+        })
+}
+
+// "DSC_0101.JPG" -> ("DSC_", 101): l'ultimo gruppo di cifre del nome senza estensione
+fn numero_sequenza(nome_file: &str) -> Option<(String, i64)> {
+    let stem = nome_file.rsplit_once('.').map(|(s, _)| s).unwrap_or(nome_file);
+    // Sui byte: una cifra ASCII non compare mai dentro un carattere multibyte ("Città2.jpg")
+    let byte = stem.as_bytes();
+    let fine = byte.iter().rposition(u8::is_ascii_digit)? + 1;
+    let inizio = byte[..fine].iter().rposition(|b| !b.is_ascii_digit()).map(|i| i + 1).unwrap_or(0);
+    let numero = stem[inizio..fine].parse().ok()?;
+    Some((stem[..inizio].to_string(), numero))
+}
+
+// Vicino datato: posizione nella sequenza o nella cartella, data, indice in foto_list
+type Vicino = (i64, DateTime<Utc>, usize);
+
+// Data per la posizione `pos` dai vicini datati più prossimi, se ci sono
+fn interpola(pos: i64, prima: Option<Vicino>, dopo: Option<Vicino>, foto_list: &[FotoData], in_sequenza: bool) -> Option<DataInterpolata> {
+    let prima = prima.filter(|(p, _, _)| pos - p <= DISTANZA_MASSIMA);
+    let dopo = dopo.filter(|(p, _, _)| p - pos <= DISTANZA_MASSIMA);
+    let nome = |i: usize| &foto_list[i].nome_file;
+    let dove = if in_sequenza { "sequence" } else { "folder order" };
+
+    match (prima, dopo) {
+        // Vicini in ordine cronologico: interpolazione lineare sulla posizione
+        (Some((p1, d1, i1)), Some((p2, d2, i2))) if d1 <= d2 => {
+            let intervallo = d2 - d1;
+            let frazione = (pos - p1) as f64 / (p2 - p1) as f64;
+            let data = d1 + Duration::seconds((intervallo.num_seconds() as f64 * frazione).round() as i64);
+            let confidenza = match (in_sequenza, intervallo <= Duration::hours(INTERVALLO_AFFIDABILE_ORE)) {
+                (true, true) => CONFIDENZA_SEQUENZA,
+                (true, false) => CONFIDENZA_SEQUENZA_AMPIA,
+                (false, _) => CONFIDENZA_CARTELLA,
+            };
+            Some(DataInterpolata {
+                data,
+                confidenza,
+                origine: format!("interpolated between {} and {} ({})", nome(i1), nome(i2), dove),
+            })
+        }
+        // Vicini fuori ordine (contatore ripartito, foto di fotocamere diverse) o da un lato solo:
+        // si prende la data del più vicino
+        (prima, dopo) => {
+            let (_, data, idx) = match (prima, dopo) {
+                (Some(a), Some(b)) => if pos - a.0 <= b.0 - pos { a } else { b },
+                (Some(a), None) | (None, Some(a)) => a,
+                (None, None) => return None,
+            };
+            Some(DataInterpolata {
+                data,
+                confidenza: if in_sequenza { CONFIDENZA_SEQUENZA_UN_LATO } else { CONFIDENZA_CARTELLA_UN_LATO },
+                origine: format!("copied from {} (nearest dated in {})", nome(idx), dove),
+            })
+        }
+    }
+}
+
+// Vicini datati prima e dopo `pos` in una lista di posizioni ordinate
+fn vicini(pos: i64, datate: &[Vicino]) -> (Option<Vicino>, Option<Vicino>) {
+    let dopo = datate.partition_point(|(p, _, _)| *p <= pos);
+    let prima = datate[..dopo].iter().rev().find(|(p, _, _)| *p < pos).copied();
+    (prima, datate.get(dopo).copied())
+}
+
+// Calcola data_interpolata per tutte le foto senza data, prima per sequenza di
+// numerazione (stessa cartella e prefisso), poi per ordine di nome nella cartella.
+// Restituisce quante foto hanno ricevuto una data
+pub fn interpola_date(foto_list: &mut [FotoData]) -> usize {
+    let date: Vec<Option<DateTime<Utc>>> = foto_list.iter().map(data_nota).collect();
+
+    let mut cartelle: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+    let mut sequenze: HashMap<(PathBuf, String), Vec<(i64, usize)>> = HashMap::new();
+    for (idx, foto) in foto_list.iter().enumerate() {
+        let cartella = foto.path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        if let Some((prefisso, numero)) = numero_sequenza(&foto.nome_file) {
+            sequenze.entry((cartella.clone(), prefisso)).or_default().push((numero, idx));
+        }
+        cartelle.entry(cartella).or_default().push(idx);
+    }
+
+    let mut risultati: Vec<Option<DataInterpolata>> = vec![None; foto_list.len()];
+
+    for membri in sequenze.values_mut() {
+        membri.sort();
+        let datate: Vec<Vicino> = membri.iter()
+            .filter_map(|&(n, i)| date[i].map(|d| (n, d, i)))
+            .collect();
+        if datate.is_empty() {
+            continue;
+        }
+        for &(numero, idx) in membri.iter().filter(|(_, i)| date[*i].is_none()) {
+            let (prima, dopo) = vicini(numero, &datate);
+            risultati[idx] = interpola(numero, prima, dopo, foto_list, true);
+        }
+    }
+
+    for membri in cartelle.values_mut() {
+        membri.sort_by(|&a, &b| foto_list[a].nome_file.cmp(&foto_list[b].nome_file));
+        let datate: Vec<Vicino> = membri.iter().enumerate()
+            .filter_map(|(pos, &i)| date[i].map(|d| (pos as i64, d, i)))
+            .collect();
+        if datate.is_empty() {
+            continue;
+        }
+        for (pos, &idx) in membri.iter().enumerate() {
+            if date[idx].is_none() && risultati[idx].is_none() {
+                let (prima, dopo) = vicini(pos as i64, &datate);
+                risultati[idx] = interpola(pos as i64, prima, dopo, foto_list, false);
+            }
+        }
+    }
+
+    let mut interpolate = 0;
+    for (foto, risultato) in foto_list.iter_mut().zip(risultati) {
+        interpolate += risultato.is_some() as usize;
+        foto.data_interpolata = risultato;
+    }
+    interpolate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numero_sequenza_ultimo_gruppo_di_cifre() {
+        assert_eq!(numero_sequenza("DSC_0101.JPG"), Some(("DSC_".to_string(), 101)));
+        assert_eq!(numero_sequenza("IMG_20190714_0003.jpg"), Some(("IMG_20190714_".to_string(), 3)));
+        assert_eq!(numero_sequenza("0042.jpg"), Some((String::new(), 42)));
+        assert_eq!(numero_sequenza("foto.jpg"), None);
+    }
+
+    #[test]
+    fn numero_sequenza_nome_non_ascii() {
+        assert_eq!(numero_sequenza("Città2.jpg"), Some(("Città".to_string(), 2)));
+        assert_eq!(numero_sequenza("日本12.jpg"), Some(("日本".to_string(), 12)));
+        assert_eq!(numero_sequenza("Città.jpg"), None);
+    }
+}
@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Registro delle operazioni eseguite, una riga JSON per operazione.
+// Ogni esecuzione (rinomina, scrittura, ...) ha il suo file, così si può annullare
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "tipo", rename_all = "snake_case")]
+pub enum VoceJournal {
+    Rinomina { da: PathBuf, a: PathBuf },
+    Spostamento { da: PathBuf, a: PathBuf },
+    Copia { da: PathBuf, a: PathBuf },
+    Collegamento { da: PathBuf, a: PathBuf }, // hardlink
+    // Date scritte in una foto (o nel suo sidecar .xmp), con i valori precedenti
+    Scrittura {
+        path: PathBuf,
+        sidecar: Option<PathBuf>,
+        sidecar_creato: bool,
+        precedenti: Vec<(String, Option<chrono::DateTime<chrono::Utc>>)>,
+        date_file_precedenti: Vec<(PathBuf, i64)>, // mtime in secondi Unix
+        // Descrizione, persone, preferita e GPS scritti senza -all=, con i valori di prima
+        // (vuoto se il tag non c'era)
+        #[serde(default)]
+        tag_precedenti: Vec<(String, Vec<String>)>,
+    },
+    // Copia di un file fatta prima di scriverlo (registrata prima della Scrittura)
+    Backup { originale: PathBuf, copia: PathBuf },
+}
+
+pub struct Journal {
+    path: PathBuf,
+    file: Mutex<fs::File>,
+}
+
+pub fn dir_journal() -> Option<PathBuf> {
+    crate::percorsi::dir_dati().map(|d| d.join("journal"))
+}
+
+impl Journal {
+    pub fn nuovo(operazione: &str) -> Result<Self, String> {
+        let dir = dir_journal().ok_or("Impossibile determinare la cartella del journal")?;
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Errore creazione cartella journal {}: {}", dir.display(), e))?;
+
+        let nome = format!("{}-{}.jsonl", chrono::Local::now().format("%Y%m%d-%H%M%S%.3f"), operazione);
+        let path = dir.join(nome);
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Errore apertura journal {}: {}", path.display(), e))?;
+
+        Ok(Self { path, file: Mutex::new(file) })
+    }
+
+    // Accoda a un journal esistente (ripresa di una scrittura interrotta)
+    pub fn riapri(path: &Path) -> Result<Self, String> {
+        let file = fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Errore apertura journal {}: {}", path.display(), e))?;
+        Ok(Self { path: path.to_path_buf(), file: Mutex::new(file) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Scrive subito su disco: se il programma si interrompe il journal resta valido
+    pub fn registra(&self, voce: &VoceJournal) -> Result<(), String> {
+        let riga = serde_json::to_string(voce).map_err(|e| e.to_string())?;
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", riga)
+            .and_then(|_| file.flush())
+            .map_err(|e| format!("Errore scrittura journal {}: {}", self.path.display(), e))
+    }
+}
+
+// Journal esistenti, dal più recente
+pub fn elenca_journal() -> Vec<PathBuf> {
+    let dir = match dir_journal() {
+        Some(d) => d,
+        None => return Vec::new(),
+    };
+    let mut journal: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().map(|e| e == "jsonl").unwrap_or(false))
+            .collect())
+        .unwrap_or_default();
+    // Il nome inizia con il timestamp, quindi l'ordine alfabetico è cronologico
+    journal.sort();
+    journal.reverse();
+    journal
+}
+
+pub fn leggi_journal(path: &Path) -> Result<Vec<VoceJournal>, String> {
+    let file = fs::File::open(path)
+        .map_err(|e| format!("Errore apertura journal {}: {}", path.display(), e))?;
+    let mut voci = Vec::new();
+    for riga in std::io::BufReader::new(file).lines() {
+        let riga = riga.map_err(|e| e.to_string())?;
+        if riga.trim().is_empty() {
+            continue;
+        }
+        // Una riga troncata (interruzione durante la scrittura) viene ignorata
+        if let Ok(voce) = serde_json::from_str(&riga) {
+            voci.push(voce);
+        }
+    }
+    Ok(voci)
+}
+
+// Come fs::rename, ma funziona anche tra filesystem diversi (copia + cancellazione)
+pub fn sposta_file(da: &Path, a: &Path) -> Result<(), String> {
+    if fs::rename(da, a).is_ok() {
+        return Ok(());
+    }
+    fs::copy(da, a)
+        .and_then(|_| fs::remove_file(da))
+        .map_err(|e| format!("Errore spostamento {} -> {}: {}", da.display(), a.display(), e))
+}
+
+// Annulla le operazioni in ordine inverso e toglie dalla cache di scansione i file
+// toccati. Restituisce (annullate, errori)
+pub fn annulla_journal(path: &Path, cache: Option<&mut crate::cache_scansione::CacheScansione>) -> Result<(usize, Vec<String>), String> {
+    let voci = leggi_journal(path)?;
+    let mut annullate = 0;
+    let mut errori = Vec::new();
+    let mut toccati: Vec<PathBuf> = Vec::new();
+    let mut fallite: Vec<usize> = Vec::new();
+
+    // Con una copia ancora presente il file torna com'era, anche nei tag che -all= ha cancellato
+    let copie: std::collections::HashMap<&Path, &Path> = voci.iter()
+        .filter_map(|voce| match voce {
+            VoceJournal::Backup { originale, copia } if copia.exists() => Some((originale.as_path(), copia.as_path())),
+            _ => None,
+        })
+        .collect();
+
+    for (indice, voce) in voci.iter().enumerate().rev() {
+        let risultato = match voce {
+            VoceJournal::Rinomina { da, a } | VoceJournal::Spostamento { da, a } => {
+                if da.exists() {
+                    Err(format!("{} esiste già, non sovrascritto", da.display()))
+                } else {
+                    if let Some(dir) = da.parent() {
+                        let _ = fs::create_dir_all(dir);
+                    }
+                    sposta_file(a, da)
+                }
+            }
+            VoceJournal::Copia { da, a } | VoceJournal::Collegamento { da, a } => {
+                // Si cancella la copia solo se l'originale è ancora al suo posto
+                if !da.exists() {
+                    Err(format!("{} non esiste più, copia {} mantenuta", da.display(), a.display()))
+                } else {
+                    fs::remove_file(a)
+                        .map_err(|e| format!("Errore cancellazione {}: {}", a.display(), e))
+                }
+            }
+            VoceJournal::Scrittura { path, sidecar, sidecar_creato, precedenti, date_file_precedenti, tag_precedenti } => {
+                let scritto = if *sidecar_creato { None } else { sidecar.as_deref().or(Some(path.as_path())) };
+                match scritto.and_then(|s| copie.get(s).map(|c| (s, *c))) {
+                    Some((scritto, copia)) => crate::backup::ripristina(copia, scritto)
+                        .and_then(|_| crate::scrittura::annulla_scrittura(path, None, false, &[], &[], date_file_precedenti)),
+                    None => crate::scrittura::annulla_scrittura(path, sidecar.as_deref(), *sidecar_creato, precedenti,
+                                                                 tag_precedenti, date_file_precedenti),
+                }
+            }
+            // Le copie restano: si cancellano con la pulizia dei backup
+            VoceJournal::Backup { .. } => continue,
+        };
+        toccati.extend(match voce {
+            VoceJournal::Scrittura { path, .. } => crate::scrittura::file_della_foto(path),
+            VoceJournal::Rinomina { da, a } | VoceJournal::Spostamento { da, a }
+            | VoceJournal::Copia { da, a } | VoceJournal::Collegamento { da, a } => vec![da.clone(), a.clone()],
+            VoceJournal::Backup { .. } => Vec::new(),
+        });
+        match risultato {
+            Ok(()) => annullate += 1,
+            Err(e) => {
+                errori.push(e);
+                fallite.push(indice);
+            }
+        }
+    }
+
+    if let Some(cache) = cache {
+        cache.rimuovi_tutti(toccati.iter().map(PathBuf::as_path));
+    }
+
+    if errori.is_empty() {
+        // Journal annullato: rinominato per non riapplicare l'annullamento
+        let mut annullato = path.as_os_str().to_os_string();
+        annullato.push(".undone");
+        let _ = fs::rename(path, PathBuf::from(annullato));
+    } else {
+        // Restano solo le operazioni fallite, da riprovare con un altro annullamento,
+        // con le copie di backup dei file che riguardano
+        let scritti: std::collections::HashSet<&Path> = fallite.iter()
+            .filter_map(|&i| match &voci[i] {
+                VoceJournal::Scrittura { path, sidecar, .. } => Some([Some(path.as_path()), sidecar.as_deref()]),
+                _ => None,
+            })
+            .flatten()
+            .flatten()
+            .collect();
+        let rimaste: Vec<&VoceJournal> = voci.iter().enumerate()
+            .filter(|(i, voce)| match voce {
+                VoceJournal::Backup { originale, .. } => scritti.contains(originale.as_path()),
+                _ => fallite.contains(i),
+            })
+            .map(|(_, voce)| voce)
+            .collect();
+        riscrivi_journal(path, &rimaste)?;
+    }
+
+    Ok((annullate, errori))
+}
+
+// Sostituisce il contenuto del journal, passando da un file temporaneo
+fn riscrivi_journal(path: &Path, voci: &[&VoceJournal]) -> Result<(), String> {
+    let mut contenuto = String::new();
+    for voce in voci {
+        contenuto.push_str(&serde_json::to_string(voce).map_err(|e| e.to_string())?);
+        contenuto.push('\n');
+    }
+    let mut temporaneo = path.as_os_str().to_os_string();
+    temporaneo.push(".tmp");
+    let temporaneo = PathBuf::from(temporaneo);
+    fs::write(&temporaneo, contenuto)
+        .and_then(|_| fs::rename(&temporaneo, path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&temporaneo);
+            format!("Errore scrittura journal {}: {}", path.display(), e)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annullamento_con_errori_tiene_solo_le_voci_fallite() {
+        let dir = std::env::temp_dir().join(format!("corrigi-exif-journal-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = |nome: &str| dir.join(nome);
+        fs::write(file("nuovo_1.jpg"), "1").unwrap();
+        fs::write(file("nuovo_2.jpg"), "2").unwrap();
+        fs::write(file("vecchio_2.jpg"), "occupato").unwrap(); // Il nome originale è stato ripreso
+
+        let path = file("prova-rinomina.jsonl");
+        fs::write(&path, "").unwrap();
+        let journal = Journal::riapri(&path).unwrap();
+        journal.registra(&VoceJournal::Rinomina { da: file("vecchio_1.jpg"), a: file("nuovo_1.jpg") }).unwrap();
+        journal.registra(&VoceJournal::Rinomina { da: file("vecchio_2.jpg"), a: file("nuovo_2.jpg") }).unwrap();
+        drop(journal);
+
+        let (annullate, errori) = annulla_journal(&path, None).unwrap();
+        assert_eq!((annullate, errori.len()), (1, 1));
+        assert!(file("vecchio_1.jpg").exists());
+        let rimaste = leggi_journal(&path).unwrap();
+        assert!(matches!(rimaste.as_slice(), [VoceJournal::Rinomina { a, .. }] if *a == file("nuovo_2.jpg")));
+
+        // Tolto l'ostacolo, il secondo annullamento riesce e il journal viene chiuso
+        fs::remove_file(file("vecchio_2.jpg")).unwrap();
+        assert_eq!(annulla_journal(&path, None).unwrap(), (1, Vec::new()));
+        assert!(!path.exists());
+        assert!(file("vecchio_2.jpg").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
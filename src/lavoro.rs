@@ -0,0 +1,75 @@
+use std::sync::{Arc, Condvar, Mutex};
+
+// Pausa e annullamento di un lavoro lungo (scansione, scrittura) da un altro thread.
+// Il lavoro li controlla prima di ogni file, così nessun file resta scritto a metà:
+// quelli già in corso finiscono, gli altri aspettano o vengono saltati
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatoLavoro {
+    InCorso,
+    InPausa,
+    Annullato,
+}
+
+#[derive(Debug, Clone)]
+pub struct ControlloLavoro {
+    stato: Arc<(Mutex<StatoLavoro>, Condvar)>,
+}
+
+impl Default for ControlloLavoro {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ControlloLavoro {
+    pub fn new() -> Self {
+        ControlloLavoro { stato: Arc::new((Mutex::new(StatoLavoro::InCorso), Condvar::new())) }
+    }
+
+    pub fn stato(&self) -> StatoLavoro {
+        *self.stato.0.lock().unwrap()
+    }
+
+    fn imposta(&self, da: &[StatoLavoro], a: StatoLavoro) {
+        let (stato, segnale) = &*self.stato;
+        let mut stato = stato.lock().unwrap();
+        if da.contains(&stato) {
+            *stato = a;
+            segnale.notify_all();
+        }
+    }
+
+    pub fn pausa(&self) {
+        self.imposta(&[StatoLavoro::InCorso], StatoLavoro::InPausa);
+    }
+
+    pub fn riprendi(&self) {
+        self.imposta(&[StatoLavoro::InPausa], StatoLavoro::InCorso);
+    }
+
+    // Definitivo: un lavoro annullato non riparte
+    pub fn annulla(&self) {
+        self.imposta(&[StatoLavoro::InCorso, StatoLavoro::InPausa], StatoLavoro::Annullato);
+    }
+
+    pub fn is_annullato(&self) -> bool {
+        self.stato() == StatoLavoro::Annullato
+    }
+
+    // Da chiamare prima di ogni file: aspetta finché il lavoro è in pausa,
+    // false se è stato annullato
+    pub fn continua(&self) -> bool {
+        let (stato, segnale) = &*self.stato;
+        let mut stato = stato.lock().unwrap();
+        while *stato == StatoLavoro::InPausa {
+            stato = segnale.wait(stato).unwrap();
+        }
+        *stato == StatoLavoro::InCorso
+    }
+}
+
+// Per i lavori senza controllo (CLI, letture brevi)
+pub fn puo_continuare(controllo: Option<&ControlloLavoro>) -> bool {
+    controllo.is_none_or(|c| c.continua())
+}
@@ -0,0 +1,1035 @@
+pub mod archivio;
+pub mod backup;
+pub mod cache_scansione;
+pub mod cli;
+pub mod cronologia;
+pub mod date_manuali;
+pub mod duplicati;
+pub mod errori_scrittura;
+pub mod esportazione;
+pub mod exiftool;
+pub mod filtri;
+pub mod gui;
+pub mod impostazioni;
+pub mod interpolazione;
+pub mod journal;
+pub mod lavoro;
+pub mod metadati;
+pub mod miniature;
+pub mod organizza;
+pub mod orologio;
+pub mod percorsi;
+pub mod rinomina;
+pub mod scostamento;
+pub mod scrittura;
+pub mod sessione;
+pub mod sidecar;
+pub mod verifica;
+
+use exif::{In, Tag, Value};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc, NaiveDateTime, Datelike, Timelike};
+use rayon::prelude::*;
+use metadati::{MetadatiGoogle, PosizioneGps};
+
+// Serializzata così com'è nel report JSON Lines (vedi esportazione.rs)
+#[derive(Debug, Clone, Serialize)]
+pub struct FotoData {
+    #[allow(dead_code)]
+    pub path: PathBuf,
+    pub nome_file: String,
+    #[allow(dead_code)]
+    pub anno_nome: Option<i32>,
+    pub data_nome: Option<(i32, u32, u32)>, // (anno, mese, giorno)
+    pub data_json: Option<DateTime<Utc>>, // photoTakenTime dal JSON
+    pub data_json_creation: Option<DateTime<Utc>>, // creationTime dal JSON
+    pub exif_datetime_original: Option<DateTime<Utc>>,
+    pub exif_create_date: Option<DateTime<Utc>>,
+    #[allow(dead_code)]
+    pub exif_modify_date: Option<DateTime<Utc>>,
+    pub proposta_datetime_original: Option<DateTime<Utc>>,
+    pub proposta_create_date: Option<DateTime<Utc>>,
+    pub proposta_modify_date: Option<DateTime<Utc>>,
+    pub strategia_datetime_original: String,
+    pub strategia_create_date: String,
+    pub strategia_modify_date: String,
+    pub incongruenze: Vec<String>, // Lista di incongruenze rilevate
+    pub gravita_incongruenza: i64, // Differenza in giorni (0 = nessuna incongruenza)
+    #[allow(dead_code)]
+    pub jpg_associato_usato: bool, // True se l'EXIF è stato letto da un JPG associato (per RAW)
+    pub metadati_json: Option<MetadatiGoogle>, // Descrizione, persone e preferiti dal JSON
+    pub posizione_json: Option<PosizioneGps>, // geoData dal JSON
+    pub sidecar_xmp: Option<PathBuf>, // Sidecar .xmp esistente (darktable/Lightroom)
+    pub xmp_datetime_original: Option<DateTime<Utc>>, // exif:DateTimeOriginal dal sidecar
+    pub xmp_create_date: Option<DateTime<Utc>>, // xmp:CreateDate dal sidecar
+    pub fotocamera: Option<String>, // EXIF Make + Model
+    pub gruppo_duplicati: Option<usize>, // Gruppo di copie della stessa foto (vedi duplicati.rs)
+    pub origine_archivio: Option<archivio::OrigineArchivio>, // Foto letta da un archivio Takeout non estratto
+    pub data_manuale: Option<date_manuali::DataManuale>, // DateTimeOriginal inserita a mano: vince su ogni strategia
+    pub data_manuale_create_date: Option<date_manuali::DataManuale>, // CreateDate inserita a mano
+    pub data_interpolata: Option<interpolazione::DataInterpolata>, // Dedotta dai vicini, per le foto senza nessuna data
+}
+
+impl FotoData {
+    // Proposte, incongruenze e gravità con le strategie scelte per i due tag
+    pub fn applica_strategie(&mut self, strategia_datetime_original: &str, strategia_create_date: &str) {
+        self.strategia_datetime_original = strategia_datetime_original.to_string();
+        self.strategia_create_date = strategia_create_date.to_string();
+        
+        self.proposta_datetime_original = calcola_proposta_con_strategia(self, &self.strategia_datetime_original);
+        self.proposta_create_date = calcola_proposta_con_strategia(self, &self.strategia_create_date);
+        if let Some(ref manuale) = self.data_manuale {
+            self.proposta_datetime_original = Some(manuale.data);
+        }
+        if let Some(ref manuale) = self.data_manuale_create_date {
+            self.proposta_create_date = Some(manuale.data);
+        }
+        
+        self.incongruenze = rileva_incongruenze(self);
+        self.gravita_incongruenza = calcola_gravita_incongruenza(self);
+    }
+    
+    pub fn ha_data_manuale(&self) -> bool {
+        self.data_manuale.is_some() || self.data_manuale_create_date.is_some()
+    }
+    
+    // Data interpolata se è lei la proposta per DateTimeOriginal (strategia json_interpolato)
+    pub fn proposta_interpolata(&self) -> Option<&interpolazione::DataInterpolata> {
+        self.interpolata_con(&self.strategia_datetime_original, self.data_manuale.as_ref())
+    }
+    
+    pub fn proposta_create_date_interpolata(&self) -> Option<&interpolazione::DataInterpolata> {
+        self.interpolata_con(&self.strategia_create_date, self.data_manuale_create_date.as_ref())
+    }
+    
+    fn interpolata_con(&self, strategia: &str, manuale: Option<&date_manuali::DataManuale>) -> Option<&interpolazione::DataInterpolata> {
+        if manuale.is_some() || strategia != "json_interpolato"
+            || self.data_json.is_some() || self.data_json_creation.is_some() || self.data_nome.is_some() {
+            return None;
+        }
+        self.data_interpolata.as_ref()
+    }
+    
+    pub fn is_foto_1900(&self) -> bool {
+        // Foto senza metadati: proposta è 1900-01-01
+        if let Some(dt) = self.proposta_datetime_original {
+            dt.year() == 1900 && dt.month() == 1 && dt.day() == 1
+        } else {
+            false
+        }
+    }
+    
+    pub fn is_foto_whatsapp(&self) -> bool {
+        // Foto IMG_* da WhatsApp
+        self.nome_file.starts_with("IMG_") || self.nome_file.starts_with("IMG-")
+    }
+    
+    pub fn is_foto_raw(&self) -> bool {
+        // Foto RAW (ORF/NEF) - controlla l'estensione
+        if let Some(ext) = self.path.extension() {
+            if let Some(ext_str) = ext.to_str() {
+                matches!(ext_str.to_lowercase().as_str(), "orf" | "nef")
+            } else {
+                false
+            }
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GooglePhotoJson {
+    #[serde(rename = "photoTakenTime")]
+    photo_taken_time: Option<PhotoTime>,
+    #[serde(rename = "creationTime")]
+    creation_time: Option<PhotoTime>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PhotoTime {
+    timestamp: String,
+}
+
+pub fn estrai_anno_da_nome(nome_file: &str) -> Option<(i32, u32, u32)> {
+    // Pattern: "2002_" all'inizio
+    let re = Regex::new(r"^(\d{4})_").ok()?;
+    if let Some(caps) = re.captures(nome_file) {
+        if let Ok(anno) = caps[1].parse::<i32>() {
+            if (1900..=2100).contains(&anno) {
+                return Some((anno, 1, 1));
+            }
+        }
+    }
+    
+    // Pattern: "IMG_YYYYMMDD" o "IMG-YYYYMMDD" (WhatsApp e altre app)
+    let re = Regex::new(r"IMG[_-](\d{4})(\d{2})(\d{2})").ok()?;
+    if let Some(caps) = re.captures(nome_file) {
+        if let Ok(anno) = caps[1].parse::<i32>() {
+            if let Ok(mese) = caps[2].parse::<u32>() {
+                if let Ok(giorno) = caps[3].parse::<u32>() {
+                    if (1900..=2100).contains(&anno) && (1..=12).contains(&mese) && (1..=31).contains(&giorno) {
+                        return Some((anno, mese, giorno));
+                    }
+                }
+            }
+        }
+    }
+    
+    // Pattern: "20050806" (YYYYMMDD)
+    let re = Regex::new(r"(\d{4})(\d{2})(\d{2})").ok()?;
+    if let Some(caps) = re.captures(nome_file) {
+        if let Ok(anno) = caps[1].parse::<i32>() {
+            if let Ok(mese) = caps[2].parse::<u32>() {
+                if let Ok(giorno) = caps[3].parse::<u32>() {
+                    if (1900..=2100).contains(&anno) && (1..=12).contains(&mese) && (1..=31).contains(&giorno) {
+                        return Some((anno, mese, giorno));
+                    }
+                }
+            }
+        }
+    }
+    
+    // Pattern: "24082009" (DDMMYYYY)
+    let re = Regex::new(r"(\d{2})(\d{2})(\d{4})").ok()?;
+    if let Some(caps) = re.captures(nome_file) {
+        if let Ok(giorno) = caps[1].parse::<u32>() {
+            if let Ok(mese) = caps[2].parse::<u32>() {
+                if let Ok(anno) = caps[3].parse::<i64>() {
+                    if (1900..=2100).contains(&(anno as i32)) && (1..=12).contains(&mese) && (1..=31).contains(&giorno) {
+                        return Some((anno as i32, mese, giorno));
+                    }
+                }
+            }
+        }
+    }
+    
+    // Pattern: "1987-07" o "1987_07" (anno-mese)
+    let re = Regex::new(r"(\d{4})[_-](\d{1,2})").ok()?;
+    if let Some(caps) = re.captures(nome_file) {
+        if let Ok(anno) = caps[1].parse::<i32>() {
+            if let Ok(mese) = caps[2].parse::<u32>() {
+                if (1900..=2100).contains(&anno) && (1..=12).contains(&mese) {
+                    return Some((anno, mese, 1)); // Usa giorno 1 come default
+                }
+            }
+        }
+    }
+    
+    // Pattern: "07-1987" o "7-1987" (mese-anno)
+    let re = Regex::new(r"(\d{1,2})[_-](\d{4})").ok()?;
+    if let Some(caps) = re.captures(nome_file) {
+        if let Ok(mese) = caps[1].parse::<u32>() {
+            if let Ok(anno) = caps[2].parse::<i32>() {
+                if (1900..=2100).contains(&anno) && (1..=12).contains(&mese) {
+                    return Some((anno, mese, 1)); // Usa giorno 1 come default
+                }
+            }
+        }
+    }
+    
+    // Pattern: solo anno a 4 cifre nel nome (es: "Foppolo 1987.jpg", "Sigurtà 1987.jpg")
+    // Questo pattern viene per ultimo, dopo tutti i pattern più specifici
+    // Cerca un anno valido preceduto da spazio o altro carattere non numerico
+    let re = Regex::new(r"[^\d](\d{4})(?:\s|\.|$|[^\d])").ok()?;
+    if let Some(caps) = re.captures(nome_file) {
+        if let Ok(anno) = caps[1].parse::<i32>() {
+            if (1900..=2100).contains(&anno) {
+                return Some((anno, 1, 1));
+            }
+        }
+    }
+    
+    None
+}
+
+pub fn trova_file_jpg_associato(foto_path: &Path) -> Option<PathBuf> {
+    trova_file_jpg_associato_con(foto_path, |p| p.exists())
+}
+
+// Come trova_file_jpg_associato, con un test di esistenza diverso (es. voci di un archivio)
+pub(crate) fn trova_file_jpg_associato_con(foto_path: &Path, esiste: impl Fn(&Path) -> bool) -> Option<PathBuf> {
+    // Per file RAW (ORF, NEF), cerca un JPG associato con lo stesso nome base
+    let ext = foto_path.extension()?.to_str()?.to_lowercase();
+    if !matches!(ext.as_str(), "orf" | "nef") {
+        return None; // Non è un file RAW
+    }
+    
+    let directory = foto_path.parent()?;
+    let base_name = foto_path.file_stem()?.to_str()?;
+    
+    // Cerca JPG con lo stesso nome base
+    let possibili_jpg = vec![
+        format!("{}.jpg", base_name),
+        format!("{}.JPG", base_name),
+        format!("{}.jpeg", base_name),
+        format!("{}.JPEG", base_name),
+    ];
+    
+    for nome_jpg in possibili_jpg {
+        let jpg_path = directory.join(&nome_jpg);
+        if esiste(&jpg_path) {
+            return Some(jpg_path);
+        }
+    }
+    
+    None
+}
+
+pub fn trova_file_json(foto_path: &Path) -> Option<PathBuf> {
+    trova_file_json_con(foto_path, |p| p.exists())
+}
+
+pub(crate) fn trova_file_json_con(foto_path: &Path, esiste: impl Fn(&Path) -> bool) -> Option<PathBuf> {
+    let directory = foto_path.parent()?;
+    let base_name = foto_path.file_stem()?.to_str()?;
+    let nome_file = foto_path.file_name()?.to_str()?;
+    
+    let possibili_nomi = vec![
+        format!("{}.supplemental-metadata.json", nome_file),
+        format!("{}.supplemental-metadata.json", base_name),
+        format!("{}.supplemental.json", nome_file),
+        format!("{}.supplemental.json", base_name),
+        format!("{}.supplemental-met.json", nome_file),
+        format!("{}.supplemental-met.json", base_name),
+        format!("{}.supplemental-me.json", nome_file),
+        format!("{}.supplemental-me.json", base_name),
+        format!("{}.supplemental-metad.json", nome_file),
+        format!("{}.supplemental-metad.json", base_name),
+        format!("{}.supplementa.json", nome_file),
+        format!("{}.supplementa.json", base_name),
+        format!("{}.supplemen.json", nome_file),
+        format!("{}.supplemen.json", base_name),
+    ];
+    
+    for nome_json in possibili_nomi {
+        let json_path = directory.join(&nome_json);
+        if esiste(&json_path) {
+            return Some(json_path);
+        }
+    }
+    
+    None
+}
+
+pub fn leggi_data_json(json_path: &Path) -> Option<DateTime<Utc>> {
+    let content = fs::read_to_string(json_path).ok()?;
+    let json: GooglePhotoJson = serde_json::from_str(&content).ok()?;
+    
+    // Preferisci photoTakenTime, altrimenti creationTime
+    if let Some(photo_time) = json.photo_taken_time {
+        if let Ok(timestamp) = photo_time.timestamp.parse::<i64>() {
+            return DateTime::from_timestamp(timestamp, 0);
+        }
+    }
+    
+    if let Some(creation_time) = json.creation_time {
+        if let Ok(timestamp) = creation_time.timestamp.parse::<i64>() {
+            return DateTime::from_timestamp(timestamp, 0);
+        }
+    }
+    
+    None
+}
+
+pub fn leggi_data_json_completo(json_path: &Path) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+    match leggi_json(json_path) {
+        Some(valore) => leggi_data_json_da_valore(&valore),
+        None => (None, None),
+    }
+}
+
+// JSON letto e interpretato una volta sola: date, metadati e posizione si ricavano
+// tutti dallo stesso valore
+pub fn leggi_json(json_path: &Path) -> Option<serde_json::Value> {
+    serde_json::from_str(&fs::read_to_string(json_path).ok()?).ok()
+}
+
+// (photoTakenTime, creationTime) da un JSON già interpretato
+pub fn leggi_data_json_da_valore(valore: &serde_json::Value) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+    let json = match GooglePhotoJson::deserialize(valore) {
+        Ok(j) => j,
+        Err(_) => return (None, None),
+    };
+    
+    let photo_taken = json.photo_taken_time.and_then(|pt| {
+        pt.timestamp.parse::<i64>().ok()
+            .and_then(|ts| {
+                // Usa from_timestamp che preserva l'ora completa
+                DateTime::from_timestamp(ts, 0)
+            })
+    });
+    
+    let creation = json.creation_time.and_then(|ct| {
+        ct.timestamp.parse::<i64>().ok()
+            .and_then(|ts| {
+                // Usa from_timestamp che preserva l'ora completa
+                DateTime::from_timestamp(ts, 0)
+            })
+    });
+    
+    (photo_taken, creation)
+}
+
+pub fn leggi_exif_datetime(file_path: &Path, tag: Tag) -> Option<DateTime<Utc>> {
+    let file = fs::File::open(file_path).ok()?;
+    let mut bufreader = std::io::BufReader::new(&file);
+    let exif = exif::Reader::new();
+    let exif_data = exif.read_from_container(&mut bufreader).ok()?;
+    
+    data_da_exif(&exif_data, tag)
+}
+
+fn data_da_exif(exif_data: &exif::Exif, tag: Tag) -> Option<DateTime<Utc>> {
+    if let Some(field) = exif_data.get_field(tag, In::PRIMARY) {
+        if let Value::Ascii(ref vec) = field.value {
+            if !vec.is_empty() {
+                let date_str = String::from_utf8_lossy(&vec[0]);
+                // Formato: "2002:01:01 12:00:00"
+                if let Ok(dt) = NaiveDateTime::parse_from_str(&date_str, "%Y:%m:%d %H:%M:%S") {
+                    return Some(DateTime::from_naive_utc_and_offset(dt, Utc));
+                }
+            }
+        }
+    }
+    
+    None
+}
+
+fn testo_da_exif(exif_data: &exif::Exif, tag: Tag) -> Option<String> {
+    let field = exif_data.get_field(tag, In::PRIMARY)?;
+    if let Value::Ascii(ref vec) = field.value {
+        let testo = String::from_utf8_lossy(vec.first()?).trim_matches(char::from(0)).trim().to_string();
+        if !testo.is_empty() {
+            return Some(testo);
+        }
+    }
+    
+    None
+}
+
+fn componi_fotocamera(make: Option<String>, model: Option<String>) -> Option<String> {
+    match (make, model) {
+        // Molti produttori ripetono la marca nel modello ("NIKON CORPORATION" + "NIKON D90")
+        (Some(make), Some(model)) => {
+            let marca = make.split_whitespace().next().unwrap_or(&make).to_lowercase();
+            if model.to_lowercase().starts_with(&marca) {
+                Some(model)
+            } else {
+                Some(format!("{} {}", make, model))
+            }
+        }
+        (None, Some(model)) => Some(model),
+        (Some(make), None) => Some(make),
+        (None, None) => None,
+    }
+}
+
+// (DateTimeOriginal, CreateDate, ModifyDate)
+pub type CampiDataExif = (Option<DateTime<Utc>>, Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+pub fn ottieni_tutti_campi_exif(foto_path: &Path) -> CampiDataExif {
+    leggi_exif_file(foto_path).0
+}
+
+// Date e fotocamera con una sola lettura dell'EXIF del file
+pub fn leggi_exif_file(foto_path: &Path) -> (CampiDataExif, Option<String>) {
+    let Ok(file) = fs::File::open(foto_path) else {
+        return ((None, None, None), None);
+    };
+    match exif::Reader::new().read_from_container(&mut std::io::BufReader::new(&file)) {
+        Ok(exif_data) => campi_e_fotocamera(&exif_data),
+        Err(_) => ((None, None, None), None),
+    }
+}
+
+// Date e fotocamera da un file già in memoria (es. letto da un archivio).
+// Per i JPEG basta l'inizio del file, dove si trova il segmento APP1
+pub fn leggi_exif_da_bytes(dati: &[u8]) -> (CampiDataExif, Option<String>) {
+    match exif::Reader::new().read_from_container(&mut std::io::Cursor::new(dati)) {
+        Ok(exif_data) => campi_e_fotocamera(&exif_data),
+        Err(_) => ((None, None, None), None),
+    }
+}
+
+fn campi_e_fotocamera(exif_data: &exif::Exif) -> (CampiDataExif, Option<String>) {
+    let campi = (
+        data_da_exif(exif_data, Tag::DateTimeOriginal),
+        data_da_exif(exif_data, Tag::DateTimeDigitized),
+        data_da_exif(exif_data, Tag::DateTime),
+    );
+    let fotocamera = componi_fotocamera(testo_da_exif(exif_data, Tag::Make), testo_da_exif(exif_data, Tag::Model));
+    (campi, fotocamera)
+}
+
+pub fn calcola_proposta_con_strategia(foto: &FotoData, strategia: &str) -> Option<DateTime<Utc>> {
+    match strategia {
+        "nome_file" => {
+            if let Some((anno, mese, giorno)) = foto.data_nome {
+                if let Ok(dt) = NaiveDateTime::parse_from_str(
+                    &format!("{:04}-{:02}-{:02} 12:00:00", anno, mese, giorno),
+                    "%Y-%m-%d %H:%M:%S"
+                ) {
+                    return Some(DateTime::from_naive_utc_and_offset(dt, Utc));
+                }
+            }
+        }
+        "json_photo_taken" => {
+            // Restituisce direttamente foto.data_json che contiene l'ora corretta dal JSON
+            return foto.data_json;
+        }
+        "json_creation" => {
+            return foto.data_json_creation;
+        }
+        "exif_attuale" => {
+            return foto.exif_datetime_original;
+        }
+        "xmp_sidecar" => {
+            return foto.xmp_datetime_original;
+        }
+        "nome_file_preferito" => {
+            // Questa strategia preferisce il nome file (con ora 12:00:00) al JSON
+            // ATTENZIONE: quando c'è un nome file con data, usa sempre 12:00:00 invece dell'ora dal JSON
+            if let Some((anno, mese, giorno)) = foto.data_nome {
+                if let Ok(dt) = NaiveDateTime::parse_from_str(
+                    &format!("{:04}-{:02}-{:02} 12:00:00", anno, mese, giorno),
+                    "%Y-%m-%d %H:%M:%S"
+                ) {
+                    return Some(DateTime::from_naive_utc_and_offset(dt, Utc));
+                }
+            }
+            // Se non c'è nome file, usa il JSON (che contiene l'ora corretta)
+            return foto.data_json;
+        }
+        "json_preferito" => {
+            // Preferisci photoTakenTime, altrimenti creationTime, altrimenti nome file
+            // Se non c'è nulla, usa 1900-01-01 come flag per foto da riclassificare
+            if let Some(dt) = foto.data_json {
+                return Some(dt);
+            }
+            if let Some(dt) = foto.data_json_creation {
+                return Some(dt);
+            }
+            if let Some((anno, mese, giorno)) = foto.data_nome {
+                if let Ok(dt) = NaiveDateTime::parse_from_str(
+                    &format!("{:04}-{:02}-{:02} 12:00:00", anno, mese, giorno),
+                    "%Y-%m-%d %H:%M:%S"
+                ) {
+                    return Some(DateTime::from_naive_utc_and_offset(dt, Utc));
+                }
+            }
+            // Nessuna fonte disponibile: usa 1900-01-01 come flag per foto da riclassificare manualmente
+            if let Ok(dt) = NaiveDateTime::parse_from_str("1900-01-01 00:00:00", "%Y-%m-%d %H:%M:%S") {
+                return Some(DateTime::from_naive_utc_and_offset(dt, Utc));
+            }
+        }
+        "json_interpolato" => {
+            // Come json_preferito, ma al posto del 1900-01-01 la data interpolata dai vicini
+            if foto.data_json.is_none() && foto.data_json_creation.is_none() && foto.data_nome.is_none() {
+                if let Some(ref interpolata) = foto.data_interpolata {
+                    return Some(interpolata.data);
+                }
+            }
+            return calcola_proposta_con_strategia(foto, "json_preferito");
+        }
+        _ => {}
+    }
+    
+    None
+}
+
+// Mantenuto per compatibilità
+pub fn calcola_proposta(foto: &FotoData) -> Option<DateTime<Utc>> {
+    calcola_proposta_con_strategia(foto, &foto.strategia_datetime_original)
+}
+
+pub fn rileva_incongruenze(foto: &FotoData) -> Vec<String> {
+    use chrono::Datelike;
+    let mut incongruenze = Vec::new();
+    
+    // Confronta EXIF DateTimeOriginal con anno nel filename
+    if let Some(exif_dt) = foto.exif_datetime_original {
+        let exif_anno = exif_dt.year();
+        
+        // Confronta con anno nel nome file
+        if let Some(anno_nome) = foto.anno_nome {
+            if exif_anno != anno_nome {
+                incongruenze.push(format!("EXIF anno {} ≠ filename anno {}", exif_anno, anno_nome));
+            }
+        }
+        
+        // Confronta con data nel JSON (photoTakenTime) - solo questo campo per il confronto
+        // Confronta solo la data (anno/mese/giorno), non l'ora, perché l'ora può differire
+        if let Some(json_dt) = foto.data_json {
+            let json_anno = json_dt.year();
+            let json_mese = json_dt.month();
+            let json_giorno = json_dt.day();
+            let exif_mese = exif_dt.month();
+            let exif_giorno = exif_dt.day();
+            
+            // Considera un'incongruenza solo se la differenza è di almeno 1 giorno
+            // (non considerare differenze di ore/minuti come incongruenze)
+            let exif_date = exif_dt.date_naive();
+            let json_date = json_dt.date_naive();
+            let diff_giorni = (exif_date - json_date).num_days().abs();
+            
+            if diff_giorni >= 1 {
+                incongruenze.push(format!("EXIF {:04}-{:02}-{:02} ≠ JSON photoTakenTime {:04}-{:02}-{:02} (differenza: {} giorni)", 
+                    exif_anno, exif_mese, exif_giorno,
+                    json_anno, json_mese, json_giorno,
+                    diff_giorni));
+            }
+        }
+    } else {
+        // EXIF mancante ma abbiamo dati da filename o JSON (solo photoTakenTime)
+        if foto.anno_nome.is_some() || foto.data_json.is_some() {
+            incongruenze.push("EXIF DateTimeOriginal mancante".to_string());
+        }
+    }
+    
+    // Data dedotta e non letta: da controllare
+    if let Some(interpolata) = foto.proposta_interpolata() {
+        incongruenze.push(format!("Data interpolata: {} (confidenza {:.0}%)",
+            interpolata.origine, interpolata.confidenza * 100.0));
+    }
+    
+    incongruenze
+}
+
+pub fn calcola_gravita_incongruenza(foto: &FotoData) -> i64 {
+    use chrono::NaiveDate;
+    
+    let mut max_diff_giorni = 0i64;
+    
+    // Confronta EXIF con data nel filename
+    if let Some(exif_dt) = foto.exif_datetime_original {
+        if let Some((anno_nome, mese_nome, giorno_nome)) = foto.data_nome {
+            // Crea una data dal filename
+            if let Some(data_nome) = NaiveDate::from_ymd_opt(anno_nome, mese_nome, giorno_nome) {
+                let exif_date = exif_dt.date_naive();
+                let diff = (exif_date - data_nome).num_days().abs();
+                max_diff_giorni = max_diff_giorni.max(diff);
+            }
+        }
+        
+        // Confronta EXIF con data nel JSON photoTakenTime (solo questo campo per il confronto)
+        // Usa solo la differenza in giorni (non considera differenze di ore/minuti)
+        if let Some(json_dt) = foto.data_json {
+            let exif_date = exif_dt.date_naive();
+            let json_date = json_dt.date_naive();
+            let diff = (exif_date - json_date).num_days().abs();
+            max_diff_giorni = max_diff_giorni.max(diff);
+        }
+    } else {
+        // EXIF mancante: non calcolare una differenza di giorni perché non c'è nulla da confrontare
+        // La gravità rimane 0 - l'incongruenza è solo "EXIF mancante", non una differenza temporale
+        // Se ci sono altre incongruenze (es. differenze tra JSON e filename), verranno calcolate sopra
+        // ma se l'EXIF è mancante, non possiamo calcolare una differenza con l'EXIF stesso
+        max_diff_giorni = 0;
+    }
+    
+    max_diff_giorni
+}
+
+// FotoData con i dati già letti; JSON e sidecar vanno aggiunti dal chiamante
+// prima di completa_foto
+pub(crate) fn nuova_foto(foto_path: PathBuf, exif: CampiDataExif, fotocamera: Option<String>) -> FotoData {
+    let nome_file = foto_path.file_name().unwrap().to_string_lossy().to_string();
+    
+    let data_nome = estrai_anno_da_nome(&nome_file);
+    let anno_nome = data_nome.map(|(a, _, _)| a);
+    let (exif_dt, exif_cd, exif_md) = exif;
+    
+    FotoData {
+        path: foto_path,
+        nome_file,
+        anno_nome,
+        data_nome,
+        data_json: None,
+        data_json_creation: None,
+        exif_datetime_original: exif_dt,
+        exif_create_date: exif_cd,
+        exif_modify_date: exif_md,
+        proposta_datetime_original: None,
+        proposta_create_date: None,
+        proposta_modify_date: None,
+        strategia_datetime_original: "nome_file_preferito".to_string(),
+        strategia_create_date: "nome_file_preferito".to_string(),
+        strategia_modify_date: "nome_file_preferito".to_string(),
+        incongruenze: Vec::new(),
+        gravita_incongruenza: 0,
+        jpg_associato_usato: false,
+        metadati_json: None,
+        posizione_json: None,
+        sidecar_xmp: None,
+        xmp_datetime_original: None,
+        xmp_create_date: None,
+        fotocamera,
+        gruppo_duplicati: None,
+        origine_archivio: None,
+        data_manuale: None,
+        data_manuale_create_date: None,
+        data_interpolata: None,
+    }
+}
+
+// Proposte iniziali con le strategie di default, incongruenze e gravità
+pub(crate) fn completa_foto(foto: &mut FotoData) {
+    foto.proposta_datetime_original = calcola_proposta_con_strategia(foto, &foto.strategia_datetime_original);
+    foto.proposta_create_date = calcola_proposta_con_strategia(foto, &foto.strategia_create_date);
+    foto.proposta_modify_date = calcola_proposta_con_strategia(foto, &foto.strategia_modify_date);
+    
+    foto.incongruenze = rileva_incongruenze(foto);
+    foto.gravita_incongruenza = calcola_gravita_incongruenza(foto);
+}
+
+// Ciò che la scansione legge dai file di una foto (la foto o il JPG della coppia RAW+JPG,
+// il JSON, il sidecar): il resto di FotoData si ricalcola. È quanto salva la cache di scansione
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SorgentiFoto {
+    pub exif: CampiDataExif,
+    pub fotocamera: Option<String>,
+    pub jpg_associato: Option<PathBuf>,
+    pub json: Option<PathBuf>,
+    pub data_json: Option<DateTime<Utc>>,
+    pub data_json_creation: Option<DateTime<Utc>>,
+    pub metadati_json: Option<MetadatiGoogle>,
+    pub posizione_json: Option<PosizioneGps>,
+    pub sidecar_xmp: Option<PathBuf>,
+    pub xmp_datetime_original: Option<DateTime<Utc>>,
+    pub xmp_create_date: Option<DateTime<Utc>>,
+}
+
+impl SorgentiFoto {
+    // File letti oltre alla foto
+    pub fn compagni(&self) -> impl Iterator<Item = &PathBuf> {
+        [&self.jpg_associato, &self.json, &self.sidecar_xmp].into_iter().flatten()
+    }
+}
+
+pub fn leggi_sorgenti(foto_path: &Path) -> SorgentiFoto {
+    // Per file RAW (ORF, NEF), cerca un JPG associato e leggi EXIF da quello
+    // Spesso le fotocamere creano sia RAW che JPG, e l'EXIF è solo nel JPG
+    let jpg_associato = trova_file_jpg_associato(foto_path);
+    let file_per_exif = jpg_associato.as_deref().unwrap_or(foto_path);
+    let (exif, fotocamera) = leggi_exif_file(file_per_exif);
+    
+    let json = trova_file_json(foto_path);
+    let ((data_json, data_json_creation), metadati_json, posizione_json) = match json.as_deref().and_then(leggi_json) {
+        Some(ref valore) => (
+            leggi_data_json_da_valore(valore),
+            metadati::leggi_metadati_json_da_valore(valore),
+            metadati::leggi_posizione_json_da_valore(valore),
+        ),
+        None => ((None, None), None, None),
+    };
+    
+    // Sidecar .xmp già presente (es. da darktable o da una precedente correzione)
+    let sidecar_xmp = sidecar::trova_sidecar_xmp(foto_path);
+    let (xmp_datetime_original, xmp_create_date) = sidecar_xmp.as_deref()
+        .map(sidecar::leggi_date_sidecar)
+        .unwrap_or((None, None));
+    
+    SorgentiFoto {
+        exif,
+        fotocamera,
+        jpg_associato,
+        json,
+        data_json,
+        data_json_creation,
+        metadati_json,
+        posizione_json,
+        sidecar_xmp,
+        xmp_datetime_original,
+        xmp_create_date,
+    }
+}
+
+pub fn foto_da_sorgenti(foto_path: PathBuf, sorgenti: SorgentiFoto) -> FotoData {
+    let mut foto = nuova_foto(foto_path, sorgenti.exif, sorgenti.fotocamera);
+    foto.data_json = sorgenti.data_json;
+    foto.data_json_creation = sorgenti.data_json_creation;
+    foto.metadati_json = sorgenti.metadati_json;
+    foto.posizione_json = sorgenti.posizione_json;
+    foto.sidecar_xmp = sorgenti.sidecar_xmp;
+    foto.xmp_datetime_original = sorgenti.xmp_datetime_original;
+    foto.xmp_create_date = sorgenti.xmp_create_date;
+    
+    // Verifica se è stato usato un JPG associato
+    foto.jpg_associato_usato = sorgenti.jpg_associato.is_some() && foto.exif_datetime_original.is_some();
+    
+    completa_foto(&mut foto);
+    foto
+}
+
+pub fn leggi_foto_singola(foto_path: PathBuf) -> FotoData {
+    let sorgenti = leggi_sorgenti(&foto_path);
+    foto_da_sorgenti(foto_path, sorgenti)
+}
+
+pub fn leggi_foto_da_directory(directory: &Path) -> Vec<FotoData> {
+    leggi_foto_da_directory_con_progresso(directory, None)
+}
+
+pub fn leggi_foto_da_directory_con_progresso(directory: &Path, progress_sender: Option<std::sync::mpsc::Sender<usize>>) -> Vec<FotoData> {
+    leggi_foto_da_directory_con_thread(directory, progress_sender, None, None, None)
+}
+
+// `num_thread` None = scelta automatica (massimo 8, la lettura è limitata dal disco).
+// Con una cache si rileggono solo le foto cambiate; la cache va poi salvata dal chiamante.
+// Se `controllo` viene annullato la lista restituita è parziale
+pub fn leggi_foto_da_directory_con_thread(directory: &Path, progress_sender: Option<std::sync::mpsc::Sender<usize>>, num_thread: Option<usize>,
+                                          mut cache: Option<&mut cache_scansione::CacheScansione>,
+                                          controllo: Option<&lavoro::ControlloLavoro>) -> Vec<FotoData> {
+    eprintln!("[DEBUG] leggi_foto_da_directory: inizio scan directory {:?}", directory);
+    let estensioni = ["jpg", "JPG", "jpeg", "JPEG", "orf", "ORF", "nef", "NEF"];
+    let mut foto_files = Vec::new();
+    
+    // Cerca ricorsivamente in tutte le sottocartelle
+    eprintln!("[DEBUG] Scan ricorsivo directory...");
+    let mut count = 0;
+    for entry in walkdir::WalkDir::new(directory).into_iter().flatten() {
+        if controllo.is_some_and(|c| c.is_annullato()) {
+            break;
+        }
+        if entry.file_type().is_file() {
+            let path = entry.path();
+            if let Some(ext) = path.extension() {
+                if let Some(ext_str) = ext.to_str() {
+                    if estensioni.contains(&ext_str) {
+                        foto_files.push(path.to_path_buf());
+                        count += 1;
+                        if count % 1000 == 0 {
+                            eprintln!("[DEBUG] Trovate {} foto finora...", count);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    // Comunica il totale trovato PRIMA di iniziare la lettura
+    let total_files = foto_files.len();
+    if let Some(ref sender) = progress_sender {
+        let _ = sender.send(total_files); // Invia il totale trovato
+    }
+    
+    // Usa rayon per parallelizzare la lettura con progresso
+    // Per operazioni I/O bound, limitiamo i thread per ridurre competizione disco
+    // Usa max 8 thread invece di tutti i core disponibili
+    let num_threads = num_thread.unwrap_or_else(|| std::cmp::min(8, num_cpus::get().max(4)));
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build();
+    
+    let start = std::time::Instant::now();
+    let progress_mutex = std::sync::Arc::new(std::sync::Mutex::new(0usize));
+    
+    // Ogni foto dalla cache se è invariata, altrimenti dal disco (con la voce nuova per la cache)
+    let cache_lettura = cache.as_deref();
+    let leggi = |foto_path: PathBuf| {
+        // In pausa aspetta qui; annullata, le foto restanti si saltano
+        if !lavoro::puo_continuare(controllo) {
+            return None;
+        }
+        let result = cache_scansione::CacheScansione::leggi(cache_lettura, foto_path);
+        
+        // Aggiorna progresso ogni 100 foto
+        let mut count = progress_mutex.lock().unwrap();
+        *count += 1;
+        if (*count).is_multiple_of(100) || *count == total_files {
+            if let Some(ref sender) = progress_sender {
+                let _ = sender.send(*count);
+            }
+        }
+        
+        Some(result)
+    };
+    let letture: Vec<(FotoData, Option<cache_scansione::VoceCache>)> = if let Ok(pool) = pool {
+        pool.install(|| foto_files.into_par_iter().filter_map(leggi).collect())
+    } else {
+        // Fallback: usa il thread pool globale
+        foto_files.into_par_iter().filter_map(leggi).collect()
+    };
+    let annullata = controllo.is_some_and(|c| c.is_annullato());
+    
+    let mut foto_list = Vec::with_capacity(letture.len());
+    let mut rilette = 0;
+    for (foto, voce) in letture {
+        if let (Some(voce), Some(cache)) = (voce, cache.as_deref_mut()) {
+            cache.inserisci(&foto.path, voce);
+            rilette += 1;
+        }
+        foto_list.push(foto);
+    }
+    if let Some(cache) = cache {
+        // Una scansione interrotta non ha visto tutte le foto: le voci delle altre restano
+        if !annullata {
+            let presenti: Vec<&Path> = foto_list.iter().map(|f| f.path.as_path()).collect();
+            cache.mantieni_solo(directory, &presenti);
+        }
+        eprintln!("[DEBUG] Cache di scansione: {} foto dalla cache, {} rilette", foto_list.len() - rilette, rilette);
+    }
+    let elapsed = start.elapsed();
+    eprintln!("[DEBUG] Lettura completata: {} foto elaborate in {:?} ({:.2} foto/sec)", 
+              foto_list.len(), elapsed, 
+              foto_list.len() as f64 / elapsed.as_secs_f64());
+    
+    eprintln!("[DEBUG] Ordinamento foto...");
+    let mut foto_list_sorted = foto_list;
+    foto_list_sorted.sort_by(|a, b| a.nome_file.cmp(&b.nome_file));
+    eprintln!("[DEBUG] Ordinamento completato");
+    foto_list_sorted
+}
+
+pub fn scrivi_exif_datetime(foto_path: &Path, data: DateTime<Utc>, solo_datetime_original: bool) -> Result<(), Box<dyn std::error::Error>> {
+    use std::process::Command;
+    
+    // Includi anche i secondi nella data
+    let data_str = format!("{:04}:{:02}:{:02} {:02}:{:02}:{:02}", 
+                          data.year(), data.month(), data.day(), 
+                          data.hour(), data.minute(), data.second());
+    
+    let mut cmd = Command::new("exiftool");
+    cmd.arg("-overwrite_original");
+    cmd.arg("-q"); // Quiet mode
+    
+    if solo_datetime_original {
+        cmd.arg(format!("-DateTimeOriginal={}", data_str));
+    } else {
+        cmd.arg(format!("-DateTimeOriginal={}", data_str));
+        cmd.arg(format!("-CreateDate={}", data_str));
+    }
+    
+    cmd.arg(foto_path);
+    let output = cmd.output()?;
+    
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        return Err(format!("exiftool fallito per {}: {} {}", foto_path.display(), stderr, stdout).into());
+    }
+    
+    Ok(())
+}
+
+// Argomenti exiftool per una scrittura (senza il file di destinazione).
+// Usato sia per la scrittura reale sia per l'anteprima (dry-run)
+pub fn argomenti_scrittura_exiftool(campi: &[(&str, DateTime<Utc>)], metadati: Option<&MetadatiGoogle>) -> Vec<String> {
+    argomenti_scrittura_exiftool_con(campi, metadati, false)
+}
+
+// In modalità riparazione i metadati si ricostruiscono copiando i tag ancora leggibili
+// in una struttura nuova (FAQ 20 di exiftool) e gli errori minori si ignorano (-m)
+pub fn argomenti_scrittura_exiftool_con(campi: &[(&str, DateTime<Utc>)], metadati: Option<&MetadatiGoogle>, riparazione: bool) -> Vec<String> {
+    let mut args = vec![
+        "-overwrite_original".to_string(),
+        // Non usare -q per vedere gli errori quando necessario
+        "-P".to_string(), // Preserve file modification date/time
+    ];
+    if riparazione {
+        args.extend(["-m", "-all=", "-tagsfromfile", "@", "-all:all", "-unsafe", "-icc_profile"].map(String::from));
+    } else if !campi.is_empty() {
+        // Usa -all= per permettere la scrittura anche su file EXIF corrotti
+        // Questo è necessario per alcuni file JPG e per tutti i file RAW
+        args.push("-all=".to_string());
+    }
+    // Solo metadati (nessuna data da cambiare): senza -all=, che cancellerebbe le date
+    // esistenti senza riscriverle; descrizione e persone si aggiungono a quello che c'è
+    
+    for (nome_campo, data) in campi {
+        // Includi anche i secondi nella data
+        let data_str = format!("{:04}:{:02}:{:02} {:02}:{:02}:{:02}", 
+                              data.year(), data.month(), data.day(), 
+                              data.hour(), data.minute(), data.second());
+        args.push(format!("-{}={}", nome_campo, data_str));
+    }
+    
+    // I tag descrittivi vanno nello stesso comando: -all= li cancellerebbe
+    // se fossero scritti in un passaggio separato
+    if let Some(metadati) = metadati {
+        args.extend(metadati::argomenti_exiftool_metadati(metadati));
+    }
+    
+    args
+}
+
+// exiftool fallito: codice di uscita (None se non è partito) e cosa ha stampato
+#[derive(Debug, Clone)]
+pub struct FallimentoExiftool {
+    pub codice_uscita: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+pub fn scrivi_tutti_campi_exif(foto_path: &Path, campi: &[(&str, DateTime<Utc>)]) -> Result<(), String> {
+    scrivi_tutti_campi_exif_con_metadati(foto_path, campi, None)
+}
+
+pub fn scrivi_tutti_campi_exif_con_metadati(foto_path: &Path, campi: &[(&str, DateTime<Utc>)], metadati: Option<&MetadatiGoogle>) -> Result<(), String> {
+    scrivi_campi_exif(foto_path, campi, metadati, false).map_err(|f| match f.codice_uscita {
+        Some(exit_code) => format!("exiftool fallito per {} (exit code {}):\nSTDOUT: {}\nSTDERR: {}",
+                                   foto_path.display(), exit_code, f.stdout, f.stderr),
+        None => f.stderr,
+    })
+}
+
+pub fn scrivi_campi_exif(foto_path: &Path, campi: &[(&str, DateTime<Utc>)], metadati: Option<&MetadatiGoogle>, riparazione: bool) -> Result<(), FallimentoExiftool> {
+    if campi.is_empty() && metadati.is_none() {
+        return Ok(());
+    }
+    
+    // Processo exiftool già aperto del pool: niente avvio di Perl per ogni foto
+    let args = argomenti_scrittura_exiftool_con(campi, metadati, riparazione);
+    match exiftool::esegui(&args, foto_path) {
+        Ok(risposta) if risposta.riuscita() => Ok(()),
+        Ok(risposta) => Err(FallimentoExiftool {
+            codice_uscita: Some(risposta.codice_uscita),
+            stdout: risposta.stdout,
+            stderr: risposta.stderr,
+        }),
+        Err(e) => Err(FallimentoExiftool {
+            codice_uscita: None,
+            stdout: String::new(),
+            stderr: format!("{} ({})", e, foto_path.display()),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn argomenti_scrittura_solo_metadati_senza_all() {
+        let metadati = MetadatiGoogle { descrizione: Some("Mare".to_string()), ..Default::default() };
+        let args = argomenti_scrittura_exiftool_con(&[], Some(&metadati), false);
+        assert!(!args.contains(&"-all=".to_string()));
+        assert!(args.contains(&"-EXIF:ImageDescription=Mare".to_string()));
+
+        let data = DateTime::from_timestamp(0, 0).unwrap();
+        let args = argomenti_scrittura_exiftool_con(&[("DateTimeOriginal", data)], Some(&metadati), false);
+        assert!(args.contains(&"-all=".to_string()));
+        assert!(args.contains(&"-DateTimeOriginal=1970:01:01 00:00:00".to_string()));
+    }
+
+    #[test]
+    fn json_interpretato_una_volta() {
+        let valore: serde_json::Value = serde_json::from_str(r#"{
+            "photoTakenTime": {"timestamp": "1000"},
+            "creationTime": {"timestamp": "2000"},
+            "description": " Mare ",
+            "geoData": {"latitude": 45.0, "longitude": 9.0, "altitude": 0.0}
+        }"#).unwrap();
+        assert_eq!(leggi_data_json_da_valore(&valore), (DateTime::from_timestamp(1000, 0), DateTime::from_timestamp(2000, 0)));
+        assert_eq!(metadati::leggi_metadati_json_da_valore(&valore).unwrap().descrizione.as_deref(), Some("Mare"));
+        assert_eq!(metadati::leggi_posizione_json_da_valore(&valore).map(|p| p.latitudine), Some(45.0));
+
+        // JSON non valido: niente da ricavare
+        let nullo = serde_json::Value::Null;
+        assert_eq!(leggi_data_json_da_valore(&nullo), (None, None));
+        assert!(metadati::leggi_metadati_json_da_valore(&nullo).is_none());
+    }
+}
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Metadati descrittivi che Google Foto salva solo nel JSON supplementare:
+// didascalia, nomi dei volti riconosciuti e flag "preferita"
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetadatiGoogle {
+    pub descrizione: Option<String>,
+    pub persone: Vec<String>,
+    pub preferita: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct GoogleMetadatiJson {
+    description: Option<String>,
+    #[serde(default)]
+    people: Vec<PersonaJson>,
+    #[serde(default)]
+    favorited: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct PersonaJson {
+    name: Option<String>,
+}
+
+impl MetadatiGoogle {
+    pub fn is_vuoto(&self) -> bool {
+        self.descrizione.is_none() && self.persone.is_empty() && !self.preferita
+    }
+
+    // Testo breve per la colonna di anteprima della GUI
+    pub fn anteprima(&self) -> String {
+        let mut parti = Vec::new();
+        if let Some(ref descrizione) = self.descrizione {
+            parti.push(format!("📝 {}", descrizione.replace('\n', " ")));
+        }
+        if !self.persone.is_empty() {
+            parti.push(format!("👤 {}", self.persone.join(", ")));
+        }
+        if self.preferita {
+            parti.push("⭐".to_string());
+        }
+        parti.join(" | ")
+    }
+}
+
+pub fn leggi_metadati_json(json_path: &Path) -> Option<MetadatiGoogle> {
+    leggi_metadati_json_da_valore(&crate::leggi_json(json_path)?)
+}
+
+pub fn leggi_metadati_json_da_valore(valore: &serde_json::Value) -> Option<MetadatiGoogle> {
+    let json = GoogleMetadatiJson::deserialize(valore).ok()?;
+
+    // Google esporta "description": "" quando non c'è didascalia
+    let descrizione = json.description
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty());
+
+    let mut persone: Vec<String> = Vec::new();
+    for persona in json.people {
+        if let Some(nome) = persona.name {
+            let nome = nome.trim().to_string();
+            if !nome.is_empty() && !persone.contains(&nome) {
+                persone.push(nome);
+            }
+        }
+    }
+
+    let metadati = MetadatiGoogle {
+        descrizione,
+        persone,
+        preferita: json.favorited,
+    };
+
+    if metadati.is_vuoto() {
+        None
+    } else {
+        Some(metadati)
+    }
+}
+
+pub fn argomenti_exiftool_metadati(metadati: &MetadatiGoogle) -> Vec<String> {
+    let mut args = Vec::new();
+
+    if let Some(ref descrizione) = metadati.descrizione {
+        args.push(format!("-XMP-dc:Description={}", descrizione));
+        args.push(format!("-EXIF:ImageDescription={}", descrizione));
+    }
+
+    for nome in &metadati.persone {
+        // "-=" prima di "+=" evita duplicati se il tag contiene già il nome. Conta nelle
+        // scritture di soli metadati: con le date c'è -all= e i tag partono vuoti
+        args.push(format!("-XMP-iptcExt:PersonInImage-={}", nome));
+        args.push(format!("-XMP-iptcExt:PersonInImage+={}", nome));
+        args.push(format!("-XMP-dc:Subject-={}", nome));
+        args.push(format!("-XMP-dc:Subject+={}", nome));
+        args.push(format!("-IPTC:Keywords-={}", nome));
+        args.push(format!("-IPTC:Keywords+={}", nome));
+    }
+
+    if metadati.preferita {
+        args.push("-XMP-xmp:Rating=5".to_string());
+    }
+
+    args
+}
+
+// Posizione GPS dal JSON (geoData, oppure geoDataExif se geoData è vuoto)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PosizioneGps {
+    pub latitudine: f64,
+    pub longitudine: f64,
+    pub altitudine: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct GooglePosizioneJson {
+    #[serde(rename = "geoData")]
+    geo_data: Option<GeoDataJson>,
+    #[serde(rename = "geoDataExif")]
+    geo_data_exif: Option<GeoDataJson>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeoDataJson {
+    #[serde(default)]
+    latitude: f64,
+    #[serde(default)]
+    longitude: f64,
+    #[serde(default)]
+    altitude: f64,
+}
+
+impl GeoDataJson {
+    fn in_posizione(&self) -> Option<PosizioneGps> {
+        // Google scrive 0.0/0.0 quando la foto non ha posizione
+        if self.latitude == 0.0 && self.longitude == 0.0 {
+            return None;
+        }
+        Some(PosizioneGps {
+            latitudine: self.latitude,
+            longitudine: self.longitude,
+            altitudine: self.altitude,
+        })
+    }
+}
+
+pub fn leggi_posizione_json(json_path: &Path) -> Option<PosizioneGps> {
+    leggi_posizione_json_da_valore(&crate::leggi_json(json_path)?)
+}
+
+pub fn leggi_posizione_json_da_valore(valore: &serde_json::Value) -> Option<PosizioneGps> {
+    let json = GooglePosizioneJson::deserialize(valore).ok()?;
+
+    json.geo_data.as_ref().and_then(|g| g.in_posizione())
+        .or_else(|| json.geo_data_exif.as_ref().and_then(|g| g.in_posizione()))
+}
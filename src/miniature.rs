@@ -0,0 +1,178 @@
+use exif::{In, Tag};
+use image::{imageops::FilterType, DynamicImage};
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+
+// Miniature per la tabella e l'anteprima della GUI. La miniatura incorporata
+// nell'EXIF basta quasi sempre ed evita di decodificare l'intera foto; il risultato
+// si salva come PNG nella cache, con una chiave che cambia se il file cambia
+
+// Lato lungo della miniatura nella tabella
+pub const LATO_MINIATURA: u32 = 96;
+// Lato lungo dell'immagine nel pannello di anteprima
+pub const LATO_ANTEPRIMA: u32 = 1024;
+
+// Pixel RGBA pronti per una texture
+#[derive(Debug, Clone)]
+pub struct Miniatura {
+    pub larghezza: u32,
+    pub altezza: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl Miniatura {
+    fn da_immagine(immagine: DynamicImage) -> Self {
+        let rgba = immagine.to_rgba8();
+        Miniatura { larghezza: rgba.width(), altezza: rgba.height(), rgba: rgba.into_raw() }
+    }
+}
+
+pub fn dir_miniature() -> Option<PathBuf> {
+    crate::percorsi::dir_cache().map(|d| d.join("miniature"))
+}
+
+// Path, dimensione, data di modifica e lato: se il file viene riscritto la chiave cambia
+fn file_cache(path: &Path, lato: u32) -> Option<PathBuf> {
+    let metadata = fs::metadata(path).ok()?;
+    let mtime = filetime::FileTime::from_last_modification_time(&metadata);
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(path.to_string_lossy().as_bytes());
+    hasher.update(&metadata.len().to_le_bytes());
+    hasher.update(&mtime.unix_seconds().to_le_bytes());
+    hasher.update(&mtime.nanoseconds().to_le_bytes());
+    hasher.update(&lato.to_le_bytes());
+    let chiave = hasher.finalize().to_hex();
+    Some(dir_miniature()?.join(&chiave[..2]).join(format!("{}.png", chiave)))
+}
+
+// Miniatura JPEG nell'IFD1 dell'EXIF (160x120 tipicamente) e orientamento della foto
+fn leggi_exif(path: &Path) -> (Option<DynamicImage>, u32) {
+    let Some(exif) = fs::File::open(path).ok()
+        .and_then(|f| exif::Reader::new().read_from_container(&mut BufReader::new(f)).ok()) else {
+        return (None, 1);
+    };
+    let orientamento = exif.get_field(Tag::Orientation, In::PRIMARY)
+        .and_then(|f| f.value.get_uint(0))
+        .unwrap_or(1);
+    let miniatura = (|| {
+        let inizio = exif.get_field(Tag::JPEGInterchangeFormat, In::THUMBNAIL)?.value.get_uint(0)? as usize;
+        let lunghezza = exif.get_field(Tag::JPEGInterchangeFormatLength, In::THUMBNAIL)?.value.get_uint(0)? as usize;
+        let dati = exif.buf().get(inizio..inizio.checked_add(lunghezza)?)?;
+        image::load_from_memory_with_format(dati, image::ImageFormat::Jpeg).ok()
+    })();
+    (miniatura, orientamento)
+}
+
+// Le rotazioni dell'EXIF (i ribaltamenti sono rarissimi e si ignorano)
+fn orienta(immagine: DynamicImage, orientamento: u32) -> DynamicImage {
+    match orientamento {
+        3 => immagine.rotate180(),
+        6 => immagine.rotate90(),
+        8 => immagine.rotate270(),
+        _ => immagine,
+    }
+}
+
+fn decodifica(path: &Path) -> Result<DynamicImage, String> {
+    image::io::Reader::open(path)
+        .map_err(|e| format!("Errore apertura {}: {}", path.display(), e))?
+        .with_guessed_format()
+        .map_err(|e| format!("Errore lettura {}: {}", path.display(), e))?
+        .decode()
+        .map_err(|e| format!("Formato non supportato {}: {}", path.display(), e))
+}
+
+// Miniatura con il lato lungo al massimo `lato`, dalla cache se c'è.
+// Per i RAW si usa il JPG della coppia, se esiste
+pub fn miniatura(path: &Path, lato: u32) -> Result<Miniatura, String> {
+    let cache = file_cache(path, lato);
+    if let Some(immagine) = cache.as_ref().and_then(|c| image::open(c).ok()) {
+        return Ok(Miniatura::da_immagine(immagine));
+    }
+
+    let sorgente = crate::trova_file_jpg_associato(path).unwrap_or_else(|| path.to_path_buf());
+    let (incorporata, orientamento) = leggi_exif(&sorgente);
+    // L'incorporata va bene se non va ingrandita, o se la foto non si decodifica (RAW senza JPG)
+    let immagine = match incorporata {
+        Some(immagine) if immagine.width().max(immagine.height()) >= lato => immagine,
+        incorporata => match decodifica(&sorgente) {
+            Ok(immagine) => immagine,
+            Err(e) => incorporata.ok_or(e)?,
+        },
+    };
+    let immagine = if immagine.width().max(immagine.height()) > lato {
+        immagine.resize(lato, lato, FilterType::Triangle)
+    } else {
+        immagine
+    };
+    let immagine = orienta(immagine, orientamento);
+
+    if let Some(cache) = cache {
+        let salvata = cache.parent().map(fs::create_dir_all).unwrap_or(Ok(()))
+            .map_err(|e| e.to_string())
+            .and_then(|_| immagine.save_with_format(&cache, image::ImageFormat::Png).map_err(|e| e.to_string()));
+        if let Err(e) = salvata {
+            eprintln!("[DEBUG] Miniatura non salvata in cache {}: {}", cache.display(), e);
+        }
+    }
+    Ok(Miniatura::da_immagine(immagine))
+}
+
+type Risultato = (PathBuf, u32, Result<Miniatura, String>);
+// Richieste in attesa (path, lato) e segnale per svegliare i thread
+type Richieste = Arc<(Mutex<Vec<(PathBuf, u32)>>, Condvar)>;
+
+// Thread che preparano le miniature richieste. Le richieste più recenti passano
+// prima: sono quelle delle righe visibili dopo uno scorrimento
+pub struct CodaMiniature {
+    richieste: Richieste,
+    risultati: mpsc::Receiver<Risultato>,
+}
+
+impl CodaMiniature {
+    pub fn new(num_thread: usize) -> Self {
+        let richieste: Richieste = Arc::new((Mutex::new(Vec::new()), Condvar::new()));
+        let (sender, risultati) = mpsc::channel();
+        for _ in 0..num_thread.max(1) {
+            let richieste = Arc::clone(&richieste);
+            let sender: mpsc::Sender<Risultato> = sender.clone();
+            std::thread::spawn(move || loop {
+                let (path, lato) = {
+                    let (coda, segnale) = &*richieste;
+                    let mut coda = coda.lock().unwrap();
+                    loop {
+                        match coda.pop() {
+                            Some(richiesta) => break richiesta,
+                            None => coda = segnale.wait(coda).unwrap(),
+                        }
+                    }
+                };
+                let esito = miniatura(&path, lato);
+                // Ricevitore chiuso: la GUI è stata chiusa
+                if sender.send((path, lato, esito)).is_err() {
+                    break;
+                }
+            });
+        }
+        CodaMiniature { richieste, risultati }
+    }
+
+    pub fn richiedi(&self, path: PathBuf, lato: u32) {
+        let (coda, segnale) = &*self.richieste;
+        coda.lock().unwrap().push((path, lato));
+        segnale.notify_one();
+    }
+
+    // Toglie le richieste più vecchie oltre `massimo` e le restituisce
+    pub fn sfoltisci(&self, massimo: usize) -> Vec<(PathBuf, u32)> {
+        let mut coda = self.richieste.0.lock().unwrap();
+        let eccesso = coda.len().saturating_sub(massimo);
+        coda.drain(..eccesso).collect()
+    }
+
+    pub fn ricevi(&self) -> Vec<Risultato> {
+        self.risultati.try_iter().collect()
+    }
+}
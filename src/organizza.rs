@@ -0,0 +1,310 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use crate::FotoData;
+use crate::duplicati;
+use crate::journal::{self, Journal, VoceJournal};
+use crate::rinomina::{self, OperazioneRinomina};
+
+pub const TEMPLATE_CARTELLE_PREDEFINITO: &str = "{year}/{year}-{month}";
+pub const CARTELLA_DATA_SCONOSCIUTA: &str = "unknown-date";
+pub const CARTELLA_FOTOCAMERA_SCONOSCIUTA: &str = "unknown-camera";
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ModalitaOrganizza {
+    #[value(name = "copy")]
+    Copia,
+    #[value(name = "move")]
+    Sposta,
+    #[value(name = "hardlink")]
+    Hardlink,
+}
+
+impl ModalitaOrganizza {
+    pub fn display_name(&self) -> &str {
+        match self {
+            ModalitaOrganizza::Copia => "Copy",
+            ModalitaOrganizza::Sposta => "Move",
+            ModalitaOrganizza::Hardlink => "Hardlink",
+        }
+    }
+
+    fn nome_manifest(&self) -> &str {
+        match self {
+            ModalitaOrganizza::Copia => "copy",
+            ModalitaOrganizza::Sposta => "move",
+            ModalitaOrganizza::Hardlink => "hardlink",
+        }
+    }
+}
+
+// Una foto con JSON, XMP e JPG associato, da portare nella stessa cartella
+#[derive(Debug, Clone)]
+pub struct GruppoOrganizza {
+    pub foto: PathBuf,
+    pub data: Option<DateTime<Utc>>, // None = cartella unknown-date
+    pub operazioni: Vec<OperazioneRinomina>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PianoOrganizza {
+    pub gruppi: Vec<GruppoOrganizza>,
+    pub saltate: Vec<(PathBuf, String)>, // (foto, motivo)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EsitoOrganizza {
+    pub eseguite: Vec<OperazioneRinomina>,
+    pub errori: Vec<String>,
+    pub manifest: Option<PathBuf>,
+    pub journal: Option<PathBuf>,
+}
+
+// Cartella relativa per una data: ogni componente del template è espanso
+// con gli stessi segnaposto della rinomina ({year}, {month}, {date:%Y}, {camera}, ...)
+pub fn cartella_per_data(template: &str, data: Option<DateTime<Utc>>, foto: &FotoData) -> Result<PathBuf, String> {
+    let data = match data {
+        Some(d) => d,
+        None => return Ok(PathBuf::from(CARTELLA_DATA_SCONOSCIUTA)),
+    };
+
+    let stem = foto.path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let ext = foto.path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+
+    // Senza fotocamera {camera} sarebbe vuoto: le foto finiscono in unknown-camera,
+    // come quelle senza data in unknown-date, invece di essere escluse
+    let fotocamera = foto.fotocamera.as_deref().unwrap_or(CARTELLA_FOTOCAMERA_SCONOSCIUTA);
+
+    let mut cartella = PathBuf::new();
+    for componente in template.split('/').filter(|c| !c.is_empty()) {
+        let espanso = rinomina::applica_template(componente, data, Some(fotocamera), stem, ext)?;
+        if espanso == ".." || espanso == "." {
+            return Err(format!("Componente di cartella non valido: {}", espanso));
+        }
+        cartella.push(espanso);
+    }
+    Ok(cartella)
+}
+
+// Con `salta_duplicati` di ogni gruppo di duplicati si organizza una sola copia
+pub fn pianifica_organizzazione(foto_list: &[&FotoData], destinazione: &Path, template: &str, salta_duplicati: bool) -> PianoOrganizza {
+    let mut piano = PianoOrganizza::default();
+    let jpg_di_coppia = rinomina::jpg_di_coppia(foto_list);
+    let mut destinazioni_occupate: HashSet<PathBuf> = HashSet::new();
+
+    let mut copie_tenute: HashMap<usize, &FotoData> = HashMap::new();
+    if salta_duplicati {
+        for foto in foto_list {
+            if let Some(gruppo) = foto.gruppo_duplicati {
+                let tenuta = copie_tenute.entry(gruppo).or_insert(foto);
+                if duplicati::chiave_rappresentante(foto) < duplicati::chiave_rappresentante(tenuta) {
+                    *tenuta = foto;
+                }
+            }
+        }
+    }
+
+    for foto in foto_list {
+        if jpg_di_coppia.contains(&foto.path) {
+            continue;
+        }
+        if foto.origine_archivio.is_some() {
+            piano.saltate.push((foto.path.clone(), "ancora nell'archivio Takeout, estrarla prima".to_string()));
+            continue;
+        }
+        if let Some(tenuta) = foto.gruppo_duplicati.and_then(|g| copie_tenute.get(&g)) {
+            if tenuta.path != foto.path {
+                piano.saltate.push((foto.path.clone(), format!("copia di {}", tenuta.path.display())));
+                continue;
+            }
+        }
+
+        // Le foto con il segnaposto 1900-01-01 finiscono in unknown-date/
+        let data = if foto.is_foto_1900() {
+            None
+        } else {
+            crate::scrittura::data_finale(foto)
+        };
+
+        let cartella = match cartella_per_data(template, data, foto) {
+            Ok(c) => destinazione.join(c),
+            Err(e) => {
+                piano.saltate.push((foto.path.clone(), e));
+                continue;
+            }
+        };
+
+        let stem = foto.path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+        let ext = foto.path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_string();
+        let mut principali = vec![(foto.path.clone(), ext)];
+        if let Some(jpg) = crate::trova_file_jpg_associato(&foto.path) {
+            let ext_jpg = jpg.extension().and_then(|e| e.to_str()).unwrap_or("jpg").to_string();
+            principali.push((jpg, ext_jpg));
+        }
+
+        // Collisioni nella cartella di destinazione: _1, _2, ... per tutto il gruppo
+        let mut trovato = None;
+        for n in 0..10000 {
+            let stem_candidato = if n == 0 {
+                stem.clone()
+            } else {
+                format!("{}_{}", stem, n)
+            };
+            let operazioni = rinomina::operazioni_gruppo(&principali, Some(&cartella), &stem_candidato);
+            let libero = operazioni.iter().all(|op| {
+                !destinazioni_occupate.contains(&op.a) && !op.a.exists()
+            });
+            if libero {
+                trovato = Some(operazioni);
+                break;
+            }
+        }
+
+        match trovato {
+            Some(operazioni) if operazioni.is_empty() => {
+                piano.saltate.push((foto.path.clone(), "già nella cartella di destinazione".to_string()));
+            }
+            Some(operazioni) => {
+                for op in &operazioni {
+                    destinazioni_occupate.insert(op.a.clone());
+                }
+                piano.gruppi.push(GruppoOrganizza { foto: foto.path.clone(), data, operazioni });
+            }
+            None => piano.saltate.push((foto.path.clone(), "nessun nome libero disponibile".to_string())),
+        }
+    }
+
+    piano
+}
+
+// Riga del manifest CSV: un'operazione eseguita o tentata, o una foto saltata
+#[derive(Serialize)]
+struct RigaManifest<'a> {
+    mode: &'a str,
+    source: String,
+    destination: String,
+    date: String,
+    status: String,
+}
+
+fn esegui_operazione(op: &OperazioneRinomina, modalita: ModalitaOrganizza) -> Result<VoceJournal, String> {
+    if let Some(dir) = op.a.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Errore creazione cartella {}: {}", dir.display(), e))?;
+    }
+    // Nessuna operazione deve sovrascrivere un file esistente
+    if op.a.exists() {
+        return Err(format!("{} esiste già", op.a.display()));
+    }
+
+    let (da, a) = (op.da.clone(), op.a.clone());
+    match modalita {
+        ModalitaOrganizza::Copia => {
+            fs::copy(&op.da, &op.a)
+                .map_err(|e| format!("Errore copia {} -> {}: {}", op.da.display(), op.a.display(), e))?;
+            Ok(VoceJournal::Copia { da, a })
+        }
+        ModalitaOrganizza::Sposta => {
+            journal::sposta_file(&op.da, &op.a)?;
+            Ok(VoceJournal::Spostamento { da, a })
+        }
+        ModalitaOrganizza::Hardlink => {
+            fs::hard_link(&op.da, &op.a)
+                .map_err(|e| format!("Errore hardlink {} -> {}: {}", op.da.display(), op.a.display(), e))?;
+            Ok(VoceJournal::Collegamento { da, a })
+        }
+    }
+}
+
+// Esegue il piano e scrive un manifest CSV nella cartella di destinazione.
+// `progresso` conta i gruppi completati (per la barra di avanzamento)
+pub fn esegui_organizzazione(piano: &PianoOrganizza, destinazione: &Path, modalita: ModalitaOrganizza, progresso: Option<&AtomicUsize>) -> Result<EsitoOrganizza, String> {
+    fs::create_dir_all(destinazione)
+        .map_err(|e| format!("Errore creazione cartella {}: {}", destinazione.display(), e))?;
+
+    let journal = Journal::nuovo("organizza")?;
+    let nome_manifest = format!("manifest-{}.csv", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+    let manifest_path = destinazione.join(nome_manifest);
+    let mut manifest = fs::File::create(&manifest_path)
+        .map(csv::Writer::from_writer)
+        .map_err(|e| format!("Errore creazione manifest {}: {}", manifest_path.display(), e))?;
+    let errore_manifest = |e: csv::Error| format!("Errore scrittura manifest {}: {}", manifest_path.display(), e);
+
+    let mut esito = EsitoOrganizza {
+        manifest: Some(manifest_path.clone()),
+        journal: Some(journal.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    for gruppo in &piano.gruppi {
+        let data = gruppo.data
+            .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+
+        for op in &gruppo.operazioni {
+            let stato = match esegui_operazione(op, modalita) {
+                Ok(voce) => {
+                    journal.registra(&voce)?;
+                    esito.eseguite.push(op.clone());
+                    "ok".to_string()
+                }
+                Err(e) => {
+                    esito.errori.push(e.clone());
+                    format!("error: {}", e)
+                }
+            };
+            manifest.serialize(RigaManifest {
+                mode: modalita.nome_manifest(),
+                source: op.da.to_string_lossy().to_string(),
+                destination: op.a.to_string_lossy().to_string(),
+                date: data.clone(),
+                status: stato,
+            }).map_err(errore_manifest)?;
+        }
+        // Un'interruzione lascia nel manifest i gruppi già fatti
+        manifest.flush().map_err(|e| errore_manifest(e.into()))?;
+
+        if let Some(contatore) = progresso {
+            contatore.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Anche le foto escluse (duplicati, template non valido) finiscono nel manifest
+    for (foto, motivo) in &piano.saltate {
+        manifest.serialize(RigaManifest {
+            mode: modalita.nome_manifest(),
+            source: foto.to_string_lossy().to_string(),
+            destination: String::new(),
+            date: String::new(),
+            status: format!("skipped: {}", motivo),
+        }).map_err(errore_manifest)?;
+    }
+    manifest.flush().map_err(|e| errore_manifest(e.into()))?;
+
+    Ok(esito)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn foto(fotocamera: Option<&str>) -> FotoData {
+        let mut foto = crate::nuova_foto(PathBuf::from("IMG_0001.jpg"), (None, None, None), None);
+        foto.fotocamera = fotocamera.map(str::to_string);
+        foto
+    }
+
+    #[test]
+    fn cartella_per_data_senza_fotocamera() {
+        let data = DateTime::from_timestamp(1563095103, 0); // 2019-07-14
+        assert_eq!(cartella_per_data("{camera}/{year}", data, &foto(Some("NIKON D750"))),
+                   Ok(PathBuf::from("NIKON-D750/2019")));
+        assert_eq!(cartella_per_data("{camera}/{year}", data, &foto(None)),
+                   Ok(PathBuf::from("unknown-camera/2019")));
+        assert_eq!(cartella_per_data("{camera}/{year}", None, &foto(None)),
+                   Ok(PathBuf::from(CARTELLA_DATA_SCONOSCIUTA)));
+    }
+}
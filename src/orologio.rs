@@ -0,0 +1,155 @@
+use chrono::Local;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use crate::FotoData;
+use crate::scostamento::Scostamento;
+
+// Rilevamento dell'orologio sbagliato di una fotocamera: se in un gruppo (stessa
+// fotocamera, stessa cartella) l'EXIF differisce dal photoTakenTime del JSON sempre
+// della stessa quantità, è uno scostamento dell'orologio e non una serie di errori
+
+// Foto con EXIF e JSON necessarie per giudicare un gruppo
+pub const CAMPIONI_MINIMI: usize = 5;
+// Differenze entro questa distanza dalla mediana contano come coerenti (deriva dell'orologio)
+pub const TOLLERANZA_SECONDI: i64 = 120;
+// Quota di differenze coerenti perché lo scostamento sia credibile
+pub const QUOTA_COERENTE: f64 = 0.8;
+// Sotto questa soglia l'orologio si considera giusto
+pub const SCOSTAMENTO_MINIMO_SECONDI: i64 = 60;
+// Fusi orari esistenti: multipli di un quarto d'ora fino a 14 ore
+const QUARTO_D_ORA_SECONDI: i64 = 900;
+const FUSO_MASSIMO_SECONDI: i64 = 14 * 3600;
+
+#[derive(Debug, Clone)]
+pub struct GruppoOrologio {
+    pub fotocamera: String,
+    pub cartella: PathBuf,
+    pub indici: Vec<usize>, // Tutte le foto del gruppo in foto_list
+    pub campioni: usize,    // Foto con EXIF e JSON
+    pub coerenti: usize,    // Campioni entro la tolleranza dalla mediana
+    pub scostamento: Scostamento, // Mediana di JSON - EXIF
+    pub media_secondi: f64,      // Media e deviazione standard dei soli campioni coerenti
+    pub deviazione_secondi: f64,
+    pub min_secondi: i64,        // Minimo e massimo di tutti i campioni
+    pub max_secondi: i64,
+    pub fuori_tolleranza: Vec<usize>, // Campioni che non seguono lo scostamento
+}
+
+impl GruppoOrologio {
+    pub fn quota_coerente(&self) -> f64 {
+        self.coerenti as f64 / self.campioni as f64
+    }
+
+    // Scostamento coerente e abbastanza grande da correggere. Uno di ore intere (o quarti d'ora)
+    // è più probabilmente un fuso diverso da quello di questo computer (foto in viaggio con
+    // l'orologio della fotocamera giusto per il posto): si segnala ma non si propone di correggerlo
+    pub fn da_correggere(&self) -> bool {
+        self.quota_coerente() >= QUOTA_COERENTE
+            && self.scostamento.secondi.abs() >= SCOSTAMENTO_MINIMO_SECONDI
+            && !self.probabile_fuso()
+    }
+
+    pub fn probabile_fuso(&self) -> bool {
+        self.quota_coerente() >= QUOTA_COERENTE && come_fuso(self.scostamento.secondi)
+    }
+
+    // Foto senza JSON, che ricevono la correzione senza poterla verificare
+    pub fn senza_json(&self, foto_list: &[FotoData]) -> usize {
+        self.indici.iter().filter(|&&i| foto_list[i].data_json.is_none()).count()
+    }
+}
+
+// Differenza in secondi tra l'ora del JSON e quella dell'EXIF. photoTakenTime è un istante
+// UTC, l'EXIF un'ora da orologio senza fuso: il JSON si porta all'ora locale di questo
+// computer (come per la data del file), con l'ora legale del giorno dello scatto
+fn differenza(foto: &FotoData) -> Option<i64> {
+    let json = foto.data_json?.with_timezone(&Local).naive_local().and_utc();
+    Some((json - foto.exif_datetime_original?).num_seconds())
+}
+
+// Differenza che corrisponde a un fuso orario, a meno della deriva tollerata
+fn come_fuso(secondi: i64) -> bool {
+    let resto = secondi.rem_euclid(QUARTO_D_ORA_SECONDI);
+    secondi.abs() >= QUARTO_D_ORA_SECONDI - TOLLERANZA_SECONDI
+        && secondi.abs() <= FUSO_MASSIMO_SECONDI + TOLLERANZA_SECONDI
+        && resto.min(QUARTO_D_ORA_SECONDI - resto) <= TOLLERANZA_SECONDI
+}
+
+// Analizza tutti i gruppi con abbastanza campioni, dal più numeroso.
+// Le foto senza Make/Model non si raggruppano: potrebbero venire da dispositivi diversi
+pub fn analizza_orologi(foto_list: &[FotoData]) -> Vec<GruppoOrologio> {
+    let mut gruppi: HashMap<(&str, PathBuf), Vec<usize>> = HashMap::new();
+    for (idx, foto) in foto_list.iter().enumerate() {
+        if let Some(ref fotocamera) = foto.fotocamera {
+            let cartella = foto.path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+            gruppi.entry((fotocamera.as_str(), cartella)).or_default().push(idx);
+        }
+    }
+
+    let mut risultato: Vec<GruppoOrologio> = gruppi.into_iter()
+        .filter_map(|((fotocamera, cartella), indici)| {
+            let differenze: Vec<(usize, i64)> = indici.iter()
+                .filter_map(|&i| differenza(&foto_list[i]).map(|d| (i, d)))
+                .collect();
+            if differenze.len() < CAMPIONI_MINIMI {
+                return None;
+            }
+
+            let mut valori: Vec<i64> = differenze.iter().map(|(_, d)| *d).collect();
+            valori.sort();
+            let mediana = valori[valori.len() / 2];
+            let coerenti: Vec<f64> = valori.iter()
+                .filter(|&&d| (d - mediana).abs() <= TOLLERANZA_SECONDI)
+                .map(|&d| d as f64)
+                .collect();
+            let n = coerenti.len() as f64;
+            let media = coerenti.iter().sum::<f64>() / n;
+            let varianza = coerenti.iter().map(|d| (d - media).powi(2)).sum::<f64>() / n;
+            let fuori_tolleranza: Vec<usize> = differenze.iter()
+                .filter(|(_, d)| (d - mediana).abs() > TOLLERANZA_SECONDI)
+                .map(|(i, _)| *i)
+                .collect();
+
+            Some(GruppoOrologio {
+                fotocamera: fotocamera.to_string(),
+                cartella,
+                campioni: differenze.len(),
+                coerenti: differenze.len() - fuori_tolleranza.len(),
+                scostamento: Scostamento { mesi: 0, secondi: mediana },
+                media_secondi: media,
+                deviazione_secondi: varianza.sqrt(),
+                min_secondi: valori[0],
+                max_secondi: valori[valori.len() - 1],
+                fuori_tolleranza,
+                indici,
+            })
+        })
+        .collect();
+    risultato.sort_by(|a, b| b.indici.len().cmp(&a.indici.len()).then(a.cartella.cmp(&b.cartella)));
+    risultato
+}
+
+// Propone le date corrette per tutto il gruppo, anche per le foto senza JSON.
+// Le foto con JSON fuori tolleranza restano con la loro proposta. Restituisce (corrette, senza data)
+pub fn applica_correzione(foto_list: &mut [FotoData], gruppo: &GruppoOrologio) -> (usize, usize) {
+    let indici: Vec<usize> = gruppo.indici.iter()
+        .copied()
+        .filter(|i| !gruppo.fuori_tolleranza.contains(i))
+        .collect();
+    crate::scostamento::applica_scostamento(foto_list, &indici, gruppo.scostamento, "clock offset")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn come_fuso_ore_e_quarti_d_ora() {
+        for secondi in [3600, -3600, 7200 + 30, 5 * 3600 + 1800, -(9 * 3600 + 2700), 14 * 3600] {
+            assert!(come_fuso(secondi), "{}", secondi);
+        }
+        for secondi in [0, 60, 3600 + 180, 3 * 86400, 15 * 3600, -(2 * 3600 + 420)] {
+            assert!(!come_fuso(secondi), "{}", secondi);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+
+// Cartelle dell'applicazione secondo le convenzioni XDG
+// ($XDG_DATA_HOME, altrimenti ~/.local/share)
+pub fn dir_dati() -> Option<PathBuf> {
+    let base = std::env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var("HOME").ok().map(|home| PathBuf::from(home).join(".local").join("share")))?;
+    Some(base.join("corrigi-exif"))
+}
+
+// Dati rigenerabili (miniature): $XDG_CACHE_HOME, altrimenti ~/.cache
+pub fn dir_cache() -> Option<PathBuf> {
+    let base = std::env::var("XDG_CACHE_HOME")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var("HOME").ok().map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(base.join("corrigi-exif"))
+}
+
+// Impostazioni: $XDG_CONFIG_HOME, altrimenti ~/.config
+pub fn dir_config() -> Option<PathBuf> {
+    let base = std::env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var("HOME").ok().map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("corrigi-exif"))
+}
@@ -0,0 +1,429 @@
+use chrono::{DateTime, Datelike, Utc};
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use crate::FotoData;
+use crate::journal::{Journal, VoceJournal};
+
+pub const TEMPLATE_PREDEFINITO: &str = "{date:%Y%m%d_%H%M%S}_{camera}_{orig_stem}.{ext}";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperazioneRinomina {
+    pub da: PathBuf,
+    pub a: PathBuf,
+}
+
+// Una foto con i file che la accompagnano (JSON, XMP, JPG della coppia RAW+JPG):
+// vengono rinominati insieme, con lo stesso nome base
+#[derive(Debug, Clone)]
+pub struct GruppoRinomina {
+    pub foto: PathBuf,
+    pub operazioni: Vec<OperazioneRinomina>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PianoRinomina {
+    pub gruppi: Vec<GruppoRinomina>,
+    pub saltate: Vec<(PathBuf, String)>, // (foto, motivo)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EsitoRinomina {
+    pub eseguite: Vec<OperazioneRinomina>,
+    pub errori: Vec<String>,
+    pub journal: Option<PathBuf>,
+}
+
+fn pulisci_componente(valore: &str) -> String {
+    // Niente separatori di percorso o caratteri problematici nei nomi file
+    valore.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
+
+const SEPARATORI: [char; 2] = ['_', '-'];
+
+// Testo del template dopo un segnaposto: senza il primo separatore se quello prima era vuoto
+fn testo_dopo_vuoto<'a>(testo: &'a str, salta_separatore: &mut bool) -> &'a str {
+    if std::mem::take(salta_separatore) {
+        testo.strip_prefix(SEPARATORI).unwrap_or(testo)
+    } else {
+        testo
+    }
+}
+
+// Espande un template tipo "{date:%Y%m%d_%H%M%S}_{camera}_{orig_stem}.{ext}"
+pub fn applica_template(template: &str, data: DateTime<Utc>, fotocamera: Option<&str>, stem: &str, ext: &str) -> Result<String, String> {
+    let mut risultato = String::new();
+    let mut salta_separatore = false;
+    let mut resto = template;
+
+    while let Some(inizio) = resto.find('{') {
+        let letterale = testo_dopo_vuoto(&resto[..inizio], &mut salta_separatore);
+        risultato.push_str(letterale);
+        let fine = resto[inizio..].find('}')
+            .ok_or_else(|| format!("Template non valido, manca '}}': {}", template))? + inizio;
+        let segnaposto = &resto[inizio + 1..fine];
+
+        let valore = match segnaposto {
+            "camera" => fotocamera.map(pulisci_componente).unwrap_or_default(),
+            "orig_stem" => stem.to_string(),
+            "ext" => ext.to_lowercase(),
+            "year" => format!("{:04}", data.year()),
+            "month" => format!("{:02}", data.month()),
+            "day" => format!("{:02}", data.day()),
+            "date" => data.format("%Y%m%d_%H%M%S").to_string(),
+            s if s.starts_with("date:") => {
+                let mut testo = String::new();
+                // Un formato strftime non valido fa fallire write! invece di andare in panic
+                write!(testo, "{}", data.format(&s[5..]))
+                    .map_err(|_| format!("Formato data non valido: {}", &s[5..]))?;
+                testo
+            }
+            altro => return Err(format!("Segnaposto sconosciuto: {{{}}}", altro)),
+        };
+
+        if valore.is_empty() {
+            // Un segnaposto vuoto (es. fotocamera sconosciuta) lascerebbe un separatore doppio
+            // o in testa: si toglie quello del template accanto a lui, il resto del nome non si tocca
+            if letterale.ends_with(SEPARATORI) {
+                risultato.pop();
+            } else if risultato.is_empty() {
+                salta_separatore = true;
+            }
+        }
+        risultato.push_str(&valore);
+        resto = &resto[fine + 1..];
+    }
+    risultato.push_str(testo_dopo_vuoto(resto, &mut salta_separatore));
+
+    if risultato.contains('/') || risultato.contains('\\') {
+        return Err(format!("Il nome generato contiene un separatore di percorso: {}", risultato));
+    }
+    if risultato.is_empty() || risultato.starts_with('.') {
+        return Err(format!("Nome generato non valido: '{}'", risultato));
+    }
+
+    Ok(risultato)
+}
+
+fn dividi_nome(nome: &str) -> (&str, Option<&str>) {
+    match nome.rfind('.') {
+        Some(pos) if pos > 0 => (&nome[..pos], Some(&nome[pos + 1..])),
+        _ => (nome, None),
+    }
+}
+
+// Nome di un file compagno (JSON, XMP) dopo la rinomina del file principale:
+// "IMG_1.jpg.supplemental-metadata.json" -> "NUOVO.jpg.supplemental-metadata.json"
+fn nome_compagno(compagno: &Path, vecchio: &Path, nuovo_nome_file: &str) -> Option<String> {
+    let nome_compagno = compagno.file_name()?.to_str()?;
+    let vecchio_nome = vecchio.file_name()?.to_str()?;
+    let vecchio_stem = vecchio.file_stem()?.to_str()?;
+    let (nuovo_stem, _) = dividi_nome(nuovo_nome_file);
+
+    if let Some(resto) = nome_compagno.strip_prefix(vecchio_nome) {
+        Some(format!("{}{}", nuovo_nome_file, resto))
+    } else {
+        nome_compagno.strip_prefix(vecchio_stem)
+            .map(|resto| format!("{}{}", nuovo_stem, resto))
+    }
+}
+
+fn file_compagni(file: &Path) -> Vec<PathBuf> {
+    let mut compagni = Vec::new();
+    if let Some(json) = crate::trova_file_json(file) {
+        compagni.push(json);
+    }
+    if let Some(xmp) = crate::sidecar::trova_sidecar_xmp(file) {
+        compagni.push(xmp);
+    }
+    compagni
+}
+
+// Operazioni del gruppo con un dato nome base (file principali + compagni).
+// Con una cartella di destinazione i file vengono anche spostati lì
+pub(crate) fn operazioni_gruppo(principali: &[(PathBuf, String)], dir_destinazione: Option<&Path>, stem: &str) -> Vec<OperazioneRinomina> {
+    let mut operazioni: Vec<OperazioneRinomina> = Vec::new();
+    let mut sorgenti = HashSet::new();
+
+    for (file, ext) in principali {
+        let nuovo_nome_file = if ext.is_empty() {
+            stem.to_string()
+        } else {
+            format!("{}.{}", stem, ext)
+        };
+        let directory = dir_destinazione.unwrap_or(file.parent().unwrap_or(Path::new("")));
+
+        if sorgenti.insert(file.clone()) {
+            operazioni.push(OperazioneRinomina { da: file.clone(), a: directory.join(&nuovo_nome_file) });
+        }
+        for compagno in file_compagni(file) {
+            // JSON/XMP senza estensione nel nome possono essere condivisi da RAW e JPG
+            if !sorgenti.insert(compagno.clone()) {
+                continue;
+            }
+            if let Some(nome) = nome_compagno(&compagno, file, &nuovo_nome_file) {
+                let dir_compagno = dir_destinazione
+                    .unwrap_or(compagno.parent().unwrap_or(Path::new("")))
+                    .to_path_buf();
+                operazioni.push(OperazioneRinomina { da: compagno, a: dir_compagno.join(nome) });
+            }
+        }
+    }
+
+    operazioni.retain(|op| op.da != op.a);
+    operazioni
+}
+
+// I JPG di una coppia RAW+JPG seguono il RAW: non vanno trattati da soli
+pub(crate) fn jpg_di_coppia(foto_list: &[&FotoData]) -> HashSet<PathBuf> {
+    foto_list.iter()
+        .filter_map(|f| crate::trova_file_jpg_associato(&f.path))
+        .collect()
+}
+
+pub fn pianifica_rinomina(foto_list: &[&FotoData], template: &str) -> PianoRinomina {
+    let mut piano = PianoRinomina::default();
+
+    let jpg_di_coppia = jpg_di_coppia(foto_list);
+
+    let mut destinazioni_occupate: HashSet<PathBuf> = HashSet::new();
+
+    for foto in foto_list {
+        if jpg_di_coppia.contains(&foto.path) {
+            continue;
+        }
+        if foto.origine_archivio.is_some() {
+            piano.saltate.push((foto.path.clone(), "ancora nell'archivio Takeout, estrarla prima".to_string()));
+            continue;
+        }
+
+        let data = match foto.proposta_datetime_original {
+            Some(_) if foto.is_foto_1900() => {
+                piano.saltate.push((foto.path.clone(), "data segnaposto 1900-01-01".to_string()));
+                continue;
+            }
+            Some(d) => d,
+            None => {
+                piano.saltate.push((foto.path.clone(), "nessuna data proposta".to_string()));
+                continue;
+            }
+        };
+
+        let stem = foto.path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        let ext = foto.path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+
+        let nuovo_nome = match applica_template(template, data, foto.fotocamera.as_deref(), stem, ext) {
+            Ok(n) => n,
+            Err(e) => {
+                piano.saltate.push((foto.path.clone(), e));
+                continue;
+            }
+        };
+        let (nuovo_stem, nuova_ext) = dividi_nome(&nuovo_nome);
+
+        let mut principali = vec![(foto.path.clone(), nuova_ext.unwrap_or_default().to_string())];
+        if let Some(jpg) = crate::trova_file_jpg_associato(&foto.path) {
+            let ext_jpg = jpg.extension().and_then(|e| e.to_str()).unwrap_or("jpg").to_lowercase();
+            principali.push((jpg, ext_jpg));
+        }
+        let sorgenti: HashSet<PathBuf> = principali.iter().map(|(p, _)| p.clone()).collect();
+
+        // Collisioni: aggiunge _1, _2, ... finché tutti i file del gruppo hanno un nome libero
+        let mut trovato = None;
+        for n in 0..10000 {
+            let stem_candidato = if n == 0 {
+                nuovo_stem.to_string()
+            } else {
+                format!("{}_{}", nuovo_stem, n)
+            };
+            let operazioni = operazioni_gruppo(&principali, None, &stem_candidato);
+            let libero = operazioni.iter().all(|op| {
+                !destinazioni_occupate.contains(&op.a) &&
+                    (!op.a.exists() || sorgenti.contains(&op.a))
+            });
+            if libero {
+                trovato = Some(operazioni);
+                break;
+            }
+        }
+
+        match trovato {
+            Some(operazioni) if operazioni.is_empty() => {
+                // Ha già il nome giusto
+            }
+            Some(operazioni) => {
+                for op in &operazioni {
+                    destinazioni_occupate.insert(op.a.clone());
+                }
+                piano.gruppi.push(GruppoRinomina { foto: foto.path.clone(), operazioni });
+            }
+            None => piano.saltate.push((foto.path.clone(), "nessun nome libero disponibile".to_string())),
+        }
+    }
+
+    piano
+}
+
+pub fn esegui_rinomina(piano: &PianoRinomina) -> Result<EsitoRinomina, String> {
+    // Senza journal non si potrebbe annullare: meglio non procedere
+    let journal = Journal::nuovo("rinomina")?;
+    esegui_gruppi(piano, &journal)
+}
+
+fn esegui_gruppi(piano: &PianoRinomina, journal: &Journal) -> Result<EsitoRinomina, String> {
+    let mut esito = EsitoRinomina {
+        journal: Some(journal.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    for gruppo in &piano.gruppi {
+        // fs::rename sovrascrive in silenzio: tutte le destinazioni si controllano prima di
+        // toccare il gruppo (una può essere il nome attuale di un altro file del gruppo)
+        let sorgenti: HashSet<&Path> = gruppo.operazioni.iter().map(|op| op.da.as_path()).collect();
+        if let Some(op) = gruppo.operazioni.iter().find(|op| op.a.exists() && !sorgenti.contains(op.a.as_path())) {
+            esito.errori.push(format!("{} esiste già, gruppo {} non rinominato", op.a.display(), gruppo.foto.display()));
+            continue;
+        }
+
+        let mut fatte: Vec<&OperazioneRinomina> = Vec::new();
+        let mut errore = None;
+        for op in &gruppo.operazioni {
+            let risultato = if op.a.exists() {
+                Err(format!("{} esiste già", op.a.display()))
+            } else {
+                std::fs::rename(&op.da, &op.a)
+                    .map_err(|e| format!("Errore rinomina {} -> {}: {}", op.da.display(), op.a.display(), e))
+            };
+            if let Err(e) = risultato {
+                errore = Some(e);
+                break;
+            }
+            // Registrata subito: se il programma si interrompe a metà gruppo si può annullare
+            journal.registra(&VoceJournal::Rinomina { da: op.da.clone(), a: op.a.clone() })?;
+            fatte.push(op);
+        }
+
+        let Some(errore) = errore else {
+            esito.eseguite.extend(fatte.into_iter().cloned());
+            continue;
+        };
+        // Un gruppo a metà separa il RAW dal suo JPG o dal JSON: si rimette com'era. Anche
+        // il ritorno va nel journal, così l'annullamento dell'esecuzione resta coerente
+        esito.errori.push(format!("{}, gruppo {} riportato ai nomi di prima", errore, gruppo.foto.display()));
+        for op in fatte.into_iter().rev() {
+            match std::fs::rename(&op.a, &op.da) {
+                Ok(()) => journal.registra(&VoceJournal::Rinomina { da: op.a.clone(), a: op.da.clone() })?,
+                Err(e) => {
+                    esito.errori.push(format!("Errore ripristino {} -> {}: {}", op.a.display(), op.da.display(), e));
+                    esito.eseguite.push(op.clone());
+                }
+            }
+        }
+    }
+
+    Ok(esito)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2019-07-14T09:05:03Z").unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn applica_template_segnaposto() {
+        assert_eq!(applica_template(TEMPLATE_PREDEFINITO, data(), Some("NIKON D750"), "DSC_0101", "JPG"),
+                   Ok("20190714_090503_NIKON-D750_DSC_0101.jpg".to_string()));
+        assert_eq!(applica_template("{year}/{month}-{day}", data(), None, "a", "jpg").unwrap_err(),
+                   "Il nome generato contiene un separatore di percorso: 2019/07-14");
+        assert_eq!(applica_template("{year}-{month}-{day}_{date}.{ext}", data(), None, "a", "jpg"),
+                   Ok("2019-07-14_20190714_090503.jpg".to_string()));
+        assert_eq!(applica_template("{date:%d.%m.%Y}_{orig_stem}.{ext}", data(), None, "Città", "jpg"),
+                   Ok("14.07.2019_Città.jpg".to_string()));
+    }
+
+    #[test]
+    fn applica_template_fotocamera_mancante_senza_separatori_doppi() {
+        assert_eq!(applica_template(TEMPLATE_PREDEFINITO, data(), None, "IMG_1", "jpg"),
+                   Ok("20190714_090503_IMG_1.jpg".to_string()));
+        assert_eq!(applica_template("{camera}_{orig_stem}_{camera}.{ext}", data(), None, "IMG", "jpg"),
+                   Ok("IMG.jpg".to_string()));
+        assert_eq!(applica_template("{date}-{camera}-{orig_stem}.{ext}", data(), None, "IMG", "jpg"),
+                   Ok("20190714_090503-IMG.jpg".to_string()));
+    }
+
+    #[test]
+    fn applica_template_fotocamera_mancante_non_cambia_il_resto_del_nome() {
+        assert_eq!(applica_template(TEMPLATE_PREDEFINITO, data(), None, "IMG__0001", "jpg"),
+                   Ok("20190714_090503_IMG__0001.jpg".to_string()));
+        assert_eq!(applica_template("{camera}_{orig_stem}.{ext}", data(), None, "_DSC1234", "jpg"),
+                   Ok("_DSC1234.jpg".to_string()));
+        assert_eq!(applica_template("{date:%Y__%m}_{camera}_x__y.{ext}", data(), None, "a", "jpg"),
+                   Ok("2019__07_x__y.jpg".to_string()));
+    }
+
+    #[test]
+    fn applica_template_rifiuta_template_non_validi() {
+        assert!(applica_template("{date", data(), None, "a", "jpg").is_err());
+        assert!(applica_template("{sconosciuto}.{ext}", data(), None, "a", "jpg").is_err());
+        assert!(applica_template("{date:%Q}", data(), None, "a", "jpg").is_err());
+        assert!(applica_template("{camera}.{ext}", data(), None, "a", "jpg").is_err()); // Solo ".jpg"
+        assert!(applica_template("{camera}", data(), None, "a", "jpg").is_err());       // Vuoto
+    }
+
+    fn cartella(nome: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("corrigi-exif-rinomina-{}-{}", nome, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn journal_in(dir: &Path) -> Journal {
+        let path = dir.join("prova-rinomina.jsonl");
+        std::fs::write(&path, "").unwrap();
+        Journal::riapri(&path).unwrap()
+    }
+
+    fn piano(dir: &Path, operazioni: &[(&str, &str)]) -> PianoRinomina {
+        let operazioni = operazioni.iter()
+            .map(|(da, a)| OperazioneRinomina { da: dir.join(da), a: dir.join(a) })
+            .collect();
+        PianoRinomina { gruppi: vec![GruppoRinomina { foto: dir.join("P1.ORF"), operazioni }], ..Default::default() }
+    }
+
+    #[test]
+    fn esegui_rinomina_destinazione_occupata_non_tocca_il_gruppo() {
+        let dir = cartella("occupata");
+        for nome in ["P1.ORF", "P1.JPG", "2019.JPG"] {
+            std::fs::write(dir.join(nome), nome).unwrap();
+        }
+        let journal = journal_in(&dir);
+        let esito = esegui_gruppi(&piano(&dir, &[("P1.ORF", "2019.ORF"), ("P1.JPG", "2019.JPG")]), &journal).unwrap();
+        assert!(esito.eseguite.is_empty());
+        assert_eq!(esito.errori.len(), 1);
+        assert!(dir.join("P1.ORF").exists() && !dir.join("2019.ORF").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn esegui_rinomina_errore_a_meta_gruppo_riporta_i_nomi() {
+        let dir = cartella("meta");
+        std::fs::write(dir.join("P1.ORF"), "raw").unwrap(); // P1.JPG manca: la seconda rinomina fallisce
+        let journal = journal_in(&dir);
+        let esito = esegui_gruppi(&piano(&dir, &[("P1.ORF", "2019.ORF"), ("P1.JPG", "2019.JPG")]), &journal).unwrap();
+        assert!(esito.eseguite.is_empty());
+        assert_eq!(esito.errori.len(), 1);
+        assert!(dir.join("P1.ORF").exists() && !dir.join("2019.ORF").exists());
+
+        // Andata e ritorno nel journal: annullarlo lascia i file dove sono
+        let voci = crate::journal::leggi_journal(journal.path()).unwrap();
+        assert_eq!(voci.len(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,224 @@
+use chrono::{DateTime, Duration, Months, Utc};
+use crate::FotoData;
+use crate::date_manuali::{CampoData, DataManuale, Precisione};
+
+// Spostamento in blocco dell'orario: fotocamera con l'orologio sbagliato
+// (ora legale, anno, fuso). Il risultato diventa la data manuale delle foto,
+// quindi si vede come proposta e si scrive come le altre
+
+// Anni e mesi sono di calendario (+1y da un 29 febbraio va al 28), il resto in secondi
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Scostamento {
+    pub mesi: i32,
+    pub secondi: i64,
+}
+
+impl Scostamento {
+    // Lo scostamento se chrono sa rappresentarlo come durata, altrimenti None
+    fn controllato(self) -> Option<Self> {
+        Duration::try_seconds(self.secondi).map(|_| self)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mesi == 0 && self.secondi == 0
+    }
+
+    pub fn applica(&self, data: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let data = match self.mesi {
+            0 => data,
+            m if m > 0 => data.checked_add_months(Months::new(m as u32))?,
+            m => data.checked_sub_months(Months::new(m.unsigned_abs()))?,
+        };
+        data.checked_add_signed(Duration::try_seconds(self.secondi)?)
+    }
+
+    // Es. "+1y 2mo", "-1h 30m", "+3d 5s"
+    pub fn descrizione(&self) -> String {
+        let negativo = self.mesi < 0 || (self.mesi == 0 && self.secondi < 0);
+        let (mesi, secondi) = (self.mesi.unsigned_abs() as u64, self.secondi.unsigned_abs());
+        let mut parti = Vec::new();
+        for (valore, unita) in [
+            (mesi / 12, "y"), (mesi % 12, "mo"),
+            (secondi / 86400, "d"), (secondi % 86400 / 3600, "h"), (secondi % 3600 / 60, "m"), (secondi % 60, "s"),
+        ] {
+            if valore > 0 {
+                parti.push(format!("{}{}", valore, unita));
+            }
+        }
+        if parti.is_empty() {
+            return "0s".to_string();
+        }
+        format!("{}{}", if negativo { "-" } else { "+" }, parti.join(" "))
+    }
+}
+
+// Accetta "+1h", "-1y", "+2d 3h 15m 10s", "+1mo", "-01:00" o "+1:30:00".
+// Il segno iniziale vale per tutto
+pub fn interpreta_scostamento(testo: &str) -> Result<Scostamento, String> {
+    let errore = || format!("Scostamento non valido: \"{}\" (es. +1h, -1y, +2d 3h, -01:30)", testo.trim());
+    let troppo_grande = || format!("Scostamento troppo grande: \"{}\"", testo.trim());
+    let testo = testo.trim();
+    let (segno, resto) = match testo.chars().next() {
+        Some('-') => (-1, &testo[1..]),
+        Some('+') => (1, &testo[1..]),
+        _ => (1, testo),
+    };
+    let resto = resto.trim();
+    if resto.is_empty() {
+        return Err(errore());
+    }
+
+    // Formato orologio HH:MM[:SS]
+    if resto.contains(':') {
+        let parti: Vec<i64> = resto.split(':')
+            .map(|p| p.trim().parse::<i64>().map_err(|_| errore()))
+            .collect::<Result<_, _>>()?;
+        let (h, m, s) = match parti.as_slice() {
+            [h, m] => (*h, *m, 0),
+            [h, m, s] => (*h, *m, *s),
+            _ => return Err(errore()),
+        };
+        let secondi = h.checked_mul(3600)
+            .and_then(|h| m.checked_mul(60).and_then(|m| h.checked_add(m)))
+            .and_then(|hm| hm.checked_add(s))
+            .and_then(|secondi| secondi.checked_mul(segno as i64))
+            .ok_or_else(troppo_grande)?;
+        return Scostamento { mesi: 0, secondi }.controllato().ok_or_else(troppo_grande);
+    }
+
+    // Somma con controllo: un numero enorme scritto nella GUI non deve far traboccare i conti
+    let somma = |totale: i64, valore: i64, unita: i64| valore.checked_mul(unita).and_then(|v| totale.checked_add(v)).ok_or_else(troppo_grande);
+    let somma_mesi = |totale: i32, valore: i64, unita: i32| i32::try_from(valore).ok()
+        .and_then(|v| v.checked_mul(unita))
+        .and_then(|v| totale.checked_add(v))
+        .ok_or_else(troppo_grande);
+    let mut scostamento = Scostamento::default();
+    let mut cifre = String::new();
+    let mut caratteri = resto.chars().filter(|c| !c.is_whitespace()).peekable();
+    while let Some(c) = caratteri.next() {
+        if c.is_ascii_digit() {
+            cifre.push(c);
+            continue;
+        }
+        let valore: i64 = cifre.parse().map_err(|_| errore())?;
+        cifre.clear();
+        match c {
+            'y' => scostamento.mesi = somma_mesi(scostamento.mesi, valore, 12)?,
+            'm' if caratteri.peek() == Some(&'o') => {
+                caratteri.next();
+                scostamento.mesi = somma_mesi(scostamento.mesi, valore, 1)?;
+            }
+            'd' => scostamento.secondi = somma(scostamento.secondi, valore, 86400)?,
+            'h' => scostamento.secondi = somma(scostamento.secondi, valore, 3600)?,
+            'm' => scostamento.secondi = somma(scostamento.secondi, valore, 60)?,
+            's' => scostamento.secondi = somma(scostamento.secondi, valore, 1)?,
+            _ => return Err(errore()),
+        }
+    }
+    // Numero senza unità in fondo
+    if !cifre.is_empty() {
+        return Err(errore());
+    }
+    // Con il segno i valori restano nei limiti: mesi e secondi sono positivi
+    scostamento.mesi *= segno;
+    scostamento.secondi *= segno as i64;
+    scostamento.controllato().ok_or_else(troppo_grande)
+}
+
+// Data segnata dalla fotocamera: l'EXIF, o il sidecar se il file non ce l'ha
+fn orologio_fotocamera(foto: &FotoData) -> Option<DateTime<Utc>> {
+    foto.exif_datetime_original.or(foto.xmp_datetime_original)
+}
+
+// Scostamento tra l'orologio della fotocamera in una foto di riferimento e l'ora vera
+pub fn scostamento_da_riferimento(foto: &FotoData, ora_vera: DateTime<Utc>) -> Option<Scostamento> {
+    let attuale = orologio_fotocamera(foto)?;
+    Some(Scostamento { mesi: 0, secondi: (ora_vera - attuale).num_seconds() })
+}
+
+// Fotocamere presenti, con il numero di foto, in ordine alfabetico
+pub fn fotocamere(foto_list: &[FotoData]) -> Vec<(String, usize)> {
+    let mut conteggi: std::collections::BTreeMap<&str, usize> = std::collections::BTreeMap::new();
+    for foto in foto_list {
+        if let Some(ref fotocamera) = foto.fotocamera {
+            *conteggi.entry(fotocamera).or_default() += 1;
+        }
+    }
+    conteggi.into_iter().map(|(f, n)| (f.to_string(), n)).collect()
+}
+
+// Sposta le foto indicate partendo sempre dall'orologio della fotocamera, non dalla
+// proposta: ripetere lo spostamento non lo somma. `etichetta` finisce nell'origine
+// della data manuale ("shift +1h"). Restituisce (spostate, senza data)
+pub fn applica_scostamento(foto_list: &mut [FotoData], indici: &[usize], scostamento: Scostamento, etichetta: &str) -> (usize, usize) {
+    let origine = format!("{} {}", etichetta, scostamento.descrizione());
+    let (mut spostate, mut senza_data) = (0, 0);
+    for &idx in indici {
+        let Some(foto) = foto_list.get_mut(idx) else {
+            continue;
+        };
+        let Some(dto) = orologio_fotocamera(foto).and_then(|d| scostamento.applica(d)) else {
+            senza_data += 1;
+            continue;
+        };
+        // CreateDate spostata dal suo valore, se c'è, altrimenti uguale alla nuova DateTimeOriginal
+        let cd = foto.exif_create_date.or(foto.xmp_create_date)
+            .and_then(|d| scostamento.applica(d))
+            .unwrap_or(dto);
+        let manuale = |data| Some(DataManuale { data, precisione: Precisione::Completa, origine: origine.clone() });
+        crate::date_manuali::imposta_data_manuale(foto, CampoData::DateTimeOriginal, manuale(dto));
+        crate::date_manuali::imposta_data_manuale(foto, CampoData::CreateDate, manuale(cd));
+        spostate += 1;
+    }
+    (spostate, senza_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scostamento(mesi: i32, secondi: i64) -> Scostamento {
+        Scostamento { mesi, secondi }
+    }
+
+    #[test]
+    fn interpreta_scostamento_unita_e_orologio() {
+        assert_eq!(interpreta_scostamento("+1h"), Ok(scostamento(0, 3600)));
+        assert_eq!(interpreta_scostamento("-1y"), Ok(scostamento(-12, 0)));
+        assert_eq!(interpreta_scostamento("+2d 3h 15m 10s"), Ok(scostamento(0, 2 * 86400 + 3 * 3600 + 15 * 60 + 10)));
+        assert_eq!(interpreta_scostamento("1y 2mo 1m"), Ok(scostamento(14, 60)));
+        assert_eq!(interpreta_scostamento("-01:30"), Ok(scostamento(0, -5400)));
+        assert_eq!(interpreta_scostamento("+1:30:05"), Ok(scostamento(0, 5405)));
+    }
+
+    #[test]
+    fn interpreta_scostamento_rifiuta_testo_non_valido() {
+        for testo in ["", "+", "1", "1x", "1h 5", "1:2:3:4", "a:b", "-1à"] {
+            assert!(interpreta_scostamento(testo).is_err(), "{}", testo);
+        }
+    }
+
+    #[test]
+    fn interpreta_scostamento_rifiuta_valori_enormi() {
+        for testo in ["+9999999999999999999y", "+3000000000y", "+9999999999999999d", "+999999999999999999s",
+                      "-9999999999999999:00", "+3000000000mo", "+200000000y"] {
+            assert!(interpreta_scostamento(testo).is_err(), "{}", testo);
+        }
+    }
+
+    #[test]
+    fn applica_fuori_intervallo_da_none() {
+        let data = DateTime::from_timestamp(0, 0).unwrap();
+        assert_eq!(scostamento(0, 3600).applica(data), DateTime::from_timestamp(3600, 0));
+        assert_eq!(scostamento(i32::MAX, 0).applica(data), None);
+        assert_eq!(scostamento(0, i64::MAX).applica(data), None);
+        assert_eq!(interpreta_scostamento("+100000000d").ok().and_then(|s| s.applica(data)), None);
+    }
+
+    #[test]
+    fn descrizione_come_interpretata() {
+        for testo in ["+1y 2mo", "-1h 30m", "+3d 5s"] {
+            assert_eq!(interpreta_scostamento(testo).unwrap().descrizione(), testo);
+        }
+    }
+}
@@ -0,0 +1,621 @@
+use chrono::{DateTime, Local, TimeZone, Utc};
+use rayon::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::FotoData;
+use crate::cache_scansione::CacheScansione;
+use crate::errori_scrittura::ErroreScrittura;
+use crate::journal::{Journal, VoceJournal};
+use crate::lavoro::ControlloLavoro;
+use crate::metadati::{MetadatiGoogle, PosizioneGps};
+use crate::sidecar::{self, ModalitaOutput, NomeSidecar};
+
+// Tutto ciò che serve per scrivere una foto, preparato prima di avviare il thread
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RichiestaScrittura {
+    pub path: PathBuf,
+    #[serde(deserialize_with = "deserializza_tag")]
+    pub campi: Vec<(&'static str, DateTime<Utc>)>,
+    pub metadati: Option<MetadatiGoogle>,
+    pub posizione: Option<PosizioneGps>,
+    pub usa_sidecar: bool,
+    pub data_file: Option<DateTime<Utc>>, // mtime/atime da impostare dopo la scrittura
+    #[serde(deserialize_with = "deserializza_tag")]
+    pub precedenti: Vec<(&'static str, Option<DateTime<Utc>>)>, // Valori prima della scrittura, per annullarla
+}
+
+// Da una scrittura sospesa salvata: i nomi dei tag si riconoscono tra quelli che si scrivono
+fn deserializza_tag<'de, D, T>(deserializer: D) -> Result<Vec<(&'static str, T)>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let campi: Vec<(String, T)> = Vec::deserialize(deserializer)?;
+    campi.into_iter()
+        .map(|(nome, valore)| {
+            let tag = ["DateTimeOriginal", "CreateDate", "ModifyDate"].into_iter()
+                .find(|t| *t == nome)
+                .ok_or_else(|| serde::de::Error::custom(format!("tag sconosciuto: {}", nome)))?;
+            Ok((tag, valore))
+        })
+        .collect()
+}
+
+// Fuso in cui leggere l'ora EXIF (senza fuso) per impostare la data del file
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum, Serialize, Deserialize)]
+pub enum FusoDataFile {
+    #[default]
+    #[value(name = "local")]
+    #[serde(rename = "local")]
+    Locale, // Fuso di questo computer: file manager e NAS mostrano la stessa ora della foto
+    #[value(name = "utc")]
+    #[serde(rename = "utc")]
+    Utc,    // Per archivi condivisi tra fusi diversi
+}
+
+impl FusoDataFile {
+    pub fn display_name(&self) -> &str {
+        match self {
+            FusoDataFile::Locale => "Local time zone",
+            FusoDataFile::Utc => "UTC",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpzioniScrittura {
+    pub modalita_output: ModalitaOutput,
+    pub nome_sidecar: NomeSidecar,
+    pub scrivi_metadati_json: bool,
+    pub imposta_data_file: bool, // Porta mtime/atime alla DateTimeOriginal finale
+    #[serde(default)]
+    pub fuso_data_file: FusoDataFile,
+    #[serde(default)]
+    pub riparazione: bool,       // Ricostruisce i metadati illeggibili invece di fallire (solo EXIF incorporato)
+    #[serde(default = "vero")]
+    pub verifica: bool,          // Rilegge i tag scritti e li confronta con quelli voluti
+    #[serde(default)]
+    pub verifica_pixel: bool,    // Controlla anche che i dati dell'immagine (JPEG) non siano cambiati
+    #[serde(default)]
+    pub backup: bool,            // Copia ogni file prima di scriverlo
+    #[serde(default)]
+    pub dir_backup: Option<PathBuf>, // None: cartella predefinita
+    #[serde(default)]
+    pub elimina_backup_verificati: bool, // Cancella la copia se la verifica conferma la scrittura
+}
+
+fn vero() -> bool {
+    true
+}
+
+impl Default for OpzioniScrittura {
+    fn default() -> Self {
+        Self {
+            modalita_output: ModalitaOutput::Incorporato,
+            nome_sidecar: NomeSidecar::ConEstensione,
+            scrivi_metadati_json: false,
+            imposta_data_file: false,
+            fuso_data_file: FusoDataFile::Locale,
+            riparazione: false,
+            verifica: true,
+            verifica_pixel: false,
+            backup: false,
+            dir_backup: None,
+            elimina_backup_verificati: false,
+        }
+    }
+}
+
+// DateTimeOriginal che la foto avrà dopo la scrittura
+pub fn data_finale(foto: &FotoData) -> Option<DateTime<Utc>> {
+    if foto.is_foto_1900() {
+        // Il segnaposto 1900-01-01 non deve finire nella data del file
+        return foto.exif_datetime_original.or(foto.xmp_datetime_original);
+    }
+    foto.proposta_datetime_original
+        .or(foto.exif_datetime_original)
+        .or(foto.xmp_datetime_original)
+}
+
+impl RichiestaScrittura {
+    pub fn da_foto(foto: &FotoData, opzioni: &OpzioniScrittura) -> Self {
+        let mut campi = Vec::new();
+        if let Some(data) = foto.proposta_datetime_original {
+            campi.push(("DateTimeOriginal", data));
+        }
+        if let Some(data) = foto.proposta_create_date {
+            campi.push(("CreateDate", data));
+        }
+
+        let metadati = if opzioni.scrivi_metadati_json {
+            foto.metadati_json.clone()
+        } else {
+            None
+        };
+
+        let data_file = if opzioni.imposta_data_file {
+            data_finale(foto)
+        } else {
+            None
+        };
+
+        let usa_sidecar = opzioni.modalita_output.usa_sidecar(&foto.path);
+        let precedenti = campi.iter()
+            .map(|(campo, _)| {
+                let valore = match (*campo, usa_sidecar) {
+                    ("DateTimeOriginal", false) => foto.exif_datetime_original,
+                    ("DateTimeOriginal", true) => foto.xmp_datetime_original,
+                    (_, false) => foto.exif_create_date,
+                    (_, true) => foto.xmp_create_date,
+                };
+                (*campo, valore)
+            })
+            .collect();
+
+        Self {
+            path: foto.path.clone(),
+            campi,
+            metadati,
+            posizione: foto.posizione_json,
+            usa_sidecar,
+            data_file,
+            precedenti,
+        }
+    }
+
+    // Solo data del file, senza toccare i metadati (foto con EXIF già corretto)
+    pub fn solo_data_file(foto: &FotoData) -> Option<Self> {
+        Some(Self {
+            path: foto.path.clone(),
+            campi: Vec::new(),
+            metadati: None,
+            posizione: None,
+            usa_sidecar: false,
+            data_file: Some(data_finale(foto)?),
+            precedenti: Vec::new(),
+        })
+    }
+}
+
+// True se scrivere la foto cambierebbe qualcosa: proposta diversa dall'EXIF attuale,
+// metadati JSON da ripristinare o data del file da impostare
+pub fn richiede_scrittura(foto: &FotoData, opzioni: &OpzioniScrittura) -> bool {
+    let cambia = |attuale: Option<DateTime<Utc>>, proposta: Option<DateTime<Utc>>| {
+        matches!((attuale, proposta), (Some(a), Some(p)) if a != p) || (attuale.is_none() && proposta.is_some())
+    };
+    let cambia_dt = cambia(foto.exif_datetime_original, foto.proposta_datetime_original);
+    let cambia_cd = cambia(foto.exif_create_date, foto.proposta_create_date);
+    let ha_metadati = opzioni.scrivi_metadati_json && foto.metadati_json.is_some();
+    // Con l'opzione mtime anche le foto già corrette vanno toccate
+    let ha_data_file = opzioni.imposta_data_file && data_finale(foto).is_some();
+    cambia_dt || cambia_cd || ha_metadati || ha_data_file
+}
+
+fn mtime(path: &Path) -> Option<i64> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some(filetime::FileTime::from_last_modification_time(&meta).unix_seconds())
+}
+
+// Tag scritti, per dire negli errori cosa non è andato a buon fine
+fn nomi_tag(richiesta: &RichiestaScrittura) -> String {
+    let mut nomi: Vec<&str> = richiesta.campi.iter().map(|(campo, _)| *campo).collect();
+    if richiesta.metadati.is_some() {
+        nomi.push("description/people/GPS");
+    }
+    nomi.join(", ")
+}
+
+// Con un journal la scrittura viene registrata e si può annullare con annulla_journal.
+// `dir_backup` è la cartella delle copie dell'esecuzione (backup::dir_esecuzione), se attivo
+pub fn esegui_scrittura(richiesta: &RichiestaScrittura, opzioni: &OpzioniScrittura, journal: Option<&Journal>,
+                        dir_backup: Option<&Path>) -> Result<(), ErroreScrittura> {
+    let errore = |tag: &str, messaggio: String| ErroreScrittura::nuovo(richiesta.path.clone(), tag, None, messaggio);
+    let campi: Vec<(&str, DateTime<Utc>)> = richiesta.campi.clone();
+    let mut voce = VoceJournal::Scrittura {
+        path: richiesta.path.clone(),
+        sidecar: None,
+        sidecar_creato: false,
+        precedenti: richiesta.precedenti.iter().map(|(c, v)| (c.to_string(), *v)).collect(),
+        date_file_precedenti: Vec::new(),
+        tag_precedenti: Vec::new(),
+    };
+
+    let mut verifica = Ok(());
+    let mut copia = None;
+    if !campi.is_empty() || richiesta.metadati.is_some() {
+        let esistente = sidecar::trova_sidecar_xmp(&richiesta.path);
+        if opzioni.backup {
+            // Si copia il file che exiftool sta per riscrivere: la foto o il sidecar già presente
+            let da_copiare = if richiesta.usa_sidecar { esistente.as_deref() } else { Some(richiesta.path.as_path()) };
+            if let Some(da_copiare) = da_copiare {
+                let dir_backup = dir_backup.ok_or_else(|| errore("backup", "Impossibile determinare la cartella dei backup".to_string()))?;
+                copia = copia_prima_di_scrivere(da_copiare, dir_backup, journal).map_err(|e| errore("backup", e))?;
+            }
+        }
+        // Senza -all= exiftool modifica solo i tag indicati: i valori che avevano si leggono
+        // prima, così l'annullamento li rimette anche senza copia di backup
+        let args = if richiesta.usa_sidecar {
+            esistente.as_ref().map(|xmp| (xmp.as_path(), sidecar::argomenti_aggiornamento_sidecar(&campi,
+                richiesta.metadati.as_ref(), richiesta.posizione.as_ref())))
+        } else if campi.is_empty() && !opzioni.riparazione {
+            Some((richiesta.path.as_path(), crate::argomenti_scrittura_exiftool_con(&campi, richiesta.metadati.as_ref(), false)))
+        } else {
+            None
+        };
+        if let (Some(_), Some((file, args)), VoceJournal::Scrittura { ref mut tag_precedenti, .. }) = (journal, args, &mut voce) {
+            *tag_precedenti = leggi_tag_precedenti(file, &tag_scritti(&args)).map_err(|e| errore(&nomi_tag(richiesta), e))?;
+        }
+        if richiesta.usa_sidecar {
+            // L'originale resta intatto: date, GPS e descrizione vanno nel .xmp
+            let xmp = sidecar::scrivi_sidecar_xmp(&richiesta.path, opzioni.nome_sidecar, &campi,
+                                                  richiesta.metadati.as_ref(), richiesta.posizione.as_ref())
+                .map_err(|e| errore(&format!("{} (XMP sidecar)", nomi_tag(richiesta)), e))?;
+            if opzioni.verifica {
+                verifica = esito_verifica(&richiesta.path, Ok(crate::verifica::verifica_sidecar(&xmp, &campi)));
+            }
+            if let VoceJournal::Scrittura { ref mut sidecar, ref mut sidecar_creato, .. } = voce {
+                *sidecar_creato = esistente.is_none();
+                *sidecar = Some(xmp);
+            }
+        } else {
+            let impronta_prima = if opzioni.verifica_pixel { impronta_immagine(&richiesta.path) } else { None };
+            crate::scrivi_campi_exif(&richiesta.path, &campi, richiesta.metadati.as_ref(), opzioni.riparazione)
+                .map_err(|f| {
+                    // Alcuni errori exiftool li stampa su stdout
+                    let messaggio = if f.stderr.trim().is_empty() { f.stdout } else { f.stderr };
+                    ErroreScrittura::nuovo(richiesta.path.clone(), nomi_tag(richiesta), f.codice_uscita, messaggio)
+                })?;
+            if opzioni.verifica {
+                let descrizione = richiesta.metadati.as_ref().and_then(|m| m.descrizione.as_deref());
+                verifica = esito_verifica(&richiesta.path, crate::verifica::verifica_exif(&richiesta.path, &campi, descrizione));
+            }
+            if let (Some(prima), Ok(())) = (impronta_prima, &verifica) {
+                if impronta_immagine(&richiesta.path) != Some(prima) {
+                    verifica = Err(ErroreScrittura::verifica(richiesta.path.clone(), "image data",
+                                                             "I dati dell'immagine sono cambiati con la scrittura"));
+                }
+            }
+        }
+    }
+
+    if let Some(data) = richiesta.data_file {
+        // Anche JSON e sidecar XMP, così restano ordinati accanto alla foto
+        for path in file_della_foto(&richiesta.path) {
+            if let (Some(precedente), VoceJournal::Scrittura { ref mut date_file_precedenti, .. }) = (mtime(&path), &mut voce) {
+                date_file_precedenti.push((path.clone(), precedente));
+            }
+            imposta_data_file(&path, data, opzioni.fuso_data_file).map_err(|e| errore("FileModifyDate", e))?;
+        }
+    }
+
+    // Il file è comunque stato scritto: la voce va nel journal anche se la verifica fallisce,
+    // così la scrittura si può annullare
+    if let Some(journal) = journal {
+        journal.registra(&voce).map_err(|e| errore("journal", e))?;
+    }
+
+    // Scrittura confermata dalla rilettura: la copia non serve più (l'annullamento usa exiftool)
+    if let (Some((copia, dir_esecuzione)), true, Ok(())) = (copia, opzioni.verifica && opzioni.elimina_backup_verificati, &verifica) {
+        if let Err(e) = crate::backup::elimina_copia(&copia, &dir_esecuzione) {
+            eprintln!("[DEBUG] {}", e);
+        }
+    }
+    verifica
+}
+
+// Copia il file nella cartella di backup dell'esecuzione e la registra nel journal prima
+// della scrittura. Restituisce (copia, cartella dell'esecuzione) se la copia è nuova
+fn copia_prima_di_scrivere(path: &Path, dir_esecuzione: &Path, journal: Option<&Journal>) -> Result<Option<(PathBuf, PathBuf)>, String> {
+    let Some(copia) = crate::backup::copia(path, dir_esecuzione)? else {
+        return Ok(None);
+    };
+    if let Some(journal) = journal {
+        journal.registra(&VoceJournal::Backup { originale: path.to_path_buf(), copia: copia.clone() })?;
+    }
+    Ok(Some((copia, dir_esecuzione.to_path_buf())))
+}
+
+// Discrepanze della rilettura come errore, con i tag diversi da quelli voluti
+fn esito_verifica(path: &Path, discrepanze: Result<Vec<crate::verifica::Discrepanza>, String>) -> Result<(), ErroreScrittura> {
+    match discrepanze {
+        Ok(discrepanze) if discrepanze.is_empty() => Ok(()),
+        Ok(discrepanze) => {
+            let tag: Vec<&str> = discrepanze.iter().map(|d| d.tag.as_str()).collect();
+            let messaggio: Vec<String> = discrepanze.iter().map(|d| d.to_string()).collect();
+            Err(ErroreScrittura::verifica(path.to_path_buf(), tag.join(", "), messaggio.join("; ")))
+        }
+        Err(e) => Err(ErroreScrittura::verifica(path.to_path_buf(), "EXIF", e)),
+    }
+}
+
+// Impronta dei dati dell'immagine; None se non si può calcolare (non JPEG, file illeggibile)
+fn impronta_immagine(path: &Path) -> Option<blake3::Hash> {
+    crate::verifica::impronta_immagine(path)
+        .inspect_err(|e| eprintln!("[DEBUG] {}", e))
+        .ok()
+        .flatten()
+}
+
+// La foto e i file che una scrittura o un annullamento possono toccare: sidecar e JSON
+pub fn file_della_foto(path: &Path) -> Vec<PathBuf> {
+    std::iter::once(path.to_path_buf())
+        .chain(crate::trova_file_json(path))
+        .chain(sidecar::trova_sidecar_xmp(path))
+        .collect()
+}
+
+// Esito della scrittura di una foto, per rileggere dopo solo i file toccati
+#[derive(Debug, Clone)]
+pub struct EsitoScrittura {
+    pub path: PathBuf,
+    pub risultato: Result<(), ErroreScrittura>,
+}
+
+// Scrive le foto in parallelo nel pool rayon corrente, un esito per richiesta tentata nello
+// stesso ordine. `progresso` conta (riuscite, fallite) mentre si procede; con `controllo`
+// annullato le richieste non ancora iniziate si saltano e non hanno esito. Dalla `cache`
+// di scansione si tolgono i file tentati, anche quelli falliti che possono essere stati toccati
+pub fn esegui_scritture(richieste: &[RichiestaScrittura], opzioni: &OpzioniScrittura, journal: Option<&Journal>,
+                        cache: Option<&mut CacheScansione>, progresso: Option<&Mutex<(usize, usize)>>,
+                        controllo: Option<&ControlloLavoro>) -> Vec<EsitoScrittura> {
+    // Una sola cartella di backup per tutta l'esecuzione, anche senza journal
+    let dir_backup = opzioni.backup
+        .then(|| crate::backup::dir_esecuzione(opzioni.dir_backup.as_deref(), journal.map(|j| j.path())))
+        .flatten();
+    let esiti: Vec<EsitoScrittura> = richieste.par_iter()
+        .filter_map(|richiesta| {
+            if !crate::lavoro::puo_continuare(controllo) {
+                return None;
+            }
+            let risultato = esegui_scrittura(richiesta, opzioni, journal, dir_backup.as_deref());
+            if let Err(ref e) = risultato {
+                eprintln!("EXIF write error for {}: {}", richiesta.path.display(), e);
+            }
+            if let Some(progresso) = progresso {
+                let mut counter = progresso.lock().unwrap();
+                if risultato.is_ok() {
+                    counter.0 += 1;
+                } else {
+                    counter.1 += 1;
+                }
+            }
+            Some(EsitoScrittura { path: richiesta.path.clone(), risultato })
+        })
+        .collect();
+    if let Some(cache) = cache {
+        let toccati: Vec<PathBuf> = esiti.iter().flat_map(|e| file_della_foto(&e.path)).collect();
+        cache.rimuovi_tutti(toccati.iter().map(PathBuf::as_path));
+    }
+    esiti
+}
+
+// Scrittura interrotta da riprendere: le richieste non ancora eseguite, le opzioni e il
+// journal a cui accodare le nuove voci (così un solo `undo` annulla tutta l'esecuzione).
+// Si salva all'inizio di ogni scrittura e si cancella alla fine: se il programma viene
+// chiuso o si blocca a metà il file resta, e le foto già registrate nel journal si saltano
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScritturaSospesa {
+    pub journal: Option<PathBuf>,
+    pub opzioni: OpzioniScrittura,
+    pub richieste: Vec<RichiestaScrittura>,
+}
+
+impl ScritturaSospesa {
+    fn file() -> Option<PathBuf> {
+        crate::percorsi::dir_dati().map(|d| d.join("scrittura-sospesa.json"))
+    }
+
+    pub fn carica() -> Option<Self> {
+        let contenuto = std::fs::read(Self::file()?).ok()?;
+        match serde_json::from_slice(&contenuto) {
+            Ok(sospesa) => Some(sospesa),
+            Err(e) => {
+                eprintln!("[DEBUG] Scrittura sospesa illeggibile, ignorata: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn salva(&self) -> Result<(), String> {
+        let file = Self::file().ok_or("Impossibile determinare la cartella dei dati")?;
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Errore creazione {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_vec(self).map_err(|e| e.to_string())?;
+        let temporaneo = file.with_extension("json.tmp");
+        std::fs::write(&temporaneo, json)
+            .and_then(|_| std::fs::rename(&temporaneo, &file))
+            .map_err(|e| format!("Errore scrittura {}: {}", file.display(), e))
+    }
+
+    pub fn elimina() {
+        if let Some(file) = Self::file().filter(|f| f.exists()) {
+            if let Err(e) = std::fs::remove_file(&file) {
+                eprintln!("[DEBUG] Errore cancellazione {}: {}", file.display(), e);
+            }
+        }
+    }
+
+    // Richieste ancora da eseguire: senza quelle che il journal registra già come scritte
+    pub fn da_eseguire(&self) -> Vec<RichiestaScrittura> {
+        let scritte: std::collections::HashSet<PathBuf> = self.journal.as_deref()
+            .and_then(|j| crate::journal::leggi_journal(j).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|voce| match voce {
+                VoceJournal::Scrittura { path, .. } => Some(path),
+                _ => None,
+            })
+            .collect();
+        self.richieste.iter()
+            .filter(|r| !scritte.contains(&r.path))
+            .cloned()
+            .collect()
+    }
+
+    // Dopo un'esecuzione: se restano richieste non tentate il file le conserva, altrimenti sparisce
+    pub fn aggiorna_dopo(journal: Option<&Path>, opzioni: OpzioniScrittura, richieste: &[RichiestaScrittura], esiti: &[EsitoScrittura]) -> Option<Self> {
+        let tentate: std::collections::HashSet<&PathBuf> = esiti.iter().map(|e| &e.path).collect();
+        let restanti: Vec<RichiestaScrittura> = richieste.iter()
+            .filter(|r| !tentate.contains(&r.path))
+            .cloned()
+            .collect();
+        if restanti.is_empty() {
+            Self::elimina();
+            return None;
+        }
+        let sospesa = ScritturaSospesa { journal: journal.map(Path::to_path_buf), opzioni, richieste: restanti };
+        if let Err(e) = sospesa.salva() {
+            eprintln!("[DEBUG] {}", e);
+        }
+        Some(sospesa)
+    }
+}
+
+// Nomi dei tag assegnati dagli argomenti exiftool ("-TAG=", "-TAG+=", "-TAG-="), senza ripetizioni
+fn tag_scritti(args: &[String]) -> Vec<String> {
+    let mut tag: Vec<String> = Vec::new();
+    for arg in args {
+        let Some((nome, _)) = arg.strip_prefix('-').and_then(|a| a.split_once('=')) else { continue };
+        let nome = nome.trim_end_matches(['+', '-']);
+        if nome != "all" && !tag.iter().any(|t| t == nome) {
+            tag.push(nome.to_string());
+        }
+    }
+    tag
+}
+
+// Valori attuali dei tag (-n: numeri come exiftool li riscrive, GPS con il segno)
+fn leggi_tag_precedenti(file: &Path, tag: &[String]) -> Result<Vec<(String, Vec<String>)>, String> {
+    if tag.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut args = vec!["-j".to_string(), "-n".to_string()];
+    args.extend(tag.iter().map(|t| format!("-{}", t)));
+    let risposta = crate::exiftool::esegui(&args, file)
+        .map_err(|e| format!("Errore esecuzione exiftool per {}: {}", file.display(), e))?;
+    if !risposta.riuscita() {
+        return Err(format!("Lettura dei tag da {} fallita: {}", file.display(), risposta.stderr.trim()));
+    }
+    valori_da_json(tag, &risposta.stdout)
+}
+
+// exiftool -j riporta i tag col solo nome, senza gruppo; un tag assente non c'è
+fn valori_da_json(tag: &[String], json: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let oggetti: Vec<serde_json::Map<String, serde_json::Value>> = serde_json::from_str(json)
+        .map_err(|e| format!("Risposta di exiftool non valida: {}", e))?;
+    let letti = oggetti.into_iter().next().unwrap_or_default();
+    let testo = |v: &serde_json::Value| match v {
+        serde_json::Value::String(s) => s.clone(),
+        altro => altro.to_string(),
+    };
+    Ok(tag.iter().map(|t| {
+        let nome = t.rsplit(':').next().unwrap_or(t);
+        let valori = match letti.get(nome) {
+            Some(serde_json::Value::Array(lista)) => lista.iter().map(testo).collect(),
+            Some(valore) => vec![testo(valore)],
+            None => Vec::new(),
+        };
+        (t.clone(), valori)
+    }).collect())
+}
+
+// Un tag che non c'era viene tolto; più assegnazioni dello stesso tag lista lo sostituiscono
+fn argomenti_ripristino_tag(tag_precedenti: &[(String, Vec<String>)]) -> Vec<String> {
+    let mut args = Vec::new();
+    for (tag, valori) in tag_precedenti {
+        if valori.is_empty() {
+            args.push(format!("-{}=", tag));
+        }
+        args.extend(valori.iter().map(|v| format!("-{}={}", tag, v)));
+    }
+    args
+}
+
+// Riporta date, tag descrittivi e data del file ai valori registrati nel journal.
+// Con l'EXIF incorporato gli altri tag cancellati da -all= non si possono recuperare
+pub(crate) fn annulla_scrittura(path: &Path, sidecar: Option<&Path>, sidecar_creato: bool,
+                                precedenti: &[(String, Option<DateTime<Utc>>)], tag_precedenti: &[(String, Vec<String>)],
+                                date_file_precedenti: &[(PathBuf, i64)]) -> Result<(), String> {
+    match sidecar {
+        Some(xmp) if sidecar_creato => {
+            std::fs::remove_file(xmp)
+                .map_err(|e| format!("Errore cancellazione {}: {}", xmp.display(), e))?;
+        }
+        _ if !precedenti.is_empty() || !tag_precedenti.is_empty() => {
+            let destinazione = sidecar.unwrap_or(path);
+            let mut args = vec!["-overwrite_original".to_string(), "-P".to_string(), "-n".to_string()];
+            for (campo, valore) in precedenti {
+                // Un campo che prima non c'era viene tolto
+                args.push(match valore {
+                    Some(data) => format!("-{}={}", campo, data.format("%Y:%m:%d %H:%M:%S")),
+                    None => format!("-{}=", campo),
+                });
+            }
+            // Dopo le date: nel sidecar i valori letti dal file stesso sono quelli esatti
+            args.extend(argomenti_ripristino_tag(tag_precedenti));
+            let risposta = crate::exiftool::esegui(&args, destinazione)
+                .map_err(|e| format!("Errore esecuzione exiftool per {}: {}", destinazione.display(), e))?;
+            if !risposta.riuscita() {
+                return Err(format!("exiftool fallito per {}: {}", destinazione.display(), risposta.stderr.trim()));
+            }
+        }
+        _ => {}
+    }
+
+    for (file, secondi) in date_file_precedenti {
+        if file.exists() {
+            let tempo = filetime::FileTime::from_unix_time(*secondi, 0);
+            filetime::set_file_times(file, tempo, tempo)
+                .map_err(|e| format!("Errore ripristino data file {}: {}", file.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn imposta_data_file(path: &Path, data: DateTime<Utc>, fuso: FusoDataFile) -> Result<(), String> {
+    // Le date EXIF sono ore "da orologio" senza fuso: di norma le interpretiamo nel fuso locale
+    // così file manager e NAS mostrano la stessa ora della DateTimeOriginal
+    let secondi = match fuso {
+        FusoDataFile::Locale => Local.from_local_datetime(&data.naive_utc())
+            .earliest()
+            .map(|dt| dt.timestamp())
+            .unwrap_or_else(|| data.timestamp()),
+        FusoDataFile::Utc => data.timestamp(),
+    };
+    let tempo = filetime::FileTime::from_unix_time(secondi, 0);
+
+    filetime::set_file_times(path, tempo, tempo)
+        .map_err(|e| format!("Errore impostazione data file {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_scritti_dagli_argomenti_di_soli_metadati() {
+        let metadati = MetadatiGoogle {
+            descrizione: Some("Al mare".to_string()),
+            persone: vec!["Anna".to_string(), "Luca".to_string()],
+            preferita: true,
+        };
+        let args = crate::argomenti_scrittura_exiftool_con(&[], Some(&metadati), false);
+        assert_eq!(tag_scritti(&args), ["XMP-dc:Description", "EXIF:ImageDescription", "XMP-iptcExt:PersonInImage",
+                                        "XMP-dc:Subject", "IPTC:Keywords", "XMP-xmp:Rating"]);
+    }
+
+    #[test]
+    fn ripristino_dei_tag_letti_prima_della_scrittura() {
+        let tag: Vec<String> = ["XMP-dc:Description", "IPTC:Keywords", "XMP-xmp:Rating", "XMP-dc:Subject"]
+            .map(String::from).to_vec();
+        let json = r#"[{"SourceFile": "a.jpg", "Description": "Vecchia", "Keywords": ["mare", "estate"], "Rating": 3}]"#;
+        let precedenti = valori_da_json(&tag, json).unwrap();
+        assert_eq!(precedenti[1], ("IPTC:Keywords".to_string(), vec!["mare".to_string(), "estate".to_string()]));
+        assert_eq!(argomenti_ripristino_tag(&precedenti), [
+            "-XMP-dc:Description=Vecchia", "-IPTC:Keywords=mare", "-IPTC:Keywords=estate", "-XMP-xmp:Rating=3",
+            "-XMP-dc:Subject=", // Non c'era: viene tolto
+        ]);
+    }
+}
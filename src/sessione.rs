@@ -0,0 +1,218 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::FotoData;
+use crate::cache_scansione::Firma;
+use crate::date_manuali::DataManuale;
+use crate::filtri::Strategia;
+use crate::impostazioni::{ColonnaOrdinamento, ImpostazioniFiltri};
+
+// Sessione di revisione: per rivedere una libreria grande in più volte si salva quello
+// che non sta nei file (cartelle aperte, selezione, date manuali, strategie e filtri,
+// stato di revisione di ogni foto) e si riapre più tardi sulle stesse cartelle. Ogni foto
+// ha la firma (dimensione e data di modifica) che aveva al salvataggio: alla riapertura
+// si vede quali file sono cambiati nel frattempo e quali non ci sono più
+
+// Cambia se cambia il formato: le sessioni di un'altra versione non si aprono
+const VERSIONE_SESSIONE: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StatoRevisione {
+    #[serde(rename = "reviewed")]
+    Revisionata,
+    #[serde(rename = "skip")]
+    Saltata,
+    #[serde(rename = "needs-attention")]
+    DaControllare,
+}
+
+impl StatoRevisione {
+    pub const TUTTI: [StatoRevisione; 3] = [StatoRevisione::Revisionata, StatoRevisione::Saltata, StatoRevisione::DaControllare];
+
+    pub fn display_name(&self) -> &str {
+        match self {
+            StatoRevisione::Revisionata => "Reviewed",
+            StatoRevisione::Saltata => "Skip",
+            StatoRevisione::DaControllare => "Needs attention",
+        }
+    }
+
+    pub fn icona(&self) -> &str {
+        match self {
+            StatoRevisione::Revisionata => "✔",
+            StatoRevisione::Saltata => "⏭",
+            StatoRevisione::DaControllare => "⚠",
+        }
+    }
+}
+
+// Filtro della tabella per stato di revisione
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FiltroRevisione {
+    #[default]
+    Tutte,
+    DaRevisionare, // Senza stato
+    Stato(StatoRevisione),
+}
+
+impl FiltroRevisione {
+    pub fn display_name(&self) -> &str {
+        match self {
+            FiltroRevisione::Tutte => "Any review status",
+            FiltroRevisione::DaRevisionare => "Not reviewed yet",
+            FiltroRevisione::Stato(stato) => stato.display_name(),
+        }
+    }
+
+    pub fn accetta(&self, stato: Option<StatoRevisione>) -> bool {
+        match self {
+            FiltroRevisione::Tutte => true,
+            FiltroRevisione::DaRevisionare => stato.is_none(),
+            FiltroRevisione::Stato(voluto) => stato == Some(*voluto),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FotoSessione {
+    pub path: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    firma: Option<Firma>, // None per le foto lette da un archivio non estratto
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub selezionata: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stato: Option<StatoRevisione>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_manuale: Option<DataManuale>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_manuale_create_date: Option<DataManuale>,
+    pub strategia_datetime_original: String,
+    pub strategia_create_date: String,
+}
+
+impl FotoSessione {
+    pub fn da_foto(foto: &FotoData, selezionata: bool, stato: Option<StatoRevisione>) -> Self {
+        FotoSessione {
+            path: foto.path.clone(),
+            firma: foto.origine_archivio.is_none().then(|| Firma::di(&foto.path)).flatten(),
+            selezionata,
+            stato,
+            data_manuale: foto.data_manuale.clone(),
+            data_manuale_create_date: foto.data_manuale_create_date.clone(),
+            strategia_datetime_original: foto.strategia_datetime_original.clone(),
+            strategia_create_date: foto.strategia_create_date.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sessione {
+    versione: u32,
+    pub salvata: DateTime<Local>,
+    pub cartelle: Vec<PathBuf>,
+    #[serde(default)]
+    pub archivi: Vec<PathBuf>, // Archivi Takeout letti senza estrarli, al posto delle cartelle
+    pub strategia_datetime_original: Strategia,
+    pub strategia_create_date: Strategia,
+    pub filtri: ImpostazioniFiltri,
+    #[serde(default)]
+    pub filtro_revisione: FiltroRevisione,
+    pub colonna_ordinamento: Option<ColonnaOrdinamento>,
+    pub ordine_crescente: bool,
+    pub foto: Vec<FotoSessione>,
+}
+
+// Come è andata la riapertura sulle foto appena lette
+#[derive(Debug, Clone, Default)]
+pub struct EsitoRipristino {
+    pub selezionate: HashSet<usize>,
+    pub stati: HashMap<PathBuf, StatoRevisione>,
+    pub ritrovate: usize,
+    pub cambiate: Vec<PathBuf>, // Firma diversa da quella al salvataggio
+    pub sparite: Vec<PathBuf>,  // Nella sessione ma non più tra le foto lette
+    pub nuove: usize,           // Lette ma non nella sessione
+}
+
+impl Sessione {
+    // Foto e cartelle lette; strategie, filtri e ordinamento da impostare come nella vista
+    pub fn new(cartelle: Vec<PathBuf>, archivi: Vec<PathBuf>, foto: Vec<FotoSessione>) -> Self {
+        let predefinite = crate::impostazioni::Impostazioni::default();
+        Sessione {
+            versione: VERSIONE_SESSIONE,
+            salvata: Local::now(),
+            cartelle,
+            archivi,
+            strategia_datetime_original: predefinite.strategia_datetime_original,
+            strategia_create_date: predefinite.strategia_create_date,
+            filtri: predefinite.filtri,
+            filtro_revisione: FiltroRevisione::Tutte,
+            colonna_ordinamento: predefinite.colonna_ordinamento,
+            ordine_crescente: predefinite.ordine_crescente,
+            foto,
+        }
+    }
+
+    pub fn carica(file: &Path) -> Result<Self, String> {
+        let contenuto = fs::read(file).map_err(|e| format!("Errore lettura {}: {}", file.display(), e))?;
+        let sessione: Sessione = serde_json::from_slice(&contenuto)
+            .map_err(|e| format!("Sessione non valida {}: {}", file.display(), e))?;
+        if sessione.versione != VERSIONE_SESSIONE {
+            return Err(format!("Sessione {} di un'altra versione ({})", file.display(), sessione.versione));
+        }
+        Ok(sessione)
+    }
+
+    // Su un file temporaneo rinominato alla fine, come le impostazioni
+    pub fn salva(&self, file: &Path) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        let mut temporaneo = file.as_os_str().to_os_string();
+        temporaneo.push(".tmp");
+        let temporaneo = PathBuf::from(temporaneo);
+        fs::write(&temporaneo, json)
+            .and_then(|_| fs::rename(&temporaneo, file))
+            .map_err(|e| {
+                let _ = fs::remove_file(&temporaneo);
+                format!("Errore scrittura sessione {}: {}", file.display(), e)
+            })
+    }
+
+    // Rimette date manuali e strategie sulle foto lette e restituisce selezione e stati.
+    // Una foto già revisionata il cui file è cambiato torna da controllare
+    pub fn ripristina(&self, foto_list: &mut [FotoData]) -> EsitoRipristino {
+        let mut salvate: HashMap<&Path, &FotoSessione> = self.foto.iter().map(|f| (f.path.as_path(), f)).collect();
+        let mut esito = EsitoRipristino::default();
+        for (idx, foto) in foto_list.iter_mut().enumerate() {
+            let Some(salvata) = salvate.remove(foto.path.as_path()) else {
+                esito.nuove += 1;
+                continue;
+            };
+            esito.ritrovate += 1;
+            let cambiata = salvata.firma.is_some() && Firma::di(&foto.path) != salvata.firma;
+            if cambiata {
+                esito.cambiate.push(foto.path.clone());
+            }
+
+            foto.data_manuale = salvata.data_manuale.clone();
+            foto.data_manuale_create_date = salvata.data_manuale_create_date.clone();
+            foto.applica_strategie(&salvata.strategia_datetime_original, &salvata.strategia_create_date);
+
+            if salvata.selezionata {
+                esito.selezionate.insert(idx);
+            }
+            let stato = match salvata.stato {
+                Some(StatoRevisione::Revisionata) if cambiata => Some(StatoRevisione::DaControllare),
+                stato => stato,
+            };
+            if let Some(stato) = stato {
+                esito.stati.insert(foto.path.clone(), stato);
+            }
+        }
+        esito.sparite = salvate.into_keys().map(Path::to_path_buf).collect();
+        esito.sparite.sort();
+        esito.cambiate.sort();
+        esito
+    }
+}
@@ -0,0 +1,296 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::metadati::{MetadatiGoogle, PosizioneGps};
+
+// Dove scrivere le date corrette
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+pub enum ModalitaOutput {
+    #[value(name = "embedded")]
+    Incorporato,   // Scrive dentro il file con exiftool (comportamento storico)
+    #[value(name = "sidecar-raw")]
+    SidecarPerRaw, // RAW (ORF/NEF) in sidecar .xmp, gli altri dentro il file
+    #[value(name = "sidecar")]
+    SidecarSempre, // Nessun originale viene modificato
+}
+
+impl ModalitaOutput {
+    pub fn display_name(&self) -> &str {
+        match self {
+            ModalitaOutput::Incorporato => "Write into the file",
+            ModalitaOutput::SidecarPerRaw => "XMP sidecar for RAW, embedded for others",
+            ModalitaOutput::SidecarSempre => "XMP sidecar only (originals untouched)",
+        }
+    }
+
+    pub fn usa_sidecar(self, foto_path: &Path) -> bool {
+        match self {
+            ModalitaOutput::Incorporato => false,
+            ModalitaOutput::SidecarPerRaw => is_raw(foto_path),
+            ModalitaOutput::SidecarSempre => true,
+        }
+    }
+}
+
+// Convenzione per il nome del sidecar
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+pub enum NomeSidecar {
+    #[value(name = "with-extension")]
+    ConEstensione,   // IMG_1234.ORF.xmp (darktable, digiKam)
+    #[value(name = "without-extension")]
+    SenzaEstensione, // IMG_1234.xmp (Lightroom, Capture One)
+}
+
+impl NomeSidecar {
+    pub fn display_name(&self) -> &str {
+        match self {
+            NomeSidecar::ConEstensione => "IMG_1234.ORF.xmp (darktable)",
+            NomeSidecar::SenzaEstensione => "IMG_1234.xmp (Lightroom)",
+        }
+    }
+}
+
+pub fn is_raw(foto_path: &Path) -> bool {
+    foto_path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| matches!(e.to_lowercase().as_str(), "orf" | "nef"))
+        .unwrap_or(false)
+}
+
+pub fn percorso_sidecar(foto_path: &Path, nome: NomeSidecar) -> PathBuf {
+    match nome {
+        NomeSidecar::ConEstensione => {
+            let mut nome_file = foto_path.as_os_str().to_os_string();
+            nome_file.push(".xmp");
+            PathBuf::from(nome_file)
+        }
+        NomeSidecar::SenzaEstensione => foto_path.with_extension("xmp"),
+    }
+}
+
+pub fn trova_sidecar_xmp(foto_path: &Path) -> Option<PathBuf> {
+    // Prova entrambe le convenzioni, anche con estensione maiuscola
+    let directory = foto_path.parent()?;
+    let base_name = foto_path.file_stem()?.to_str()?;
+    let nome_file = foto_path.file_name()?.to_str()?;
+
+    let possibili_nomi = [
+        format!("{}.xmp", nome_file),
+        format!("{}.XMP", nome_file),
+        format!("{}.xmp", base_name),
+        format!("{}.XMP", base_name),
+    ];
+
+    for nome_xmp in possibili_nomi {
+        let xmp_path = directory.join(&nome_xmp);
+        if xmp_path.exists() {
+            return Some(xmp_path);
+        }
+    }
+
+    None
+}
+
+fn valore_xmp(contenuto: &str, tag: &str) -> Option<String> {
+    // Forma attributo: exif:DateTimeOriginal="..."
+    let re = Regex::new(&format!(r#"{}\s*=\s*"([^"]*)""#, regex::escape(tag))).ok()?;
+    if let Some(caps) = re.captures(contenuto) {
+        return Some(caps[1].to_string());
+    }
+
+    // Forma elemento: <exif:DateTimeOriginal>...</exif:DateTimeOriginal>
+    let re = Regex::new(&format!(r"<{0}>([^<]*)</{0}>", regex::escape(tag))).ok()?;
+    re.captures(contenuto).map(|caps| caps[1].to_string())
+}
+
+fn parse_data_xmp(valore: &str) -> Option<DateTime<Utc>> {
+    let valore = valore.trim();
+
+    // Con fuso orario (2015-07-04T12:00:00+02:00): come per l'EXIF teniamo l'ora locale
+    if let Ok(dt) = DateTime::parse_from_rfc3339(valore) {
+        return Some(DateTime::from_naive_utc_and_offset(dt.naive_local(), Utc));
+    }
+
+    for formato in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y:%m:%d %H:%M:%S"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(valore, formato) {
+            return Some(DateTime::from_naive_utc_and_offset(dt, Utc));
+        }
+    }
+
+    None
+}
+
+// (DateTimeOriginal, CreateDate) dal sidecar
+pub fn leggi_date_sidecar(xmp_path: &Path) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+    let contenuto = match fs::read_to_string(xmp_path) {
+        Ok(c) => c,
+        Err(_) => return (None, None),
+    };
+
+    let datetime_original = valore_xmp(&contenuto, "exif:DateTimeOriginal")
+        .or_else(|| valore_xmp(&contenuto, "photoshop:DateCreated"))
+        .and_then(|v| parse_data_xmp(&v));
+    let create_date = valore_xmp(&contenuto, "xmp:CreateDate")
+        .and_then(|v| parse_data_xmp(&v));
+
+    (datetime_original, create_date)
+}
+
+fn escape_xml(testo: &str) -> String {
+    testo.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn formatta_data_xmp(data: DateTime<Utc>) -> String {
+    data.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+// Formato XMP per le coordinate: "DDD,MM.mmmmmmK"
+fn formatta_coordinata_xmp(valore: f64, positivo: char, negativo: char) -> String {
+    let riferimento = if valore < 0.0 { negativo } else { positivo };
+    let assoluto = valore.abs();
+    let gradi = assoluto.trunc();
+    let minuti = (assoluto - gradi) * 60.0;
+    format!("{},{:.6}{}", gradi as u32, minuti, riferimento)
+}
+
+pub fn genera_xmp(campi: &[(&str, DateTime<Utc>)], metadati: Option<&MetadatiGoogle>, posizione: Option<&PosizioneGps>) -> String {
+    let mut attributi = Vec::new();
+    let mut elementi = Vec::new();
+
+    for (nome_campo, data) in campi {
+        match *nome_campo {
+            "DateTimeOriginal" => {
+                attributi.push(format!("exif:DateTimeOriginal=\"{}\"", formatta_data_xmp(*data)));
+                // Lightroom legge la data di scatto da photoshop:DateCreated
+                attributi.push(format!("photoshop:DateCreated=\"{}\"", formatta_data_xmp(*data)));
+            }
+            "CreateDate" => {
+                attributi.push(format!("xmp:CreateDate=\"{}\"", formatta_data_xmp(*data)));
+            }
+            "ModifyDate" => {
+                attributi.push(format!("xmp:ModifyDate=\"{}\"", formatta_data_xmp(*data)));
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(pos) = posizione {
+        attributi.push(format!("exif:GPSLatitude=\"{}\"", formatta_coordinata_xmp(pos.latitudine, 'N', 'S')));
+        attributi.push(format!("exif:GPSLongitude=\"{}\"", formatta_coordinata_xmp(pos.longitudine, 'E', 'W')));
+        if pos.altitudine != 0.0 {
+            let altitudine_ref = if pos.altitudine < 0.0 { 1 } else { 0 };
+            attributi.push(format!("exif:GPSAltitude=\"{}/100\"", (pos.altitudine.abs() * 100.0).round() as i64));
+            attributi.push(format!("exif:GPSAltitudeRef=\"{}\"", altitudine_ref));
+        }
+    }
+
+    if let Some(m) = metadati {
+        if m.preferita {
+            attributi.push("xmp:Rating=\"5\"".to_string());
+        }
+        if let Some(ref descrizione) = m.descrizione {
+            elementi.push(format!(
+                "   <dc:description>\n    <rdf:Alt>\n     <rdf:li xml:lang=\"x-default\">{}</rdf:li>\n    </rdf:Alt>\n   </dc:description>",
+                escape_xml(descrizione)));
+        }
+        if !m.persone.is_empty() {
+            let voci: Vec<String> = m.persone.iter()
+                .map(|p| format!("     <rdf:li>{}</rdf:li>", escape_xml(p)))
+                .collect();
+            let bag = format!("    <rdf:Bag>\n{}\n    </rdf:Bag>", voci.join("\n"));
+            elementi.push(format!("   <Iptc4xmpExt:PersonInImage>\n{}\n   </Iptc4xmpExt:PersonInImage>", bag));
+            elementi.push(format!("   <dc:subject>\n{}\n   </dc:subject>", bag));
+        }
+    }
+
+    let mut xmp = String::new();
+    xmp.push_str("<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n");
+    xmp.push_str("<x:xmpmeta xmlns:x=\"adobe:ns:meta/\" x:xmptk=\"corrigi-exif\">\n");
+    xmp.push_str(" <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n");
+    xmp.push_str("  <rdf:Description rdf:about=\"\"\n");
+    xmp.push_str("    xmlns:exif=\"http://ns.adobe.com/exif/1.0/\"\n");
+    xmp.push_str("    xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n");
+    xmp.push_str("    xmlns:photoshop=\"http://ns.adobe.com/photoshop/1.0/\"\n");
+    xmp.push_str("    xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n");
+    xmp.push_str("    xmlns:Iptc4xmpExt=\"http://iptc.org/std/Iptc4xmpExt/2008-02-29/\"");
+    for attributo in &attributi {
+        xmp.push_str("\n    ");
+        xmp.push_str(attributo);
+    }
+    if elementi.is_empty() {
+        xmp.push_str("/>\n");
+    } else {
+        xmp.push_str(">\n");
+        for elemento in &elementi {
+            xmp.push_str(elemento);
+            xmp.push('\n');
+        }
+        xmp.push_str("  </rdf:Description>\n");
+    }
+    xmp.push_str(" </rdf:RDF>\n");
+    xmp.push_str("</x:xmpmeta>\n");
+    xmp.push_str("<?xpacket end=\"w\"?>\n");
+    xmp
+}
+
+// Argomenti exiftool per aggiornare un sidecar esistente senza perdere
+// il resto del contenuto (es. cronologia di sviluppo di darktable)
+pub fn argomenti_aggiornamento_sidecar(campi: &[(&str, DateTime<Utc>)], metadati: Option<&MetadatiGoogle>, posizione: Option<&PosizioneGps>) -> Vec<String> {
+    let mut args = vec!["-overwrite_original".to_string()];
+
+    for (nome_campo, data) in campi {
+        let data_str = data.format("%Y:%m:%d %H:%M:%S").to_string();
+        match *nome_campo {
+            "DateTimeOriginal" => {
+                args.push(format!("-XMP-exif:DateTimeOriginal={}", data_str));
+                args.push(format!("-XMP-photoshop:DateCreated={}", data_str));
+            }
+            "CreateDate" => args.push(format!("-XMP-xmp:CreateDate={}", data_str)),
+            "ModifyDate" => args.push(format!("-XMP-xmp:ModifyDate={}", data_str)),
+            _ => {}
+        }
+    }
+
+    if let Some(pos) = posizione {
+        args.push(format!("-XMP-exif:GPSLatitude={}", pos.latitudine));
+        args.push(format!("-XMP-exif:GPSLongitude={}", pos.longitudine));
+        if pos.altitudine != 0.0 {
+            args.push(format!("-XMP-exif:GPSAltitude={}", pos.altitudine));
+        }
+    }
+
+    if let Some(m) = metadati {
+        // Nel sidecar solo tag XMP: gli argomenti EXIF/IPTC non avrebbero effetto
+        args.extend(crate::metadati::argomenti_exiftool_metadati(m)
+            .into_iter()
+            .filter(|a| a.starts_with("-XMP-")));
+    }
+
+    args
+}
+
+pub fn scrivi_sidecar_xmp(foto_path: &Path, nome: NomeSidecar, campi: &[(&str, DateTime<Utc>)], metadati: Option<&MetadatiGoogle>, posizione: Option<&PosizioneGps>) -> Result<PathBuf, String> {
+    // Se esiste già un sidecar (con qualsiasi convenzione) lo aggiorniamo
+    let xmp_path = trova_sidecar_xmp(foto_path).unwrap_or_else(|| percorso_sidecar(foto_path, nome));
+
+    if !xmp_path.exists() {
+        fs::write(&xmp_path, genera_xmp(campi, metadati, posizione))
+            .map_err(|e| format!("Errore scrittura sidecar {}: {}", xmp_path.display(), e))?;
+        return Ok(xmp_path);
+    }
+
+    let args = argomenti_aggiornamento_sidecar(campi, metadati, posizione);
+    let risposta = crate::exiftool::esegui(&args, &xmp_path)
+        .map_err(|e| format!("Errore esecuzione exiftool per {}: {}", xmp_path.display(), e))?;
+
+    if risposta.riuscita() {
+        Ok(xmp_path)
+    } else {
+        Err(format!("exiftool fallito per {} (exit code {}):\nSTDOUT: {}\nSTDERR: {}",
+                    xmp_path.display(), risposta.codice_uscita, risposta.stdout, risposta.stderr))
+    }
+}
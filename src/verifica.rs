@@ -0,0 +1,147 @@
+use chrono::{DateTime, Timelike, Utc};
+use exif::Tag;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Controllo dopo la scrittura: il file si rilegge con lo stesso lettore della scansione
+// e ogni tag scritto si confronta con il valore voluto. exiftool può uscire con successo
+// e lasciare comunque un valore diverso (un tag duplicato in un altro IFD, un MakerNote
+// che il lettore preferisce), e qui si vede come lo vedrà la prossima scansione
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discrepanza {
+    pub tag: String,
+    pub atteso: String,
+    pub letto: Option<String>,
+}
+
+impl fmt::Display for Discrepanza {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: atteso {}, letto {}", self.tag, self.atteso, self.letto.as_deref().unwrap_or("nulla"))
+    }
+}
+
+fn formatta(data: DateTime<Utc>) -> String {
+    data.format("%Y:%m:%d %H:%M:%S").to_string()
+}
+
+// L'EXIF e il sidecar tengono i secondi: la frazione della data voluta non conta
+fn confronta_date(campi: &[(&str, DateTime<Utc>)], leggi: impl Fn(&str) -> Option<Option<DateTime<Utc>>>) -> Vec<Discrepanza> {
+    campi.iter()
+        .filter_map(|(campo, atteso)| {
+            // None: tag che questo lettore non conosce, non si può controllare
+            let letto = leggi(campo)?;
+            let atteso = atteso.with_nanosecond(0).unwrap_or(*atteso);
+            (letto != Some(atteso)).then(|| Discrepanza {
+                tag: campo.to_string(),
+                atteso: formatta(atteso),
+                letto: letto.map(formatta),
+            })
+        })
+        .collect()
+}
+
+// Date (e descrizione) rilette dall'EXIF incorporato. Err se il file non è più leggibile;
+// i RAW che il lettore non sa aprire (ORF) non si controllano
+pub fn verifica_exif(path: &Path, campi: &[(&str, DateTime<Utc>)], descrizione: Option<&str>) -> Result<Vec<Discrepanza>, String> {
+    let exif_data = fs::File::open(path)
+        .map_err(exif::Error::Io)
+        .and_then(|file| exif::Reader::new().read_from_container(&mut std::io::BufReader::new(&file)));
+    let exif_data = match exif_data {
+        Ok(exif_data) => Some(exif_data),
+        // Nessun blocco EXIF: ogni tag scritto risulta mancante
+        Err(exif::Error::NotFound(_)) => None,
+        Err(e) if crate::sidecar::is_raw(path) => {
+            eprintln!("[DEBUG] Verifica saltata per {}: EXIF non leggibile ({})", path.display(), e);
+            return Ok(Vec::new());
+        }
+        Err(e) => return Err(format!("EXIF illeggibile dopo la scrittura: {}", e)),
+    };
+
+    let mut discrepanze = confronta_date(campi, |campo| {
+        let tag = match campo {
+            "DateTimeOriginal" => Tag::DateTimeOriginal,
+            "CreateDate" => Tag::DateTimeDigitized,
+            "ModifyDate" => Tag::DateTime,
+            _ => return None,
+        };
+        Some(exif_data.as_ref().and_then(|e| crate::data_da_exif(e, tag)))
+    });
+
+    if let Some(descrizione) = descrizione.map(str::trim).filter(|d| !d.is_empty()) {
+        let letta = exif_data.as_ref().and_then(|e| crate::testo_da_exif(e, Tag::ImageDescription));
+        if letta.as_deref() != Some(descrizione) {
+            discrepanze.push(Discrepanza { tag: "ImageDescription".to_string(), atteso: descrizione.to_string(), letto: letta });
+        }
+    }
+    Ok(discrepanze)
+}
+
+// Date rilette dal sidecar XMP appena scritto
+pub fn verifica_sidecar(xmp_path: &Path, campi: &[(&str, DateTime<Utc>)]) -> Vec<Discrepanza> {
+    let (datetime_original, create_date) = crate::sidecar::leggi_date_sidecar(xmp_path);
+    confronta_date(campi, |campo| match campo {
+        "DateTimeOriginal" => Some(datetime_original),
+        "CreateDate" => Some(create_date),
+        _ => None,
+    })
+}
+
+// Impronta dei dati dell'immagine di un JPEG, senza i metadati: tabelle, intestazioni e
+// dati compressi, saltando i segmenti APPn (EXIF, XMP, ICC, ...) e i commenti, fino a EOI.
+// Uguale prima e dopo la scrittura se exiftool ha toccato solo i metadati.
+// None se il file non è un JPEG (per i RAW i dati dell'immagine non si isolano così)
+pub fn impronta_immagine(path: &Path) -> Result<Option<blake3::Hash>, String> {
+    let dati = fs::read(path).map_err(|e| format!("Errore lettura {}: {}", path.display(), e))?;
+    if !dati.starts_with(&[0xFF, 0xD8]) {
+        return Ok(None);
+    }
+    let troncato = || format!("JPEG troncato: {}", path.display());
+
+    let mut hasher = blake3::Hasher::new();
+    let mut i = 2;
+    loop {
+        // Marcatore, con eventuali 0xFF di riempimento davanti
+        if dati.get(i) != Some(&0xFF) {
+            return Err(troncato());
+        }
+        while dati.get(i) == Some(&0xFF) {
+            i += 1;
+        }
+        let marcatore = *dati.get(i).ok_or_else(troncato)?;
+        i += 1;
+        match marcatore {
+            0xD9 => break, // EOI: quello che segue (anteprime accodate) non è l'immagine
+            0x01 | 0xD0..=0xD7 => {
+                hasher.update(&[marcatore]);
+                continue;
+            }
+            _ => {}
+        }
+
+        let lunghezza = dati.get(i..i + 2).map(|l| u16::from_be_bytes([l[0], l[1]]) as usize).ok_or_else(troncato)?;
+        let segmento = dati.get(i..i + lunghezza).ok_or_else(troncato)?;
+        i += lunghezza;
+        if matches!(marcatore, 0xE0..=0xEF | 0xFE) {
+            continue;
+        }
+        hasher.update(&[marcatore]);
+        hasher.update(segmento);
+
+        if marcatore == 0xDA {
+            // Dati compressi fino al prossimo marcatore: 0xFF 0x00 è un byte di dati, RSTn sta nella scansione
+            let inizio = i;
+            loop {
+                match (dati.get(i), dati.get(i + 1)) {
+                    (None, _) => return Err(troncato()),
+                    (Some(0xFF), Some(0x00 | 0xD0..=0xD7)) => i += 2,
+                    (Some(0xFF), _) => break,
+                    _ => i += 1,
+                }
+            }
+            hasher.update(&dati[inizio..i]);
+        }
+    }
+    Ok(Some(hasher.finalize()))
+}